@@ -1,30 +1,432 @@
-use crate::core::mining::MiningResult;
-use hex;
+//! Blocks and their binary header.
+//!
+//! A version 1 header is exactly [`HEADER_LEN`] bytes, fields in this order
+//! with integers little-endian:
+//!
+//! ```text
+//! offset  size  field
+//!      0     4  version (u32)
+//!      4     8  height (u64)
+//!     12    32  previous block hash
+//!     44    32  merkle root of the txids
+//!     76    32  state root after the block's transactions
+//!    108    21  producer address
+//!    129     8  timestamp, seconds since the Unix epoch (u64)
+//!    137     1  difficulty: fractal depth (u8)
+//!    138    16  difficulty: area threshold (decimal)
+//!    154     1  mining strategy (u8): 0 deterministic, 1 chaotic
+//!    155     1  fractal address: depth (u8)
+//!    156    32  fractal address: digits, two bits each, zero past the depth
+//!    188    32  triangle commitment
+//!    220     8  nonce (u64)
+//! ```
+//!
+//! A decimal is its normalized form in `rust_decimal`'s 16-byte layout: a
+//! `u32` of flags (scale in bits 16-23, sign in bit 31) then the 96-bit
+//! mantissa as three `u32` words, low word first.
+//!
+//! The block hash is the SHA-256 of the header. The proof-of-work hash is
+//! the SHA-256 of the header with the address and commitment bytes zeroed,
+//! since those are what the proof produces.
+//!
+//! A whole block is the header, a `u32` transaction count, each
+//! transaction as a `u32` length and its canonical encoding, then the
+//! producer's [`Witness`] over the block hash as a `u32` length and its
+//! bytes. The genesis block has no producer and an empty witness.
+
+use crate::core::mining::{Difficulty, MiningStrategy};
+use crate::crypto::crypto::{Address, CryptoError, KeyPair, Witness, ADDRESS_LEN};
+use crate::geometry::hierarchy::FractalAddress;
+use crate::geometry::subdivision::triangle_at;
+use crate::geometry::triangle::Triangle;
+use crate::transaction::Transaction;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fmt;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Block {
-    pub index: u64,
+pub const BLOCK_VERSION: u32 = 1;
+pub const HEADER_LEN: usize = 228;
+
+const PRODUCER_AT: usize = 108;
+const TIMESTAMP_AT: usize = PRODUCER_AT + ADDRESS_LEN;
+const STRATEGY_AT: usize = 154;
+const ADDRESS_AT: usize = 155;
+const COMMITMENT_AT: usize = 188;
+const NONCE_AT: usize = 220;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub version: u32,
+    pub height: u64,
+    pub previous_hash: [u8; 32],
+    pub merkle_root: [u8; 32],
+    /// Root of the account and territory state once the block is applied.
+    pub state_root: [u8; 32],
+    /// Who mined the block; the proof of work commits to it.
+    pub producer: Address,
     pub timestamp: u64,
-    pub transactions: Vec<String>, // Placeholder for transactions
-    pub previous_hash: String,
-    pub hash: String,
-    pub mining_result: MiningResult,
+    pub difficulty: Difficulty,
+    pub strategy: MiningStrategy,
+    pub address: FractalAddress,
+    pub triangle_commitment: [u8; 32],
+    pub nonce: u64,
+}
+
+/// Why bytes are not a valid block or header encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockDecodeError {
+    UnexpectedEnd,
+    UnsupportedVersion(u32),
+    UnknownStrategy(u8),
+    /// A field has more than one encoding and this is not the canonical one.
+    NonCanonical(&'static str),
+    Transaction(crate::transaction::DecodeError),
+    TrailingBytes,
+}
+
+impl fmt::Display for BlockDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockDecodeError::UnexpectedEnd => write!(f, "block encoding ends early"),
+            BlockDecodeError::UnsupportedVersion(version) => write!(f, "unsupported block version {}", version),
+            BlockDecodeError::UnknownStrategy(tag) => write!(f, "unknown mining strategy {}", tag),
+            BlockDecodeError::NonCanonical(field) => write!(f, "{} is not canonically encoded", field),
+            BlockDecodeError::Transaction(e) => write!(f, "{}", e),
+            BlockDecodeError::TrailingBytes => write!(f, "bytes left over after block"),
+        }
+    }
+}
+
+impl std::error::Error for BlockDecodeError {}
+
+#[cfg(test)]
+impl BlockHeader {
+    /// A header at height 0 with the initial difficulty and everything
+    /// else zeroed, for tests to override the fields they care about.
+    pub fn test_template() -> Self {
+        BlockHeader {
+            version: BLOCK_VERSION,
+            height: 0,
+            previous_hash: [0; 32],
+            merkle_root: [0; 32],
+            state_root: [0; 32],
+            producer: Address::default(),
+            timestamp: 0,
+            difficulty: Difficulty::initial(),
+            strategy: MiningStrategy::Deterministic,
+            address: FractalAddress::root(),
+            triangle_commitment: [0; 32],
+            nonce: 0,
+        }
+    }
+}
+
+impl BlockHeader {
+    pub fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0; HEADER_LEN];
+        out[0..4].copy_from_slice(&self.version.to_le_bytes());
+        out[4..12].copy_from_slice(&self.height.to_le_bytes());
+        out[12..44].copy_from_slice(&self.previous_hash);
+        out[44..76].copy_from_slice(&self.merkle_root);
+        out[76..108].copy_from_slice(&self.state_root);
+        out[PRODUCER_AT..TIMESTAMP_AT].copy_from_slice(self.producer.as_bytes());
+        out[TIMESTAMP_AT..TIMESTAMP_AT + 8].copy_from_slice(&self.timestamp.to_le_bytes());
+        out[TIMESTAMP_AT + 8] = self.difficulty.depth;
+        out[TIMESTAMP_AT + 9..STRATEGY_AT].copy_from_slice(&encode_decimal(self.difficulty.threshold));
+        out[STRATEGY_AT] = self.strategy.as_u8();
+        out[ADDRESS_AT] = self.address.depth() as u8;
+        out[ADDRESS_AT + 1..COMMITMENT_AT].copy_from_slice(self.address.as_bytes());
+        out[COMMITMENT_AT..NONCE_AT].copy_from_slice(&self.triangle_commitment);
+        out[NONCE_AT..].copy_from_slice(&self.nonce.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, BlockDecodeError> {
+        let bytes: &[u8; HEADER_LEN] = bytes.try_into().map_err(|_| BlockDecodeError::UnexpectedEnd)?;
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        let hash_at = |at: usize| -> [u8; 32] { bytes[at..at + 32].try_into().expect("32 bytes") };
+        let version = u32::from_le_bytes(bytes[0..4].try_into().expect("4 bytes"));
+        if version != BLOCK_VERSION {
+            return Err(BlockDecodeError::UnsupportedVersion(version));
+        }
+        let threshold = decode_decimal(bytes[TIMESTAMP_AT + 9..STRATEGY_AT].try_into().expect("16 bytes"))?;
+        let strategy = MiningStrategy::from_u8(bytes[STRATEGY_AT]).ok_or(BlockDecodeError::UnknownStrategy(bytes[STRATEGY_AT]))?;
+        let address = FractalAddress::from_bytes(&hash_at(ADDRESS_AT + 1), bytes[ADDRESS_AT] as usize)
+            .map_err(|_| BlockDecodeError::NonCanonical("fractal address"))?;
+        if address.as_bytes()[..] != bytes[ADDRESS_AT + 1..COMMITMENT_AT] {
+            return Err(BlockDecodeError::NonCanonical("fractal address"));
+        }
+        Ok(BlockHeader {
+            version,
+            height: u64_at(4),
+            previous_hash: hash_at(12),
+            merkle_root: hash_at(44),
+            state_root: hash_at(76),
+            producer: Address::from_bytes(bytes[PRODUCER_AT..TIMESTAMP_AT].try_into().expect("21 bytes")),
+            timestamp: u64_at(TIMESTAMP_AT),
+            difficulty: Difficulty { depth: bytes[TIMESTAMP_AT + 8], threshold },
+            strategy,
+            address,
+            triangle_commitment: hash_at(COMMITMENT_AT),
+            nonce: u64_at(NONCE_AT),
+        })
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(self.encode()).into()
+    }
+
+    /// Hash of every field the proof of work is bound to.
+    pub fn pow_hash(&self) -> [u8; 32] {
+        let mut bytes = self.encode();
+        bytes[ADDRESS_AT..NONCE_AT].fill(0);
+        Sha256::digest(bytes).into()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Block {
+    pub header: BlockHeader,
+    pub transactions: Vec<Transaction>,
+    /// The producer's encoded [`Witness`] over the block hash.
+    pub signature: Vec<u8>,
 }
 
 impl Block {
-    pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        let record = format!(
-            "{}{}{:?}{}{}",
-            self.index,
-            self.timestamp,
-            self.transactions,
-            self.previous_hash,
-            self.mining_result.address.0.iter().map(|&x| x.to_string()).collect::<String>()
-        );
-        hasher.update(record.as_bytes());
-        hex::encode(hasher.finalize())
+    pub fn hash(&self) -> [u8; 32] {
+        self.header.hash()
+    }
+
+    pub fn height(&self) -> u64 {
+        self.header.height
+    }
+
+    pub fn previous_hash(&self) -> &[u8; 32] {
+        &self.header.previous_hash
+    }
+
+    /// Merkle root over the txids, in block order.
+    pub fn merkle_root(&self) -> [u8; 32] {
+        transactions_root(&self.transactions)
+    }
+
+    /// Signs the mined header as `keypair`, which must be the producer.
+    pub fn sign(&mut self, keypair: &KeyPair) -> Result<(), CryptoError> {
+        if keypair.address() != self.header.producer {
+            return Err(CryptoError::WrongSigner);
+        }
+        self.signature = Witness::sign(keypair, &self.hash())?.encode();
+        Ok(())
+    }
+
+    /// Checks that the header's producer signed the block hash.
+    pub fn verify_signature(&self) -> Result<(), CryptoError> {
+        Witness::decode(&self.signature)?.verify(&self.header.producer, &self.hash())
+    }
+
+    /// The triangle the proof of work landed on.
+    pub fn triangle(&self) -> Triangle {
+        triangle_at(&self.header.address)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = self.header.encode().to_vec();
+        out.extend_from_slice(&(self.transactions.len() as u32).to_le_bytes());
+        for tx in &self.transactions {
+            let bytes = tx.encode();
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(&bytes);
+        }
+        out.extend_from_slice(&(self.signature.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, BlockDecodeError> {
+        let header = BlockHeader::decode(bytes.get(..HEADER_LEN).ok_or(BlockDecodeError::UnexpectedEnd)?)?;
+        let mut rest = &bytes[HEADER_LEN..];
+        let mut take = |len: usize| -> Result<&[u8], BlockDecodeError> {
+            if rest.len() < len {
+                return Err(BlockDecodeError::UnexpectedEnd);
+            }
+            let (head, tail) = rest.split_at(len);
+            rest = tail;
+            Ok(head)
+        };
+        let u32_of = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().expect("4 bytes")) as usize;
+        let count = u32_of(take(4)?);
+        let mut transactions = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            let len = u32_of(take(4)?);
+            transactions.push(Transaction::decode(take(len)?).map_err(BlockDecodeError::Transaction)?);
+        }
+        let len = u32_of(take(4)?);
+        let signature = take(len)?.to_vec();
+        if !rest.is_empty() {
+            return Err(BlockDecodeError::TrailingBytes);
+        }
+        Ok(Block { header, transactions, signature })
+    }
+}
+
+/// Merkle root committing to `transactions`, as mined into a block header.
+pub fn transactions_root(transactions: &[Transaction]) -> [u8; 32] {
+    let txids: Vec<[u8; 32]> = transactions.iter().map(Transaction::txid).collect();
+    crate::crypto::hash::merkle_root(&txids)
+}
+
+/// SHA-256 over the triangle's coordinates, `a`, `b`, `c`, `x` before `y`,
+/// each in the header's decimal encoding.
+pub fn triangle_commitment(triangle: &Triangle) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for point in [triangle.a, triangle.b, triangle.c] {
+        hasher.update(encode_decimal(point.x));
+        hasher.update(encode_decimal(point.y));
+    }
+    hasher.finalize().into()
+}
+
+fn encode_decimal(value: Decimal) -> [u8; 16] {
+    value.normalize().serialize()
+}
+
+fn decode_decimal(bytes: [u8; 16]) -> Result<Decimal, BlockDecodeError> {
+    // Reject flag bits `Decimal` ignores as well as unnormalized values.
+    let flags = u32::from_le_bytes(bytes[..4].try_into().expect("4 bytes"));
+    let value = Decimal::deserialize(bytes);
+    if flags & !0x80ff_0000 != 0 || encode_decimal(value) != bytes {
+        return Err(BlockDecodeError::NonCanonical("decimal"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::crypto::{Ed25519, SignatureScheme};
+    use crate::transaction::TxMeta;
+    use rust_decimal_macros::dec;
+
+    fn sample_header() -> BlockHeader {
+        BlockHeader {
+            version: BLOCK_VERSION,
+            height: 1,
+            previous_hash: [0x11; 32],
+            merkle_root: [0x22; 32],
+            state_root: [0x44; 32],
+            producer: Address::from_bytes([0x55; ADDRESS_LEN]),
+            timestamp: 23,
+            difficulty: Difficulty { depth: 5, threshold: dec!(0.0025) },
+            strategy: MiningStrategy::Chaotic,
+            address: FractalAddress::from_digits(&[3, 2, 1, 0, 3]).unwrap(),
+            triangle_commitment: [0x33; 32],
+            nonce: 0x0102030405060708,
+        }
+    }
+
+    #[test]
+    fn test_header_layout() {
+        let encoded = sample_header().encode();
+        let expected = [
+            &[1, 0, 0, 0][..],
+            &[1, 0, 0, 0, 0, 0, 0, 0],
+            &[0x11; 32],
+            &[0x22; 32],
+            &[0x44; 32],
+            &[0x55; 21],
+            &[23, 0, 0, 0, 0, 0, 0, 0],
+            &[5],
+            // 0.0025 = 25 * 10^-4: scale 4, mantissa 25.
+            &[0, 0, 4, 0, 25, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            &[1],
+            &[5, 0b1110_0100, 0b1100_0000],
+            &[0; 30],
+            &[0x33; 32],
+            &[8, 7, 6, 5, 4, 3, 2, 1],
+        ]
+        .concat();
+        assert_eq!(encoded.to_vec(), expected);
+    }
+
+    #[test]
+    fn test_header_hash_vectors() {
+        let header = sample_header();
+        assert_eq!(hex::encode(header.hash()), "90440908a4fe982940e0b9eca0c8acd8f501a528a7adce5c31fa7cb41ee54ddd");
+        assert_eq!(hex::encode(header.pow_hash()), "149620cb61c3392727ac3f39b21934572591f4a0129d7a75a700064cd4e0d8e4");
+    }
+
+    #[test]
+    fn test_fields_are_unambiguous() {
+        // index 1 / timestamp 23 and index 12 / timestamp 3 used to hash alike.
+        let a = BlockHeader { height: 1, timestamp: 23, ..sample_header() };
+        let b = BlockHeader { height: 12, timestamp: 3, ..sample_header() };
+        assert_ne!(a.hash(), b.hash());
+    }
+
+    #[test]
+    fn test_pow_hash_ignores_proof_output_only() {
+        let header = sample_header();
+        let moved = BlockHeader { address: FractalAddress::from_digits(&[0; 5]).unwrap(), triangle_commitment: [0; 32], ..header };
+        assert_eq!(header.pow_hash(), moved.pow_hash());
+        assert_ne!(header.hash(), moved.hash());
+        assert_ne!(header.pow_hash(), BlockHeader { nonce: 1, ..header }.pow_hash());
+    }
+
+    #[test]
+    fn test_header_round_trip_and_canonical_checks() {
+        let header = sample_header();
+        let bytes = header.encode();
+        assert_eq!(BlockHeader::decode(&bytes), Ok(header));
+
+        let mut stray_digit = bytes;
+        stray_digit[ADDRESS_AT + 2] |= 1;
+        assert_eq!(BlockHeader::decode(&stray_digit), Err(BlockDecodeError::NonCanonical("fractal address")));
+
+        let mut unnormalized = bytes;
+        unnormalized[TIMESTAMP_AT + 9..STRATEGY_AT].copy_from_slice(&dec!(0.00250).serialize());
+        assert_eq!(BlockHeader::decode(&unnormalized), Err(BlockDecodeError::NonCanonical("decimal")));
+
+        let mut unknown_strategy = bytes;
+        unknown_strategy[STRATEGY_AT] = 2;
+        assert_eq!(BlockHeader::decode(&unknown_strategy), Err(BlockDecodeError::UnknownStrategy(2)));
+
+        let mut future = bytes;
+        future[0] = 2;
+        assert_eq!(BlockHeader::decode(&future), Err(BlockDecodeError::UnsupportedVersion(2)));
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let meta = TxMeta { sender: "alice".to_string(), nonce: 0, fee: 1, signature: vec![1, 2] };
+        let block = Block {
+            header: sample_header(),
+            transactions: vec![Transaction::Transfer { meta, to: "bob".to_string(), amount: 3 }],
+            signature: vec![4, 5, 6],
+        };
+        let bytes = block.encode();
+        assert_eq!(Block::decode(&bytes), Ok(block));
+        assert_eq!(Block::decode(&bytes[..bytes.len() - 1]), Err(BlockDecodeError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn test_producer_signature() {
+        let keypair = Ed25519.keypair_from_seed(&[9; 32]);
+        let header = BlockHeader { producer: keypair.address(), ..sample_header() };
+        let mut block = Block { header, transactions: vec![], signature: vec![] };
+        assert_eq!(block.verify_signature(), Err(CryptoError::InvalidSignature));
+        block.sign(&keypair).unwrap();
+        assert_eq!(block.verify_signature(), Ok(()));
+
+        let mut renonced = block.clone();
+        renonced.header.nonce += 1;
+        assert_eq!(renonced.verify_signature(), Err(CryptoError::VerificationFailed));
+
+        // Someone else's signature does not make them the producer.
+        let thief = Ed25519.keypair_from_seed(&[10; 32]);
+        assert_eq!(block.clone().sign(&thief), Err(CryptoError::WrongSigner));
+        let mut stolen = block.clone();
+        stolen.signature = Witness::sign(&thief, &block.hash()).unwrap().encode();
+        assert_eq!(stolen.verify_signature(), Err(CryptoError::WrongSigner));
     }
 }
@@ -1,40 +1,660 @@
-use crate::block::Block;
-use crate::core::mining::mine_genesis;
-use rust_decimal_macros::dec;
+use crate::block::{transactions_root, Block, BlockHeader, BLOCK_VERSION};
+use crate::core::consensus::{BlockValidationError, Consensus, DefaultConsensus};
+use crate::core::mining::deterministic::DeterministicMiner;
+use crate::core::mining::{self, Difficulty, Miner, MiningStrategy, HEADER_CONTEXT};
+use crate::crypto::crypto::Address;
+use crate::geometry::hierarchy::FractalAddress;
+use crate::state::State;
+use crate::storage::block_store::{BlockStore, StoreError};
+use crate::storage::memory::MemoryBlockStore;
+use crate::storage::segment::SegmentStore;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Timestamp of the genesis block, fixed so that every node builds the same one.
+pub const GENESIS_TIMESTAMP: u64 = 1_700_000_000;
+/// Furthest ahead of this node's clock a new block's timestamp may be.
+pub const MAX_FUTURE_DRIFT: u64 = 2 * 60 * 60;
+
+/// Errors from adding a block or loading a chain out of its store.
+#[derive(Debug)]
+pub enum ChainError {
+    Store(StoreError),
+    /// A block does not validate against its parent.
+    InvalidBlock { height: u64, error: BlockValidationError },
+    /// The block's parent is not known.
+    UnknownParent([u8; 32]),
+    /// The store starts with a different genesis block than this node's.
+    GenesisMismatch,
+    /// The chain state refused the block, or it descends from one it refused.
+    State { hash: [u8; 32], reason: String },
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::Store(e) => write!(f, "{}", e),
+            ChainError::InvalidBlock { height, error } => write!(f, "invalid block at height {}: {:?}", height, error),
+            ChainError::UnknownParent(hash) => write!(f, "unknown parent block {}", hex::encode(hash)),
+            ChainError::GenesisMismatch => write!(f, "stored chain has a different genesis block"),
+            ChainError::State { hash, reason } => write!(f, "block {} rejected by chain state: {}", hex::encode(hash), reason),
+        }
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+impl From<StoreError> for ChainError {
+    fn from(e: StoreError) -> Self {
+        ChainError::Store(e)
+    }
+}
+
+/// State derived from the best chain, such as balances and territory
+/// ownership.
+///
+/// Blocks are connected in chain order and disconnected newest first, so an
+/// implementation can keep undo data for each block it connects and pop it
+/// when that block is disconnected in a reorganization.
+pub trait ChainState {
+    /// Applies `block`, or leaves the state unchanged and says why it cannot.
+    fn connect_block(&mut self, block: &Block) -> Result<(), String>;
+    /// Reverts `block`, which is always the most recently connected one.
+    fn disconnect_block(&mut self, block: &Block);
+}
+
+/// No derived state.
+impl ChainState for () {
+    fn connect_block(&mut self, _block: &Block) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn disconnect_block(&mut self, _block: &Block) {}
+}
+
+/// A state shared with whoever else needs to read it, such as a miner
+/// assembling templates.
+impl<S: ChainState> ChainState for Rc<RefCell<S>> {
+    fn connect_block(&mut self, block: &Block) -> Result<(), String> {
+        self.borrow_mut().connect_block(block)
+    }
+
+    fn disconnect_block(&mut self, block: &Block) {
+        self.borrow_mut().disconnect_block(block)
+    }
+}
+
+/// What [`Blockchain::add_block`] did with a valid block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockOutcome {
+    AlreadyKnown,
+    /// The block extended the best chain.
+    Extended,
+    /// The block was stored on a branch lighter than the best chain.
+    SideBranch,
+    /// The block's branch became the best chain.
+    Reorganized { disconnected: usize, connected: usize },
+}
+
+/// A change to the best chain, for components that follow it, such as the
+/// mempool.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+    Connected(Block),
+    /// Taken off the best chain by a reorganization.
+    Disconnected(Block),
+}
+
+struct TreeEntry {
+    block: Block,
+    /// Cumulative work from genesis through this block.
+    work: u128,
+}
+
+/// The tree of every valid block seen, and the best chain through it.
 pub struct Blockchain {
-    pub blocks: Vec<Block>,
+    /// The best chain, genesis first. Only fork choice changes it, so that
+    /// it always matches the tree, the store and the state.
+    blocks: Vec<Block>,
+    tree: HashMap<[u8; 32], TreeEntry>,
+    invalid: HashSet<[u8; 32]>,
+    store: Box<dyn BlockStore>,
+    state: Box<dyn ChainState>,
+    /// Changes to the best chain since the last [`Blockchain::take_events`].
+    events: Vec<ChainEvent>,
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Blockchain {
+    /// A fresh chain held only in memory.
     pub fn new() -> Self {
-        let genesis_block = Self::create_genesis_block();
-        Blockchain { blocks: vec![genesis_block] }
-    }
-
-    fn create_genesis_block() -> Block {
-        loop {
-            if let Some(mining_result) = mine_genesis(1, dec!(1e-6)) {
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                let mut block = Block {
-                    index: 0,
-                    timestamp,
-                    transactions: vec![],
-                    previous_hash: "0".to_string(),
-                    hash: "".to_string(),
-                    mining_result,
-                };
-                block.hash = block.calculate_hash();
-                return block;
+        Self::with_store(Box::new(MemoryBlockStore::new()), Box::new(())).expect("empty memory store accepts genesis")
+    }
+
+    /// Opens the chain persisted under `path`, starting it with the genesis
+    /// block if the store is new, and connects its best chain to `state`.
+    pub fn open(path: impl AsRef<Path>, state: Box<dyn ChainState>) -> Result<Self, ChainError> {
+        Self::with_store(Box::new(SegmentStore::open(path)?), state)
+    }
+
+    /// Replays every block in `store` through validation and fork choice,
+    /// connecting the best chain to `state`.
+    pub fn with_store(mut store: Box<dyn BlockStore>, mut state: Box<dyn ChainState>) -> Result<Self, ChainError> {
+        let genesis = genesis_block();
+        let stored = store.load_all()?;
+        match stored.first() {
+            None => store.append(&genesis)?,
+            Some(first) if *first != genesis => return Err(ChainError::GenesisMismatch),
+            Some(_) => {}
+        }
+        DefaultConsensus
+            .validate_genesis(&genesis)
+            .map_err(|error| ChainError::InvalidBlock { height: 0, error })?;
+        state
+            .connect_block(&genesis)
+            .map_err(|reason| ChainError::State { hash: genesis.hash(), reason })?;
+
+        let work = block_work(&genesis);
+        let mut chain = Blockchain {
+            blocks: vec![genesis.clone()],
+            tree: HashMap::from([(genesis.hash(), TreeEntry { block: genesis, work })]),
+            invalid: HashSet::new(),
+            store,
+            state,
+            events: Vec::new(),
+        };
+        for block in stored.into_iter().skip(1) {
+            match chain.accept(block, false) {
+                // Stored before the state refused it; it stays out of the chain.
+                Ok(_) | Err(ChainError::State { .. }) => {}
+                Err(e) => return Err(e),
             }
         }
+        // Replaying restores what was already there; nothing changed.
+        chain.events.clear();
+        Ok(chain)
+    }
+
+    /// Validates `block` against its parent, stores it, and moves the best
+    /// chain to it if its branch now has the most cumulative work.
+    pub fn add_block(&mut self, block: Block) -> Result<BlockOutcome, ChainError> {
+        self.accept(block, true)
+    }
+
+    /// Every block connected to or disconnected from the best chain since
+    /// the last call, in the order it happened. This includes the blocks of a
+    /// reorganization that the state refused part way, which
+    /// [`BlockOutcome`] cannot report.
+    pub fn take_events(&mut self) -> Vec<ChainEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The best chain, genesis first.
+    pub fn best_chain(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// The tip of the best chain.
+    pub fn tip(&self) -> &Block {
+        self.blocks.last().expect("chain always holds genesis")
+    }
+
+    /// Cumulative work of the best chain.
+    pub fn best_work(&self) -> u128 {
+        self.work(&self.tip().hash())
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.tree.contains_key(hash)
+    }
+
+    /// Any known valid block, on the best chain or not.
+    pub fn block(&self, hash: &[u8; 32]) -> Option<&Block> {
+        self.tree.get(hash).map(|entry| &entry.block)
+    }
+
+    /// The difficulty a block on the tip must carry.
+    pub fn next_difficulty(&self) -> Difficulty {
+        mining::next_difficulty(&self.context(&self.tip().hash()))
+    }
+
+    /// The time a block on the tip must be stamped after.
+    pub fn median_time_past(&self) -> u64 {
+        mining::median_time_past(&self.context(&self.tip().hash()))
+    }
+
+    /// The last [`HEADER_CONTEXT`] headers of the branch ending at `hash`,
+    /// oldest first.
+    fn context(&self, hash: &[u8; 32]) -> Vec<BlockHeader> {
+        let mut headers = Vec::with_capacity(HEADER_CONTEXT);
+        let mut cursor = self.tree.get(hash);
+        while let Some(entry) = cursor.filter(|_| headers.len() < HEADER_CONTEXT) {
+            headers.push(entry.block.header);
+            cursor = self.tree.get(entry.block.previous_hash());
+        }
+        headers.reverse();
+        headers
+    }
+
+    fn accept(&mut self, block: Block, persist: bool) -> Result<BlockOutcome, ChainError> {
+        let hash = block.hash();
+        if self.tree.contains_key(&hash) {
+            return Ok(BlockOutcome::AlreadyKnown);
+        }
+        let previous = *block.previous_hash();
+        if self.invalid.contains(&hash) || self.invalid.contains(&previous) {
+            self.invalid.insert(hash);
+            return Err(ChainError::State { hash, reason: "descends from a rejected block".to_string() });
+        }
+        let parent_work = self.tree.get(&previous).ok_or(ChainError::UnknownParent(previous))?.work;
+        let invalid = |error| ChainError::InvalidBlock { height: block.height(), error };
+        DefaultConsensus.validate_block(&block, &self.context(&previous)).map_err(invalid)?;
+        // Stored blocks passed this when they arrived, and the clock may
+        // have been wrong since.
+        if persist && block.header.timestamp > now().saturating_add(MAX_FUTURE_DRIFT) {
+            return Err(invalid(BlockValidationError::InvalidTimestamp));
+        }
+        let work = parent_work.saturating_add(block_work(&block));
+        if persist {
+            self.store.append(&block)?;
+        }
+        let extends_tip = previous == self.tip().hash();
+        self.tree.insert(hash, TreeEntry { block: block.clone(), work });
+
+        if work <= self.best_work() {
+            return Ok(BlockOutcome::SideBranch);
+        }
+        if !extends_tip {
+            return self.reorganize(&hash);
+        }
+        if let Err(reason) = self.connect(&block) {
+            self.invalid.insert(hash);
+            return Err(ChainError::State { hash, reason });
+        }
+        self.blocks.push(block);
+        Ok(BlockOutcome::Extended)
+    }
+
+    /// Switches the best chain to end at `new_tip`.
+    ///
+    /// If the state refuses a block of the new branch, the chain settles on
+    /// whichever is heavier of the old chain and the part of the new branch
+    /// before the refused block.
+    fn reorganize(&mut self, new_tip: &[u8; 32]) -> Result<BlockOutcome, ChainError> {
+        let mut branch = Vec::new();
+        let mut cursor = *new_tip;
+        while !self.on_best_chain(&cursor) {
+            let block = &self.tree[&cursor].block;
+            cursor = *block.previous_hash();
+            branch.push(block.clone());
+        }
+        branch.reverse();
+        if let Some(bad) = branch.iter().find(|block| self.invalid.contains(&block.hash())) {
+            let reason = format!("descends from rejected block {}", hex::encode(bad.hash()));
+            self.invalid.insert(*new_tip);
+            return Err(ChainError::State { hash: *new_tip, reason });
+        }
+
+        let old_work = self.best_work();
+        let fork_height = branch[0].height() as usize;
+        let old = self.blocks.split_off(fork_height);
+        for block in old.iter().rev() {
+            self.disconnect(block);
+        }
+        for block in &branch {
+            if let Err(reason) = self.connect(block) {
+                self.invalid.insert(block.hash());
+                self.invalid.insert(*new_tip);
+                if self.best_work() <= old_work {
+                    for connected in self.blocks.split_off(fork_height).iter().rev() {
+                        self.disconnect(connected);
+                    }
+                    for block in &old {
+                        self.connect(block).expect("previously connected block reconnects");
+                    }
+                    self.blocks.extend(old);
+                }
+                return Err(ChainError::State { hash: block.hash(), reason });
+            }
+            self.blocks.push(block.clone());
+        }
+        Ok(BlockOutcome::Reorganized { disconnected: old.len(), connected: branch.len() })
+    }
+
+    /// Connects `block` to the state, recording it if the state accepts it.
+    fn connect(&mut self, block: &Block) -> Result<(), String> {
+        self.state.connect_block(block)?;
+        self.events.push(ChainEvent::Connected(block.clone()));
+        Ok(())
+    }
+
+    fn disconnect(&mut self, block: &Block) {
+        self.state.disconnect_block(block);
+        self.events.push(ChainEvent::Disconnected(block.clone()));
+    }
+
+    fn on_best_chain(&self, hash: &[u8; 32]) -> bool {
+        let height = self.tree[hash].block.height() as usize;
+        self.blocks.get(height).is_some_and(|block| block.hash() == *hash)
+    }
+
+    fn work(&self, hash: &[u8; 32]) -> u128 {
+        self.tree[hash].work
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn block_work(block: &Block) -> u128 {
+    DefaultConsensus.chain_complexity_score(std::slice::from_ref(block))
+}
+
+/// The genesis block every node starts from.
+pub fn genesis_block() -> Block {
+    let template = BlockHeader {
+        version: BLOCK_VERSION,
+        height: 0,
+        previous_hash: [0; 32],
+        merkle_root: transactions_root(&[]),
+        state_root: State::new().root(),
+        producer: Address::default(),
+        timestamp: GENESIS_TIMESTAMP,
+        difficulty: Difficulty::initial(),
+        strategy: MiningStrategy::Deterministic,
+        address: FractalAddress::root(),
+        triangle_commitment: [0; 32],
+        nonce: 0,
+    };
+    let header = DeterministicMiner.mine(&template).expect("genesis mining target is reachable");
+    Block { header, transactions: vec![], signature: vec![] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::crypto::{Address, Ed25519, KeyPair, SignatureScheme, ADDRESS_LEN};
+    use crate::transaction::{Transaction, TxMeta};
+
+    fn faucet() -> KeyPair {
+        Ed25519.keypair_from_seed(&[1; 32])
+    }
+
+    fn producer() -> KeyPair {
+        Ed25519.keypair_from_seed(&[2; 32])
+    }
+
+    /// The account standing for `name` in these tests.
+    fn account(name: &str) -> String {
+        let mut bytes = [0; ADDRESS_LEN];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Address::from_bytes(bytes).to_string()
+    }
+
+    fn pay(to: &str) -> Transaction {
+        let meta = TxMeta { sender: faucet().address().to_string(), nonce: 0, fee: 0, signature: vec![] };
+        let mut tx = Transaction::Transfer { meta, to: account(to), amount: 1 };
+        tx.sign(&faucet()).unwrap();
+        tx
+    }
+
+    /// A block paying one coin to each name in `payees`.
+    ///
+    /// Blocks are spaced at the target interval, so the difficulty never
+    /// moves off the genesis one.
+    fn next_block(previous: &Block, payees: &[&str]) -> Block {
+        let transactions: Vec<Transaction> = payees.iter().map(|to| pay(to)).collect();
+        let template = BlockHeader {
+            height: previous.height() + 1,
+            previous_hash: previous.hash(),
+            merkle_root: transactions_root(&transactions),
+            producer: producer().address(),
+            timestamp: previous.header.timestamp + mining::TARGET_BLOCK_INTERVAL,
+            ..previous.header
+        };
+        let mut block = Block { header: DeterministicMiner.mine(&template).unwrap(), transactions, signature: vec![] };
+        block.sign(&producer()).unwrap();
+        block
+    }
+
+    /// `block` with its nonce bumped, which breaks its proof of work.
+    fn forge(block: &Block) -> Block {
+        let mut forged = block.clone();
+        forged.header.nonce += 1;
+        forged
+    }
+
+    /// Credits transfers to their recipients and refuses any block paying
+    /// "mallory".
+    #[derive(Default)]
+    struct Ledger {
+        balances: std::rc::Rc<std::cell::RefCell<HashMap<String, u64>>>,
+    }
+
+    impl ChainState for Ledger {
+        fn connect_block(&mut self, block: &Block) -> Result<(), String> {
+            let transfers = || block.transactions.iter().filter_map(|tx| match tx {
+                Transaction::Transfer { to, amount, .. } => Some((to, *amount)),
+                _ => None,
+            });
+            if transfers().any(|(to, _)| *to == account("mallory")) {
+                return Err("mallory is banned".to_string());
+            }
+            let mut balances = self.balances.borrow_mut();
+            for (to, amount) in transfers() {
+                *balances.entry(to.clone()).or_default() += amount;
+            }
+            Ok(())
+        }
+
+        fn disconnect_block(&mut self, block: &Block) {
+            let mut balances = self.balances.borrow_mut();
+            for tx in &block.transactions {
+                if let Transaction::Transfer { to, amount, .. } = tx {
+                    *balances.get_mut(to).unwrap() -= amount;
+                }
+            }
+        }
+    }
+
+    fn ledger_chain() -> (Blockchain, std::rc::Rc<std::cell::RefCell<HashMap<String, u64>>>) {
+        let ledger = Ledger::default();
+        let balances = ledger.balances.clone();
+        let chain = Blockchain::with_store(Box::new(MemoryBlockStore::new()), Box::new(ledger)).unwrap();
+        (chain, balances)
+    }
+
+    #[test]
+    fn test_extends_and_rejects_invalid_blocks() {
+        let mut chain = Blockchain::new();
+        let block = next_block(chain.tip(), &["a"]);
+        assert_eq!(chain.add_block(block.clone()).unwrap(), BlockOutcome::Extended);
+        assert_eq!(chain.add_block(block.clone()).unwrap(), BlockOutcome::AlreadyKnown);
+
+        let forged = forge(&next_block(&block, &[]));
+        assert!(matches!(
+            chain.add_block(forged),
+            Err(ChainError::InvalidBlock { height: 2, error: BlockValidationError::InvalidProofOfWork })
+        ));
+        let orphan = next_block(&next_block(&block, &["x"]), &[]);
+        assert!(matches!(chain.add_block(orphan), Err(ChainError::UnknownParent(_))));
+        assert_eq!(chain.best_chain().len(), 2);
+    }
+
+    #[test]
+    fn test_enforces_timestamp_rules() {
+        let mut chain = Blockchain::new();
+        let mut early = next_block(chain.tip(), &[]);
+        early.header.timestamp = GENESIS_TIMESTAMP;
+        let mut early = Block { header: DeterministicMiner.mine(&early.header).unwrap(), ..early };
+        early.sign(&producer()).unwrap();
+        assert!(matches!(
+            chain.add_block(early),
+            Err(ChainError::InvalidBlock { height: 1, error: BlockValidationError::InvalidTimestamp })
+        ));
+
+        let mut future = next_block(chain.tip(), &[]);
+        future.header.timestamp = now() + MAX_FUTURE_DRIFT + 60;
+        let mut future = Block { header: DeterministicMiner.mine(&future.header).unwrap(), ..future };
+        future.sign(&producer()).unwrap();
+        assert!(matches!(
+            chain.add_block(future),
+            Err(ChainError::InvalidBlock { height: 1, error: BlockValidationError::InvalidTimestamp })
+        ));
+        assert_eq!(chain.median_time_past(), GENESIS_TIMESTAMP);
+        assert_eq!(chain.next_difficulty(), Difficulty::initial());
+    }
+
+    #[test]
+    fn test_heavier_fork_reorganizes_state() {
+        let (mut chain, balances) = ledger_chain();
+        let genesis = chain.tip().clone();
+        let a1 = next_block(&genesis, &["alice"]);
+        let b1 = next_block(&genesis, &["bob"]);
+        let b2 = next_block(&b1, &["bob"]);
+        assert_eq!(chain.add_block(a1.clone()).unwrap(), BlockOutcome::Extended);
+        assert_eq!(chain.add_block(b1.clone()).unwrap(), BlockOutcome::SideBranch);
+        assert_eq!(balances.borrow().get(&account("bob")), None);
+
+        chain.take_events();
+        assert_eq!(chain.add_block(b2.clone()).unwrap(), BlockOutcome::Reorganized { disconnected: 1, connected: 2 });
+        assert_eq!(chain.tip(), &b2);
+        assert_eq!(
+            chain.take_events(),
+            vec![ChainEvent::Disconnected(a1.clone()), ChainEvent::Connected(b1), ChainEvent::Connected(b2.clone())]
+        );
+        assert_eq!(balances.borrow().get(&account("alice")), Some(&0));
+        assert_eq!(balances.borrow().get(&account("bob")), Some(&2));
+        assert!(chain.contains(&a1.hash()));
+    }
+
+    #[test]
+    fn test_fork_choice_weighs_difficulty_not_length() {
+        let (mut chain, _) = ledger_chain();
+        let genesis = chain.tip().clone();
+        let a1 = next_block(&genesis, &[]);
+        let a2 = next_block(&a1, &[]);
+        chain.add_block(a1).unwrap();
+        chain.add_block(a2.clone()).unwrap();
+
+        // Slow blocks retarget to easier thresholds that keep the same depth.
+        let mut slow = vec![genesis.clone()];
+        for _ in 0..3 {
+            let previous = slow.last().unwrap();
+            let headers: Vec<BlockHeader> = slow.iter().map(|block| block.header).collect();
+            let template = BlockHeader {
+                height: previous.height() + 1,
+                previous_hash: previous.hash(),
+                timestamp: previous.header.timestamp + 100 * mining::TARGET_BLOCK_INTERVAL,
+                merkle_root: transactions_root(&[]),
+                producer: producer().address(),
+                difficulty: mining::next_difficulty(&headers),
+                ..previous.header
+            };
+            let mut block = Block { header: DeterministicMiner.mine(&template).unwrap(), transactions: vec![], signature: vec![] };
+            block.sign(&producer()).unwrap();
+            slow.push(block);
+        }
+        assert!(slow.iter().all(|block| block.header.difficulty.depth == genesis.header.difficulty.depth));
+        for block in &slow[1..] {
+            assert_eq!(chain.add_block(block.clone()).unwrap(), BlockOutcome::SideBranch);
+        }
+        assert_eq!(chain.tip(), &a2);
+        assert_eq!(chain.best_work(), 3 * Difficulty::initial().work());
+    }
+
+    #[test]
+    fn test_refused_fork_restores_old_chain() {
+        let (mut chain, balances) = ledger_chain();
+        let genesis = chain.tip().clone();
+        let a1 = next_block(&genesis, &["alice"]);
+        let a2 = next_block(&a1, &["alice"]);
+        let b1 = next_block(&genesis, &["bob"]);
+        let b2 = next_block(&b1, &["mallory"]);
+        let b3 = next_block(&b2, &["bob"]);
+        for block in [a1, a2.clone(), b1, b2] {
+            chain.add_block(block).unwrap();
+        }
+        chain.take_events();
+        assert!(matches!(chain.add_block(b3.clone()), Err(ChainError::State { .. })));
+        assert_eq!(chain.tip(), &a2);
+        // Followers see the switch to b1 and back.
+        let events = chain.take_events();
+        assert_eq!(events.len(), 6);
+        assert_eq!(events.last(), Some(&ChainEvent::Connected(a2.clone())));
+        assert_eq!(balances.borrow().get(&account("alice")), Some(&2));
+        assert_eq!(balances.borrow().get(&account("bob")), Some(&0));
+        // Anything built on the refused branch is refused too.
+        assert!(matches!(chain.add_block(next_block(&b3, &[])), Err(ChainError::State { .. })));
+    }
+
+    #[test]
+    fn test_two_nodes_converge() {
+        let (mut left, mut right) = (Blockchain::new(), Blockchain::new());
+        let l1 = next_block(left.tip(), &["left"]);
+        let r1 = next_block(right.tip(), &["right"]);
+        left.add_block(l1.clone()).unwrap();
+        right.add_block(r1.clone()).unwrap();
+        let r2 = next_block(&r1, &[]);
+        right.add_block(r2.clone()).unwrap();
+
+        // Blocks arrive at each node in any order; both settle on the heavier branch.
+        for block in [r1, r2] {
+            left.add_block(block).unwrap();
+        }
+        right.add_block(l1).unwrap();
+        assert_eq!(left.tip(), right.tip());
+        assert_eq!(left.best_chain(), right.best_chain());
+    }
+
+    #[test]
+    fn test_chain_and_forks_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut chain = Blockchain::open(dir.path(), Box::new(())).unwrap();
+        let genesis = chain.tip().clone();
+        let a1 = next_block(&genesis, &["a"]);
+        let b1 = next_block(&genesis, &["b"]);
+        let b2 = next_block(&b1, &[]);
+        for block in [a1.clone(), b1, b2] {
+            chain.add_block(block).unwrap();
+        }
+        let blocks = chain.best_chain().to_vec();
+        drop(chain);
+
+        let reopened = Blockchain::open(dir.path(), Box::new(())).unwrap();
+        assert_eq!(reopened.best_chain(), blocks);
+        assert!(reopened.contains(&a1.hash()));
+    }
+
+    #[test]
+    fn test_open_rejects_invalid_stored_block() {
+        let mut store = MemoryBlockStore::new();
+        let genesis = genesis_block();
+        let forged = forge(&next_block(&genesis, &[]));
+        store.append(&genesis).unwrap();
+        store.append(&forged).unwrap();
+        assert!(matches!(
+            Blockchain::with_store(Box::new(store), Box::new(())),
+            Err(ChainError::InvalidBlock { height: 1, error: BlockValidationError::InvalidProofOfWork })
+        ));
     }
 
-    pub fn add_block(&mut self, new_block: Block) {
-        self.blocks.push(new_block);
+    #[test]
+    fn test_open_rejects_foreign_genesis() {
+        let mut store = MemoryBlockStore::new();
+        let mut genesis = genesis_block();
+        genesis.header.timestamp += 1;
+        store.append(&genesis).unwrap();
+        assert!(matches!(Blockchain::with_store(Box::new(store), Box::new(())), Err(ChainError::GenesisMismatch)));
     }
 }
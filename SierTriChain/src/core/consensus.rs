@@ -2,20 +2,42 @@
 //! Consensus logic for SierTriChain blockchain.
 //! Provides block validation and chain scoring mechanisms.
 
-use crate::block::Block;
+use crate::block::{Block, BlockHeader, BLOCK_VERSION};
+use crate::core::mining::{median_time_past, next_difficulty, verify_proof, Difficulty};
 
 /// Errors that can occur during block validation.
 #[derive(Debug, PartialEq)]
 pub enum BlockValidationError {
+    InvalidVersion,
     InvalidIndex,
     InvalidPreviousHash,
-    InvalidHash,
+    /// The header does not carry the difficulty the retarget gives it.
+    InvalidDifficulty,
+    /// The timestamp is not after the median time past.
+    InvalidTimestamp,
+    InvalidProofOfWork,
+    /// The header's merkle root does not commit to the block's transactions.
+    InvalidMerkleRoot,
+    /// A transaction is not signed by its sender.
+    InvalidTransactionSignature,
+    /// A transfer's recipient is not a canonical address.
+    InvalidRecipient,
+    /// The block is not signed by the producer its header names.
+    InvalidProducerSignature,
 }
 
 /// Trait for consensus algorithms.
 pub trait Consensus {
-    fn validate_block(&self, block: &Block, previous_block: &Block) -> Result<(), BlockValidationError>;
-    fn chain_complexity_score(&self, chain: &[Block]) -> f64;
+    fn validate_genesis(&self, block: &Block) -> Result<(), BlockValidationError>;
+    /// Validates `block` in the context of `ancestors`, the chain ending at
+    /// its parent, oldest first. Passing the last [`HEADER_CONTEXT`] headers
+    /// is enough.
+    ///
+    /// [`HEADER_CONTEXT`]: crate::core::mining::HEADER_CONTEXT
+    fn validate_block(&self, block: &Block, ancestors: &[BlockHeader]) -> Result<(), BlockValidationError>;
+    /// Total expected proof attempts behind `chain`, which fork choice
+    /// maximises.
+    fn chain_complexity_score(&self, chain: &[Block]) -> u128;
 }
 
 
@@ -23,27 +45,248 @@ pub trait Consensus {
 pub struct DefaultConsensus;
 
 impl Consensus for DefaultConsensus {
-    /// Calculates the complexity score of a chain.
-    fn chain_complexity_score(&self, chain: &[Block]) -> f64 {
-        chain.iter().map(|block| block.mining_result.address.0.len() as f64).sum()
+    /// Sums each block's [`Difficulty::work`].
+    fn chain_complexity_score(&self, chain: &[Block]) -> u128 {
+        chain.iter().fold(0, |work, block| work.saturating_add(block.header.difficulty.work()))
     }
 
-    /// Validates a block against its previous block.
-    fn validate_block(&self, block: &Block, previous_block: &Block) -> Result<(), BlockValidationError> {
-        if block.index != previous_block.index + 1 {
+    /// Validates the first block, which has no parent to check against.
+    fn validate_genesis(&self, block: &Block) -> Result<(), BlockValidationError> {
+        if block.height() != 0 {
             return Err(BlockValidationError::InvalidIndex);
         }
-        if block.previous_hash != previous_block.hash {
+        if *block.previous_hash() != [0; 32] {
             return Err(BlockValidationError::InvalidPreviousHash);
         }
-        if block.hash != block.calculate_hash() {
-            return Err(BlockValidationError::InvalidHash);
+        self.validate_header(block, Difficulty::initial())
+    }
+
+    /// Validates a block against its parent and the retarget over its ancestors.
+    fn validate_block(&self, block: &Block, ancestors: &[BlockHeader]) -> Result<(), BlockValidationError> {
+        let Some(parent) = ancestors.last() else {
+            return Err(BlockValidationError::InvalidPreviousHash);
+        };
+        if block.height() != parent.height + 1 {
+            return Err(BlockValidationError::InvalidIndex);
+        }
+        if *block.previous_hash() != parent.hash() {
+            return Err(BlockValidationError::InvalidPreviousHash);
+        }
+        if block.header.timestamp <= median_time_past(ancestors) {
+            return Err(BlockValidationError::InvalidTimestamp);
+        }
+        self.validate_header(block, next_difficulty(ancestors))?;
+        if block.verify_signature().is_err() {
+            return Err(BlockValidationError::InvalidProducerSignature);
+        }
+        Ok(())
+    }
+}
+
+impl DefaultConsensus {
+    /// Checks the header against the expected difficulty, the block's own
+    /// contents and its proof of work, then the transaction signatures and
+    /// recipients.
+    fn validate_header(&self, block: &Block, difficulty: Difficulty) -> Result<(), BlockValidationError> {
+        if block.header.version != BLOCK_VERSION {
+            return Err(BlockValidationError::InvalidVersion);
+        }
+        if block.header.difficulty != difficulty {
+            return Err(BlockValidationError::InvalidDifficulty);
+        }
+        if block.header.merkle_root != block.merkle_root() {
+            return Err(BlockValidationError::InvalidMerkleRoot);
+        }
+        if !verify_proof(&block.header) {
+            return Err(BlockValidationError::InvalidProofOfWork);
+        }
+        if block.transactions.iter().any(|tx| tx.verify_signature().is_err()) {
+            return Err(BlockValidationError::InvalidTransactionSignature);
+        }
+        if block.transactions.iter().any(|tx| tx.verify_recipient().is_err()) {
+            return Err(BlockValidationError::InvalidRecipient);
         }
         Ok(())
     }
 }
 
 /// Legacy block validation function (use Consensus trait for extensibility).
-pub fn validate_block(block: &Block, previous_block: &Block) -> bool {
-    DefaultConsensus.validate_block(block, previous_block).is_ok()
-}
\ No newline at end of file
+pub fn validate_block(block: &Block, ancestors: &[BlockHeader]) -> bool {
+    DefaultConsensus.validate_block(block, ancestors).is_ok()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::transactions_root;
+    use crate::core::mining::chaos::ChaoticMiner;
+    use crate::core::mining::deterministic::DeterministicMiner;
+    use crate::core::mining::{Miner, MiningStrategy, TARGET_BLOCK_INTERVAL};
+    use crate::crypto::crypto::{Ed25519, KeyPair, SignatureScheme};
+    use crate::transaction::{Transaction, TxMeta};
+
+    fn genesis() -> Block {
+        let template = BlockHeader { merkle_root: transactions_root(&[]), producer: producer().address(), ..BlockHeader::test_template() };
+        Block { header: DeterministicMiner.mine(&template).unwrap(), transactions: vec![], signature: vec![] }
+    }
+
+    fn producer() -> KeyPair {
+        Ed25519.keypair_from_seed(&[1; 32])
+    }
+
+    fn alice() -> KeyPair {
+        Ed25519.keypair_from_seed(&[2; 32])
+    }
+
+    fn transfer(amount: u64) -> Transaction {
+        let meta = TxMeta { sender: alice().address().to_string(), nonce: 0, fee: 1, signature: vec![] };
+        let mut tx = Transaction::Transfer { meta, to: producer().address().to_string(), amount };
+        tx.sign(&alice()).unwrap();
+        tx
+    }
+
+    fn signed(header: BlockHeader, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block { header, transactions, signature: vec![] };
+        block.sign(&producer()).unwrap();
+        block
+    }
+
+    /// A block on top of `ancestors`, `spacing` seconds after its parent.
+    fn block_after(ancestors: &[BlockHeader], spacing: u64, transactions: Vec<Transaction>) -> Block {
+        let previous = ancestors.last().unwrap();
+        let template = BlockHeader {
+            height: previous.height + 1,
+            previous_hash: previous.hash(),
+            merkle_root: transactions_root(&transactions),
+            timestamp: previous.timestamp + spacing,
+            difficulty: next_difficulty(ancestors),
+            ..*previous
+        };
+        signed(DeterministicMiner.mine(&template).unwrap(), transactions)
+    }
+
+    fn next_block(previous: &Block, transactions: Vec<Transaction>) -> Block {
+        block_after(&[previous.header], TARGET_BLOCK_INTERVAL, transactions)
+    }
+
+    #[test]
+    fn test_valid_block_passes() {
+        let genesis = genesis();
+        let block = next_block(&genesis, vec![transfer(1)]);
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Ok(()));
+    }
+
+    #[test]
+    fn test_tampered_transactions_invalidate_block() {
+        let genesis = genesis();
+        let mut block = next_block(&genesis, vec![transfer(1)]);
+        block.transactions.push(transfer(2));
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidMerkleRoot));
+        // Recommitting to the new transactions moves the proof-of-work hash.
+        block.header.merkle_root = block.merkle_root();
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidProofOfWork));
+    }
+
+    #[test]
+    fn test_signatures_are_required() {
+        let genesis = genesis();
+        let mut forged = transfer(1);
+        forged.meta_mut().signature = transfer(2).meta().signature.clone();
+        let block = next_block(&genesis, vec![forged]);
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidTransactionSignature));
+
+        let mut unsigned = next_block(&genesis, vec![transfer(1)]);
+        unsigned.signature.clear();
+        assert_eq!(DefaultConsensus.validate_block(&unsigned, &[genesis.header]), Err(BlockValidationError::InvalidProducerSignature));
+
+        // Re-signing with another key does not change who the header credits.
+        let mut claimed = next_block(&genesis, vec![]);
+        claimed.signature = crate::crypto::crypto::Witness::sign(&alice(), &claimed.hash()).unwrap().encode();
+        assert_eq!(DefaultConsensus.validate_block(&claimed, &[genesis.header]), Err(BlockValidationError::InvalidProducerSignature));
+    }
+
+    #[test]
+    fn test_recipients_must_be_addresses() {
+        let genesis = genesis();
+        let mut lost = transfer(1);
+        if let Transaction::Transfer { to, .. } = &mut lost {
+            *to = "bob".to_string();
+        }
+        lost.sign(&alice()).unwrap();
+        let block = next_block(&genesis, vec![lost]);
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidRecipient));
+    }
+
+    #[test]
+    fn test_tampered_nonce_invalidates_proof() {
+        let genesis = genesis();
+        let mut block = next_block(&genesis, vec![]);
+        block.header.nonce += 1;
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidProofOfWork));
+    }
+
+    #[test]
+    fn test_shallow_proof_is_rejected() {
+        let genesis = genesis();
+        let mut block = next_block(&genesis, vec![]);
+        block.header.address = block.header.address.parent().unwrap();
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidProofOfWork));
+        block.header.difficulty.depth -= 1;
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidDifficulty));
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let genesis = genesis();
+        let mut block = next_block(&genesis, vec![]);
+        block.header.version += 1;
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidVersion));
+    }
+
+    #[test]
+    fn test_either_strategy_is_accepted_and_identified() {
+        let genesis = genesis();
+        let template = next_block(&genesis, vec![]).header;
+        let block = signed(ChaoticMiner.mine(&template).unwrap(), vec![]);
+        assert_eq!(block.header.strategy, MiningStrategy::Chaotic);
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Ok(()));
+
+        // The tag is part of what the proof commits to.
+        let mut relabelled = block;
+        relabelled.header.strategy = MiningStrategy::Deterministic;
+        assert_eq!(DefaultConsensus.validate_block(&relabelled, &[genesis.header]), Err(BlockValidationError::InvalidProofOfWork));
+    }
+
+    #[test]
+    fn test_retarget_is_enforced() {
+        let genesis = genesis();
+        let first = block_after(&[genesis.header], 1, vec![]);
+        let ancestors = [genesis.header, first.header];
+        // Blocks came fast, so the next one must be harder than its parent.
+        let expected = next_difficulty(&ancestors);
+        assert!(expected.threshold < first.header.difficulty.threshold);
+        assert_eq!(DefaultConsensus.validate_block(&block_after(&ancestors, 1, vec![]), &ancestors), Ok(()));
+
+        let stale = block_after(&[first.header], 1, vec![]);
+        assert_eq!(stale.header.difficulty, first.header.difficulty);
+        assert_eq!(DefaultConsensus.validate_block(&stale, &ancestors), Err(BlockValidationError::InvalidDifficulty));
+    }
+
+    #[test]
+    fn test_timestamp_must_pass_median_time_past() {
+        let genesis = genesis();
+        let mut block = next_block(&genesis, vec![]);
+        block.header.timestamp = genesis.header.timestamp;
+        assert_eq!(DefaultConsensus.validate_block(&block, &[genesis.header]), Err(BlockValidationError::InvalidTimestamp));
+    }
+
+    #[test]
+    fn test_genesis_validation() {
+        let genesis = genesis();
+        assert_eq!(DefaultConsensus.validate_genesis(&genesis), Ok(()));
+        let block = next_block(&genesis, vec![]);
+        assert_eq!(DefaultConsensus.validate_genesis(&block), Err(BlockValidationError::InvalidIndex));
+        let mut tampered = genesis.clone();
+        tampered.header.timestamp += 1;
+        assert_eq!(DefaultConsensus.validate_genesis(&tampered), Err(BlockValidationError::InvalidProofOfWork));
+    }
+}
@@ -3,14 +3,14 @@
 
 use crate::geometry::subdivision::FractalAddress;
 
-/// Calculate depth in fractal hierarchy from base-3 address
+/// Calculate depth in fractal hierarchy from base-4 address
 pub fn fractal_depth(address: &FractalAddress) -> usize {
-	address.0.len()
+	address.depth()
 }
 
-/// Get position in hierarchy (as path string)
+/// Get position in hierarchy (as canonical path string)
 pub fn fractal_position(address: &FractalAddress) -> String {
-	address.0.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("-")
+	address.to_string()
 }
 
 #[cfg(test)]
@@ -18,12 +18,12 @@ mod tests {
 	use super::*;
 	#[test]
 	fn test_fractal_depth() {
-		let addr = FractalAddress(vec![0, 1, 2, 0]);
+		let addr = FractalAddress::from_digits(&[0, 1, 2, 0]).unwrap();
 		assert_eq!(fractal_depth(&addr), 4);
 	}
 	#[test]
 	fn test_fractal_position() {
-		let addr = FractalAddress(vec![0, 1, 2]);
-		assert_eq!(fractal_position(&addr), "0-1-2");
+		let addr = FractalAddress::from_digits(&[0, 1, 2]).unwrap();
+		assert_eq!(fractal_position(&addr), "0.1.2");
 	}
 }
@@ -1,31 +1,460 @@
-use crate::geometry::subdivision::{fractal_subdivide, FractalAddress};
-use crate::geometry::triangle::Triangle;
+//! Mining logic for SierTriChain.
+//!
+//! A block's proof of work is a nonce. The header's proof-of-work hash with
+//! that nonce selects a triangle of the genesis subdivision, and the nonce is
+//! valid when that triangle falls inside the target region. The region is
+//! everything swept by the subdivision in address order up to and including
+//! the triangle, and its area may be at most the area threshold. Areas are
+//! fractions of the genesis triangle, which keeps the comparison exact.
+
+pub mod chaos;
+pub mod deterministic;
+pub mod parallel;
+
+use crate::block::{triangle_commitment, BlockHeader};
+use crate::geometry::subdivision::{triangle_at, FractalAddress};
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use std::fmt;
+use std::str::FromStr;
+
+/// Fractal depth of the first mined block, and the shallowest any block is
+/// mined at.
+pub const INITIAL_FRACTAL_DEPTH: usize = 8;
+/// Largest swept area the genesis proof may cover, as a fraction of the
+/// genesis area.
+pub const AREA_THRESHOLD: Decimal = dec!(0.0025);
+/// Deepest address a single header hash can encode (two bits per digit).
+pub const MAX_MINING_DEPTH: usize = crate::geometry::hierarchy::MAX_DEPTH;
+
+/// Seconds the retarget aims to put between blocks.
+pub const TARGET_BLOCK_INTERVAL: u64 = 60;
+/// Block intervals the retarget averages over.
+pub const RETARGET_WINDOW: usize = 10;
+/// Only this fraction of the gap between the observed and target timespans
+/// is corrected per block, so a burst of odd timestamps moves the difficulty
+/// gradually.
+pub const RETARGET_DAMPING: Decimal = dec!(0.25);
+/// Most the damped timespan may differ from the target, as a factor either way.
+pub const MAX_RETARGET_FACTOR: Decimal = dec!(2);
+/// Easiest threshold the retarget will set.
+pub const MAX_AREA_THRESHOLD: Decimal = dec!(0.25);
+/// Hardest threshold the retarget will set.
+pub const MIN_AREA_THRESHOLD: Decimal = dec!(0.000000000001);
+/// Decimal places a retargeted threshold is rounded to.
+pub const THRESHOLD_DECIMALS: u32 = 20;
+/// The depth is chosen so that at least this many triangles fit under the
+/// threshold, which keeps the rounding of the target to whole triangles
+/// under about two percent.
+pub const MIN_TARGET_TRIANGLES: Decimal = dec!(64);
+/// Number of previous timestamps whose median a new timestamp must exceed.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// Headers before a block that its difficulty and timestamp rules read.
+pub const HEADER_CONTEXT: usize =
+    if RETARGET_WINDOW + 1 > MEDIAN_TIME_SPAN { RETARGET_WINDOW + 1 } else { MEDIAN_TIME_SPAN };
+
+/// The target a block is mined against, as carried in its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Difficulty {
+    /// Depth of the address the proof must select.
+    pub depth: u8,
+    /// Largest swept area the proof may cover.
+    pub threshold: Decimal,
+}
+
+impl Difficulty {
+    /// The difficulty of the genesis block.
+    pub fn initial() -> Self {
+        Difficulty::for_threshold(AREA_THRESHOLD)
+    }
+
+    /// `threshold` at the shallowest depth that resolves it finely enough.
+    pub fn for_threshold(threshold: Decimal) -> Self {
+        let threshold = threshold.clamp(MIN_AREA_THRESHOLD, MAX_AREA_THRESHOLD);
+        let mut depth = INITIAL_FRACTAL_DEPTH;
+        let mut triangles = threshold * Decimal::from(4u64.pow(depth as u32));
+        while triangles < MIN_TARGET_TRIANGLES && depth < MAX_MINING_DEPTH {
+            triangles *= Decimal::from(4);
+            depth += 1;
+        }
+        Difficulty { depth: depth as u8, threshold }
+    }
+
+    /// Expected number of proof attempts to meet this difficulty,
+    /// `floor(1 / threshold)`, which is what fork choice adds up.
+    ///
+    /// A proof hits the target with probability about `threshold` whatever
+    /// the depth, so two thresholds a retarget apart weigh differently even
+    /// when [`for_threshold`](Self::for_threshold) gives them the same depth.
+    pub fn work(&self) -> u128 {
+        let probability = self.threshold.clamp(MIN_AREA_THRESHOLD, Decimal::ONE);
+        (Decimal::ONE / probability).floor().to_u128().unwrap_or(u128::MAX)
+    }
+
+    /// Whether any address at this depth meets the threshold.
+    pub fn is_reachable(&self) -> bool {
+        let depth = self.depth as usize;
+        depth <= MAX_MINING_DEPTH && meets_target(&address_from_hash(&[0; 32], depth), self.threshold)
+    }
+}
+
+/// The difficulty the block after the last of `ancestors` must carry.
+///
+/// `ancestors` is the chain ending at the new block's parent, oldest first;
+/// only the last [`HEADER_CONTEXT`] are read. The parent's threshold is
+/// scaled by how long the last [`RETARGET_WINDOW`] blocks took against the
+/// target, after damping and clamping that timespan, and the depth follows
+/// the threshold.
+pub fn next_difficulty(ancestors: &[BlockHeader]) -> Difficulty {
+    let Some(parent) = ancestors.last() else {
+        return Difficulty::initial();
+    };
+    let window = &ancestors[ancestors.len().saturating_sub(RETARGET_WINDOW + 1)..];
+    let intervals = window.len() as u64 - 1;
+    if intervals == 0 {
+        return parent.difficulty;
+    }
+    let target = Decimal::from(intervals * TARGET_BLOCK_INTERVAL);
+    // Timestamps need not increase, so the observed span can be negative.
+    let actual = Decimal::from(parent.timestamp as i128 - window[0].timestamp as i128);
+    let damped = (target + (actual - target) * RETARGET_DAMPING)
+        .clamp(target / MAX_RETARGET_FACTOR, target * MAX_RETARGET_FACTOR);
+    let threshold = (parent.difficulty.threshold * damped / target).round_dp(THRESHOLD_DECIMALS).normalize();
+    Difficulty::for_threshold(threshold)
+}
+
+/// Median of the last [`MEDIAN_TIME_SPAN`] timestamps in `ancestors`, which
+/// a new block's timestamp must exceed. Zero for an empty chain.
+pub fn median_time_past(ancestors: &[BlockHeader]) -> u64 {
+    let mut times: Vec<u64> = ancestors.iter().rev().take(MEDIAN_TIME_SPAN).map(|header| header.timestamp).collect();
+    times.sort_unstable();
+    times.get(times.len() / 2).copied().unwrap_or(0)
+}
+
+/// Reads `depth` base-4 digits from `hash`, most significant bits first.
+pub fn address_from_hash(hash: &[u8; 32], depth: usize) -> FractalAddress {
+    FractalAddress::from_bytes(hash, depth).expect("mining depth within MAX_MINING_DEPTH")
+}
+
+/// The first address at `depth` whose swept area exceeds `threshold`, or
+/// `None` if the threshold covers the whole genesis triangle.
+///
+/// The triangles at one depth are ordered by address, each `4^-depth` of the
+/// genesis area, so this is the first `depth` base-4 digits of `threshold`.
+pub fn target_address(threshold: Decimal, depth: usize) -> Option<FractalAddress> {
+    if threshold >= Decimal::ONE {
+        return None;
+    }
+    let threshold = threshold.max(Decimal::ZERO);
+    let denominator = 10u128.pow(threshold.scale());
+    let mut remainder = threshold.mantissa() as u128;
+    let digits: Vec<u8> = (0..depth)
+        .map(|_| {
+            remainder *= 4;
+            let digit = (remainder / denominator) as u8;
+            remainder %= denominator;
+            digit
+        })
+        .collect();
+    FractalAddress::from_digits(&digits).ok()
+}
+
+/// True if the area swept up to and including `address` is at most `threshold`.
+pub fn meets_target(address: &FractalAddress, threshold: Decimal) -> bool {
+    match target_address(threshold, address.depth()) {
+        Some(target) => *address < target,
+        None => true,
+    }
+}
+
+/// Which search produced a block's proof, recorded in its header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize)]
+pub enum MiningStrategy {
+    /// Nonces in counting order.
+    #[default]
+    Deterministic,
+    /// Nonces in a scrambled order seeded by the header, with the proof
+    /// address rotated by a chaos offset the header also seeds.
+    Chaotic,
+}
+
+impl MiningStrategy {
+    /// The tag byte written into headers.
+    pub fn as_u8(self) -> u8 {
+        match self {
+            MiningStrategy::Deterministic => 0,
+            MiningStrategy::Chaotic => 1,
+        }
+    }
+
+    pub fn from_u8(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(MiningStrategy::Deterministic),
+            1 => Some(MiningStrategy::Chaotic),
+            _ => None,
+        }
+    }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct MiningResult {
-    pub address: FractalAddress,
-    pub triangle: Triangle,
+    /// A miner that searches with this strategy.
+    pub fn miner(self) -> Box<dyn Miner> {
+        match self {
+            MiningStrategy::Deterministic => Box::new(deterministic::DeterministicMiner),
+            MiningStrategy::Chaotic => Box::new(chaos::ChaoticMiner),
+        }
+    }
 }
 
-pub fn mine_genesis(depth: usize, threshold: Decimal) -> Option<MiningResult> {
-    let a = crate::geometry::point::Point::new(dec!(0.0), dec!(0.0));
-    let b = crate::geometry::point::Point::new(dec!(1.0), dec!(0.0));
-    let c = crate::geometry::point::Point::new(dec!(0.5), dec!(0.86602540378));
-    let t = Triangle::new(a, b, c);
+impl fmt::Display for MiningStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiningStrategy::Deterministic => write!(f, "deterministic"),
+            MiningStrategy::Chaotic => write!(f, "chaotic"),
+        }
+    }
+}
 
-    let mut triangles = Vec::new();
-    fractal_subdivide(&t, depth, FractalAddress(vec![]), &mut triangles);
+impl FromStr for MiningStrategy {
+    type Err = String;
 
-    for (triangle, address) in triangles {
-        if triangle.area() < threshold {
-            return Some(MiningResult { address, triangle });
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deterministic" => Ok(MiningStrategy::Deterministic),
+            "chaotic" => Ok(MiningStrategy::Chaotic),
+            _ => Err(format!("unknown mining strategy {:?}", s)),
         }
     }
-    None
 }
 
-pub fn required_fractal_depth(block_height: usize, initial_depth: usize, adjustment_interval: usize) -> usize {
-    initial_depth + (block_height / adjustment_interval)
-}
\ No newline at end of file
+/// A proof-of-work search strategy.
+///
+/// Every strategy searches the same proof space through [`search`]; a miner
+/// only decides the order in which nonces are tried.
+pub trait Miner: Send + Sync {
+    fn strategy(&self) -> MiningStrategy;
+
+    /// The nonce tried on attempt number `attempt`, for a template whose
+    /// search seed is `seed`.
+    fn nonce(&self, seed: u64, attempt: u64) -> u64;
+
+    /// Searches for a proof for `template` at its difficulty, and returns
+    /// the header with this miner's strategy, the nonce, the address and the
+    /// triangle commitment filled in.
+    ///
+    /// Returns `None` if the target is unreachable at that depth or the nonce
+    /// space is exhausted.
+    fn mine(&self, template: &BlockHeader) -> Option<BlockHeader> {
+        search(self, template, 0..=u64::MAX)
+    }
+}
+
+/// Tries `miner`'s nonces for each of `attempts` in turn and returns the
+/// first solved header.
+pub fn search<M: Miner + ?Sized>(
+    miner: &M,
+    template: &BlockHeader,
+    attempts: impl IntoIterator<Item = u64>,
+) -> Option<BlockHeader> {
+    let template = BlockHeader { strategy: miner.strategy(), ..*template };
+    if !template.difficulty.is_reachable() {
+        return None;
+    }
+    let seed = search_seed(&template);
+    attempts.into_iter().find_map(|attempt| solve(&template, miner.nonce(seed, attempt)))
+}
+
+/// Seed for a scrambled nonce order, taken from the template's
+/// proof-of-work hash at nonce zero.
+pub fn search_seed(template: &BlockHeader) -> u64 {
+    let hash = BlockHeader { nonce: 0, ..*template }.pow_hash();
+    u64::from_le_bytes(hash[..8].try_into().expect("8 bytes"))
+}
+
+/// The address a header's proof selects, which must fall under the target.
+///
+/// A deterministic proof takes the proof-of-work hash as the address. A
+/// chaotic proof rotates that address by the header's chaos offset.
+pub fn proof_address(header: &BlockHeader) -> FractalAddress {
+    match header.strategy {
+        MiningStrategy::Deterministic => address_from_hash(&header.pow_hash(), header.difficulty.depth as usize),
+        MiningStrategy::Chaotic => chaos::chaos_proof(header).address,
+    }
+}
+
+/// `template` with `nonce` and its proof filled in, if that nonce meets the
+/// target.
+pub fn solve(template: &BlockHeader, nonce: u64) -> Option<BlockHeader> {
+    let mut header = BlockHeader { nonce, ..*template };
+    let address = proof_address(&header);
+    meets_target(&address, template.difficulty.threshold).then(|| {
+        header.address = address;
+        header.triangle_commitment = triangle_commitment(&triangle_at(&address));
+        header
+    })
+}
+
+/// Checks the proof carried by `header` against the rest of the header.
+///
+/// The strategy tag picks how the proof address is derived, and is itself
+/// covered by the proof-of-work hash.
+///
+/// Costs at most three hashes and one walk down the address, independent of
+/// how many triangles exist at that depth.
+pub fn verify_proof(header: &BlockHeader) -> bool {
+    let Difficulty { depth, threshold } = header.difficulty;
+    header.address.depth() == depth as usize
+        && proof_address(header) == header.address
+        && meets_target(&header.address, threshold)
+        && triangle_commitment(&triangle_at(&header.address)) == header.triangle_commitment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use deterministic::DeterministicMiner;
+
+    #[test]
+    fn test_address_from_hash_reads_msb_first() {
+        let mut hash = [0u8; 32];
+        hash[0] = 0b1110_0100;
+        hash[1] = 0b1100_0000;
+        assert_eq!(address_from_hash(&hash, 5), FractalAddress::from_digits(&[3, 2, 1, 0, 3]).unwrap());
+    }
+
+    #[test]
+    fn test_target_address_is_base4_expansion() {
+        // 0.3 = 0.103030...(base 4)
+        let target = target_address(dec!(0.3), 4).unwrap();
+        assert_eq!(target.to_digits(), vec![1, 0, 3, 0]);
+        assert_eq!(target_address(Decimal::ONE, 4), None);
+    }
+
+    #[test]
+    fn test_meets_target_is_inclusive_and_exact() {
+        let quarter = FractalAddress::from_digits(&[0]).unwrap();
+        let half = FractalAddress::from_digits(&[1]).unwrap();
+        assert!(meets_target(&quarter, dec!(0.25)));
+        assert!(!meets_target(&half, dec!(0.25)));
+        assert!(!meets_target(&quarter, dec!(0.2499999999999999999999999999)));
+        assert!(meets_target(&FractalAddress::from_digits(&[3]).unwrap(), Decimal::ONE));
+    }
+
+    fn template() -> BlockHeader {
+        BlockHeader {
+            previous_hash: [1; 32],
+            merkle_root: [2; 32],
+            difficulty: Difficulty { depth: 8, threshold: AREA_THRESHOLD },
+            ..BlockHeader::test_template()
+        }
+    }
+
+    #[test]
+    fn test_mined_proof_verifies() {
+        let header = DeterministicMiner.mine(&template()).unwrap();
+        assert_eq!(header.address.depth(), 8);
+        assert!(verify_proof(&header));
+    }
+
+    #[test]
+    fn test_proof_is_bound_to_header() {
+        let header = DeterministicMiner.mine(&template()).unwrap();
+        assert!(!verify_proof(&BlockHeader { previous_hash: [3; 32], ..header }));
+        assert!(!verify_proof(&BlockHeader { merkle_root: [3; 32], ..header }));
+        assert!(!verify_proof(&BlockHeader { timestamp: 1, ..header }));
+        assert!(!verify_proof(&BlockHeader { strategy: MiningStrategy::Chaotic, ..header }));
+    }
+
+    #[test]
+    fn test_proof_rejects_wrong_commitment_or_depth() {
+        let header = DeterministicMiner.mine(&template()).unwrap();
+        let genesis = crate::geometry::triangle::genesis_triangle();
+        assert!(!verify_proof(&BlockHeader { triangle_commitment: triangle_commitment(&genesis), ..header }));
+        assert!(!verify_proof(&BlockHeader { address: header.address.parent().unwrap(), ..header }));
+    }
+
+    /// `count` headers `spacing` seconds apart, each carrying the difficulty
+    /// the retarget gives it.
+    fn retargeted_chain(count: usize, spacing: u64) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        for height in 0..count as u64 {
+            let difficulty = next_difficulty(&headers);
+            headers.push(BlockHeader { height, timestamp: height * spacing, difficulty, ..template() });
+        }
+        headers
+    }
+
+    #[test]
+    fn test_on_target_blocks_keep_difficulty() {
+        let headers = retargeted_chain(30, TARGET_BLOCK_INTERVAL);
+        assert!(headers.iter().all(|header| header.difficulty == Difficulty::initial()));
+        assert_eq!(Difficulty::initial(), Difficulty { depth: INITIAL_FRACTAL_DEPTH as u8, threshold: AREA_THRESHOLD });
+    }
+
+    #[test]
+    fn test_fast_blocks_raise_difficulty_gradually() {
+        let headers = retargeted_chain(40, 1);
+        // Block 1 has only genesis to look back on, so the retarget starts at block 2.
+        assert_eq!(headers[1].difficulty, headers[0].difficulty);
+        let thresholds: Vec<Decimal> = headers[1..].iter().map(|header| header.difficulty.threshold).collect();
+        assert!(thresholds.windows(2).all(|pair| pair[1] < pair[0]));
+        // Each step is damped, and never more than the clamp allows.
+        assert!(thresholds.windows(2).all(|pair| pair[1] >= pair[0] / MAX_RETARGET_FACTOR));
+        assert!(thresholds[1] > thresholds[0] * dec!(0.75));
+        // A smaller threshold needs a deeper address to resolve it.
+        assert!(headers[39].difficulty.depth > headers[0].difficulty.depth);
+    }
+
+    #[test]
+    fn test_work_follows_threshold_at_equal_depth() {
+        let easy = Difficulty::for_threshold(AREA_THRESHOLD * dec!(4));
+        assert_eq!(easy.depth, Difficulty::initial().depth);
+        assert_eq!(Difficulty::initial().work(), 400);
+        assert_eq!(easy.work(), 100);
+        assert_eq!(Difficulty::for_threshold(MAX_AREA_THRESHOLD).work(), 4);
+        assert_eq!(Difficulty::for_threshold(MIN_AREA_THRESHOLD).work(), 1_000_000_000_000);
+    }
+
+    #[test]
+    fn test_slow_blocks_lower_difficulty_to_the_clamp() {
+        let headers = retargeted_chain(60, 100 * TARGET_BLOCK_INTERVAL);
+        assert!(headers[2].difficulty.threshold > AREA_THRESHOLD);
+        assert_eq!(headers[59].difficulty.threshold, MAX_AREA_THRESHOLD);
+        assert_eq!(headers[59].difficulty.depth, INITIAL_FRACTAL_DEPTH as u8);
+    }
+
+    #[test]
+    fn test_backwards_timespan_is_clamped() {
+        let mut headers = retargeted_chain(11, TARGET_BLOCK_INTERVAL);
+        headers[0].timestamp = 100_000;
+        let next = next_difficulty(&headers);
+        assert_eq!(next.threshold, AREA_THRESHOLD / MAX_RETARGET_FACTOR);
+    }
+
+    #[test]
+    fn test_median_time_past_uses_recent_window() {
+        let mut headers = retargeted_chain(20, 10);
+        assert_eq!(median_time_past(&headers), 140);
+        // One wild timestamp cannot drag the median with it.
+        headers[19].timestamp = 1_000_000;
+        assert_eq!(median_time_past(&headers), 140);
+        assert_eq!(median_time_past(&headers[..1]), 0);
+        assert_eq!(median_time_past(&[]), 0);
+    }
+
+    #[test]
+    fn test_strategy_tags_and_names_round_trip() {
+        for strategy in [MiningStrategy::Deterministic, MiningStrategy::Chaotic] {
+            assert_eq!(MiningStrategy::from_u8(strategy.as_u8()), Some(strategy));
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+            assert_eq!(strategy.miner().strategy(), strategy);
+        }
+        assert_eq!(MiningStrategy::from_u8(2), None);
+        assert!("random".parse::<MiningStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_unreachable_target_returns_none() {
+        let shallow = BlockHeader { difficulty: Difficulty { depth: 1, threshold: AREA_THRESHOLD }, ..template() };
+        assert!(DeterministicMiner.mine(&shallow).is_none());
+    }
+}
@@ -1,49 +1,187 @@
-use crate::geometry::subdivision::{fractal_subdivide, FractalAddress};
-use crate::geometry::triangle::Triangle;
+//! Chaotic mining.
+//!
+//! A chaotic miner tries nonces in a scrambled order, and its proof is
+//! perturbed by a chaos offset: the address the proof-of-work hash selects is
+//! rotated by the offset, modulo the number of triangles at the mining depth,
+//! and it is the rotated address that must fall under the target.
+//!
+//! The offset is a PRF of the proof-of-work hash, so like a VRF output it is
+//! fixed by the header: a miner can only change it by changing the nonce,
+//! and a validator recomputes it from the header alone. No miner key is
+//! involved, so anyone can evaluate it. Rotating a uniformly distributed
+//! address leaves it uniform, so a chaotic proof is exactly as hard to find
+//! as a deterministic one.
+
+use crate::block::BlockHeader;
+use crate::core::mining::{address_from_hash, Miner, MiningStrategy};
+use crate::geometry::subdivision::FractalAddress;
 use rust_decimal::Decimal;
-use rand::Rng;
+use sha2::{Digest, Sha256};
+
+/// Domain separator for the chaos PRF.
+const CHAOS_DOMAIN: &[u8] = b"SierTriChain chaos offset v1";
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChaoticMiner;
+
+impl Miner for ChaoticMiner {
+    fn strategy(&self) -> MiningStrategy {
+        MiningStrategy::Chaotic
+    }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct ChaoticMiningResult {
+    fn nonce(&self, seed: u64, attempt: u64) -> u64 {
+        scramble(seed.wrapping_add(attempt))
+    }
+}
+
+/// The values a validator recomputes to check a chaotic proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChaosProof {
+    /// The address the proof-of-work hash selects.
+    pub candidate: FractalAddress,
+    /// The header's chaos offset, as an address at the mining depth.
+    pub offset: FractalAddress,
+    /// `candidate` rotated by `offset`, which the header must carry.
     pub address: FractalAddress,
-    pub triangle: Triangle,
-    pub chaos_factor: f64,
-}
-
-/// Perform chaotic mining by introducing randomness into the mining process.
-pub fn mine_chaos(depth: usize, threshold: Decimal) -> Option<ChaoticMiningResult> {
-    let a = crate::geometry::point::Point::new(Decimal::ZERO, Decimal::ZERO);
-    let b = crate::geometry::point::Point::new(Decimal::ONE, Decimal::ZERO);
-    let c = crate::geometry::point::Point::new(Decimal::from_f64(0.5).unwrap(), Decimal::from_f64(0.86602540378).unwrap());
-    let t = Triangle::new(a, b, c);
-
-    let mut triangles = Vec::new();
-    fractal_subdivide(&t, depth, FractalAddress(vec![]), &mut triangles);
-
-    let mut rng = rand::thread_rng();
-    for (triangle, address) in triangles {
-        let chaos_factor: f64 = rng.gen();
-        let effective_area = triangle.area() * Decimal::from_f64(chaos_factor).unwrap_or(Decimal::ONE);
-        if effective_area < threshold {
-            return Some(ChaoticMiningResult { address, triangle, chaos_factor });
-        }
+}
+
+impl ChaosProof {
+    /// The offset as a fraction of the genesis area, in `[0, 1)`. Rounded
+    /// past depth 14, as [`effective_area`](Self::effective_area) is.
+    pub fn chaos_factor(&self) -> Decimal {
+        area_before(&self.offset)
+    }
+
+    /// Area swept up to and including the proof address, as a fraction of
+    /// the genesis area, for display.
+    ///
+    /// Each level quarters the area, and a quarter to the 14th power already
+    /// takes the 28 decimal places a `Decimal` holds, so this is exact only
+    /// down to depth 14. Deeper addresses are rounded and may land on the
+    /// wrong side of a threshold they are close to. Validation does not use
+    /// it: [`meets_target`](crate::core::mining::meets_target) compares the
+    /// address with the target address exactly.
+    pub fn effective_area(&self) -> Decimal {
+        let triangle = (0..self.address.depth()).fold(Decimal::ONE, |area, _| area * Decimal::new(25, 2));
+        area_before(&self.address) + triangle
+    }
+}
+
+/// Derives the chaos offset for `header` and applies it to the address its
+/// proof-of-work hash selects.
+pub fn chaos_proof(header: &BlockHeader) -> ChaosProof {
+    let depth = header.difficulty.depth as usize;
+    let pow_hash = header.pow_hash();
+    let candidate = address_from_hash(&pow_hash, depth);
+    let offset = address_from_hash(&chaos_prf(&pow_hash), depth);
+    ChaosProof { candidate, offset, address: rotate(&candidate, &offset) }
+}
+
+/// SHA-256 keyed by the proof-of-work hash.
+fn chaos_prf(key: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(CHAOS_DOMAIN);
+    hasher.update(key);
+    hasher.finalize().into()
+}
+
+/// `address + offset` modulo the number of addresses at their depth.
+///
+/// Packed addresses are big-endian base-4 numbers aligned to the first bit,
+/// so this is plain 256-bit addition with the carry out of the top dropped.
+fn rotate(address: &FractalAddress, offset: &FractalAddress) -> FractalAddress {
+    let mut sum = [0u8; 32];
+    let mut carry = 0u16;
+    for i in (0..32).rev() {
+        let digit = address.as_bytes()[i] as u16 + offset.as_bytes()[i] as u16 + carry;
+        sum[i] = digit as u8;
+        carry = digit >> 8;
     }
-    None
+    FractalAddress::from_bytes(&sum, address.depth()).expect("depth of an existing address")
+}
+
+/// Area swept by the triangles before `address` at its depth, as a fraction
+/// of the genesis area.
+fn area_before(address: &FractalAddress) -> Decimal {
+    let quarter = Decimal::new(25, 2);
+    let mut scale = Decimal::ONE;
+    address.digits().fold(Decimal::ZERO, |area, digit| {
+        scale *= quarter;
+        area + scale * Decimal::from(digit)
+    })
+}
+
+/// The SplitMix64 finalizer, which is a bijection on `u64`: distinct
+/// attempts always map to distinct nonces.
+fn scramble(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rust_decimal_macros::dec;
+    use crate::core::mining::{meets_target, search_seed, solve, verify_proof};
+    use std::collections::HashSet;
+
+    fn template() -> BlockHeader {
+        BlockHeader { previous_hash: [1; 32], merkle_root: [2; 32], strategy: MiningStrategy::Chaotic, ..BlockHeader::test_template() }
+    }
+
+    fn address(digits: &[u8]) -> FractalAddress {
+        FractalAddress::from_digits(digits).unwrap()
+    }
+
+    #[test]
+    fn test_order_is_a_seeded_permutation() {
+        let seed = search_seed(&template());
+        let nonces: HashSet<u64> = (0..10_000).map(|attempt| ChaoticMiner.nonce(seed, attempt)).collect();
+        assert_eq!(nonces.len(), 10_000);
+        assert_ne!(ChaoticMiner.nonce(seed, 0), ChaoticMiner.nonce(seed ^ 1, 0));
+        let other = BlockHeader { merkle_root: [3; 32], ..template() };
+        assert_ne!(search_seed(&other), seed);
+    }
+
+    #[test]
+    fn test_rotation_wraps_at_depth() {
+        // 31 + 13 = 110 (base 4), which wraps to 10.
+        assert_eq!(rotate(&address(&[3, 1]), &address(&[1, 3])), address(&[1, 0]));
+        assert_eq!(rotate(&address(&[2, 0, 1]), &address(&[0, 0, 0])), address(&[2, 0, 1]));
+    }
+
     #[test]
-    fn test_mine_chaos_returns_some() {
-        let result = mine_chaos(2, dec!(0.5));
-        assert!(result.is_some());
+    fn test_areas() {
+        let proof = ChaosProof { candidate: address(&[1, 0]), offset: address(&[0, 2]), address: address(&[1, 2]) };
+        assert_eq!(proof.chaos_factor(), Decimal::new(125, 3));
+        // Triangles 0 through 6 of 16.
+        assert_eq!(proof.effective_area(), Decimal::new(4375, 4));
     }
+
+    #[test]
+    fn test_validator_recomputes_chaos() {
+        let header = ChaoticMiner.mine(&template()).unwrap();
+        assert_eq!(ChaoticMiner.mine(&template()), Some(header));
+        assert!(verify_proof(&header));
+
+        let proof = chaos_proof(&header);
+        assert_eq!(proof.address, header.address);
+        assert_eq!(proof.candidate, address_from_hash(&header.pow_hash(), header.address.depth()));
+        assert!(proof.chaos_factor() < Decimal::ONE);
+        assert!(proof.effective_area() <= header.difficulty.threshold);
+        // The unrotated candidate is not what the proof stands on.
+        assert_ne!(proof.candidate, proof.address);
+        assert!(!verify_proof(&BlockHeader { address: proof.candidate, ..header }));
+    }
+
     #[test]
-    fn test_chaos_factor_in_range() {
-        if let Some(res) = mine_chaos(2, dec!(0.5)) {
-            assert!(res.chaos_factor >= 0.0 && res.chaos_factor <= 1.0);
+    fn test_effective_area_decides_the_proof() {
+        let template = template();
+        for nonce in 0..200 {
+            let proof = chaos_proof(&BlockHeader { nonce, ..template });
+            let meets = meets_target(&proof.address, template.difficulty.threshold);
+            assert_eq!(meets, proof.effective_area() <= template.difficulty.threshold);
+            assert_eq!(meets, solve(&template, nonce).is_some());
         }
     }
 }
@@ -1,31 +1,34 @@
-use crate::geometry::subdivision::{fractal_subdivide, FractalAddress};
-use crate::geometry::triangle::Triangle;
-use rust_decimal::Decimal;
-use rust_decimal_macros::dec;
+//! Mining that tries nonces in counting order.
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct MiningResult {
-    pub address: FractalAddress,
-    pub triangle: Triangle,
-}
+use crate::core::mining::{Miner, MiningStrategy};
 
-pub fn mine_genesis(depth: usize, threshold: Decimal) -> Option<MiningResult> {
-    let a = crate::geometry::point::Point::new(dec!(0.0), dec!(0.0));
-    let b = crate::geometry::point::Point::new(dec!(1.0), dec!(0.0));
-    let c = crate::geometry::point::Point::new(dec!(0.5), dec!(0.86602540378));
-    let t = Triangle::new(a, b, c);
+/// Tries nonce 0, then 1, and so on, so the proof found for a template is
+/// always the one with the lowest winning nonce.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeterministicMiner;
 
-    let mut triangles = Vec::new();
-    fractal_subdivide(&t, depth, FractalAddress(vec![]), &mut triangles);
+impl Miner for DeterministicMiner {
+    fn strategy(&self) -> MiningStrategy {
+        MiningStrategy::Deterministic
+    }
 
-    for (triangle, address) in triangles {
-        if triangle.area() < threshold {
-            return Some(MiningResult { address, triangle });
-        }
+    fn nonce(&self, _seed: u64, attempt: u64) -> u64 {
+        attempt
     }
-    None
 }
 
-pub fn required_fractal_depth(block_height: usize, initial_depth: usize, adjustment_interval: usize) -> usize {
-    initial_depth + (block_height / adjustment_interval)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::core::mining::{solve, verify_proof};
+
+    #[test]
+    fn test_finds_lowest_winning_nonce() {
+        let template = BlockHeader { previous_hash: [1; 32], merkle_root: [2; 32], ..BlockHeader::test_template() };
+        let header = DeterministicMiner.mine(&template).unwrap();
+        assert!(verify_proof(&header));
+        assert_eq!(header.strategy, MiningStrategy::Deterministic);
+        assert!((0..header.nonce).all(|nonce| solve(&template, nonce).is_none()));
+    }
 }
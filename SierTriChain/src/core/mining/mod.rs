@@ -1,5 +0,0 @@
-//! Mining logic for SierTriChain
-// Includes deterministic and chaotic mining algorithms
-
-pub mod deterministic;
-pub mod chaos;
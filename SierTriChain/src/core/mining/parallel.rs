@@ -0,0 +1,278 @@
+//! Mining on several threads.
+//!
+//! The attempts of a [`Miner`]'s nonce order are cut into fixed-size chunks,
+//! dealt round-robin to the worker threads. When a worker solves an attempt
+//! the others finish only the chunks that come before it, so the proof found
+//! is the one with the lowest attempt number, the same one a single thread
+//! would find.
+
+use crate::block::BlockHeader;
+use crate::core::mining::{search_seed, solve, Miner, MiningStrategy};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Attempts a worker takes at a time between checks for a solution or
+/// cancellation.
+pub const CHUNK_SIZE: u64 = 1024;
+
+/// Live counters for a running search, shared with whoever is watching it.
+///
+/// Cancelling stops every worker after its current chunk; a node does this
+/// when a new tip makes the template stale.
+#[derive(Debug)]
+pub struct MiningProgress {
+    attempts: AtomicU64,
+    cancelled: AtomicBool,
+    started: Instant,
+}
+
+impl Default for MiningProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MiningProgress {
+    pub fn new() -> Self {
+        MiningProgress { attempts: AtomicU64::new(0), cancelled: AtomicBool::new(false), started: Instant::now() }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Attempts made so far, across all workers.
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Attempts per second since the search started.
+    pub fn hashrate(&self) -> f64 {
+        hashrate(self.attempts(), self.elapsed())
+    }
+}
+
+/// Totals for a finished search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiningStats {
+    pub attempts: u64,
+    pub elapsed: Duration,
+    /// Attempts made by each worker, in worker order.
+    pub worker_attempts: Vec<u64>,
+}
+
+impl MiningStats {
+    /// Attempts per second.
+    pub fn hashrate(&self) -> f64 {
+        hashrate(self.attempts, self.elapsed)
+    }
+}
+
+/// How a search ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MiningOutcome {
+    Found { header: Box<BlockHeader>, stats: MiningStats },
+    Cancelled(MiningStats),
+    /// The target is unreachable at the template's depth, or every nonce failed.
+    Exhausted(MiningStats),
+}
+
+impl MiningOutcome {
+    pub fn header(&self) -> Option<&BlockHeader> {
+        match self {
+            MiningOutcome::Found { header, .. } => Some(header.as_ref()),
+            _ => None,
+        }
+    }
+
+    pub fn stats(&self) -> &MiningStats {
+        match self {
+            MiningOutcome::Found { stats, .. } | MiningOutcome::Cancelled(stats) | MiningOutcome::Exhausted(stats) => stats,
+        }
+    }
+}
+
+/// Runs another miner's search on a fixed number of threads.
+pub struct ParallelMiner {
+    miner: Box<dyn Miner>,
+    threads: usize,
+}
+
+impl ParallelMiner {
+    /// Mines with `miner`'s nonce order on `threads` workers, at least one.
+    pub fn new(miner: Box<dyn Miner>, threads: usize) -> Self {
+        ParallelMiner { miner, threads: threads.max(1) }
+    }
+
+    /// Mines with one worker per available core.
+    pub fn with_available_parallelism(miner: Box<dyn Miner>) -> Self {
+        Self::new(miner, thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Searches for a proof for `template`, reporting into `progress` and
+    /// stopping early if it is cancelled.
+    pub fn search(&self, template: &BlockHeader, progress: &MiningProgress) -> MiningOutcome {
+        let template = BlockHeader { strategy: self.miner.strategy(), ..*template };
+        let mut worker_attempts = vec![0; self.threads];
+        if !template.difficulty.is_reachable() {
+            return MiningOutcome::Exhausted(self.stats(progress, worker_attempts));
+        }
+        let seed = search_seed(&template);
+        // The lowest solved attempt so far, and its header.
+        let best: Mutex<Option<(u64, BlockHeader)>> = Mutex::new(None);
+        let best_attempt = AtomicU64::new(u64::MAX);
+
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads)
+                .map(|worker| {
+                    let (template, best, best_attempt) = (&template, &best, &best_attempt);
+                    scope.spawn(move || {
+                        let mut made = 0u64;
+                        let mut chunk = worker as u64;
+                        while let Some(start) = chunk.checked_mul(CHUNK_SIZE) {
+                            if start >= best_attempt.load(Ordering::Relaxed) || progress.is_cancelled() {
+                                break;
+                            }
+                            let mut in_chunk = 0;
+                            for attempt in start..start.saturating_add(CHUNK_SIZE) {
+                                in_chunk += 1;
+                                if let Some(header) = solve(template, self.miner.nonce(seed, attempt)) {
+                                    let mut best = best.lock().expect("mining worker panicked");
+                                    if !matches!(*best, Some((lowest, _)) if lowest <= attempt) {
+                                        *best = Some((attempt, header));
+                                        best_attempt.fetch_min(attempt, Ordering::Relaxed);
+                                    }
+                                    break;
+                                }
+                            }
+                            made += in_chunk;
+                            progress.attempts.fetch_add(in_chunk, Ordering::Relaxed);
+                            chunk = chunk.saturating_add(self.threads as u64);
+                        }
+                        made
+                    })
+                })
+                .collect();
+            for (count, worker) in worker_attempts.iter_mut().zip(workers) {
+                *count = worker.join().expect("mining worker panicked");
+            }
+        });
+
+        let stats = self.stats(progress, worker_attempts);
+        match best.into_inner().expect("mining worker panicked") {
+            Some((_, header)) => MiningOutcome::Found { header: Box::new(header), stats },
+            None if progress.is_cancelled() => MiningOutcome::Cancelled(stats),
+            None => MiningOutcome::Exhausted(stats),
+        }
+    }
+
+    fn stats(&self, progress: &MiningProgress, worker_attempts: Vec<u64>) -> MiningStats {
+        MiningStats { attempts: worker_attempts.iter().sum(), elapsed: progress.elapsed(), worker_attempts }
+    }
+}
+
+impl Miner for ParallelMiner {
+    fn strategy(&self) -> MiningStrategy {
+        self.miner.strategy()
+    }
+
+    fn nonce(&self, seed: u64, attempt: u64) -> u64 {
+        self.miner.nonce(seed, attempt)
+    }
+
+    fn mine(&self, template: &BlockHeader) -> Option<BlockHeader> {
+        self.search(template, &MiningProgress::new()).header().copied()
+    }
+}
+
+fn hashrate(attempts: u64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => attempts as f64 / secs,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::mining::{verify_proof, Difficulty, MIN_AREA_THRESHOLD};
+    use rust_decimal_macros::dec;
+    use std::sync::Arc;
+
+    fn template(threshold: rust_decimal::Decimal) -> BlockHeader {
+        BlockHeader {
+            previous_hash: [1; 32],
+            merkle_root: [2; 32],
+            difficulty: Difficulty::for_threshold(threshold),
+            ..BlockHeader::test_template()
+        }
+    }
+
+    #[test]
+    fn test_finds_the_same_proof_as_one_thread() {
+        // Hard enough that a proof takes a few chunks to find.
+        let template = template(dec!(0.0002));
+        for strategy in [MiningStrategy::Deterministic, MiningStrategy::Chaotic] {
+            let expected = strategy.miner().mine(&template).unwrap();
+            for threads in [1, 3, 8] {
+                let miner = ParallelMiner::new(strategy.miner(), threads);
+                let outcome = miner.search(&template, &MiningProgress::new());
+                assert_eq!(outcome.header(), Some(&expected));
+                assert!(verify_proof(&expected));
+                let stats = outcome.stats();
+                assert_eq!(stats.worker_attempts.len(), threads);
+                assert_eq!(stats.attempts, stats.worker_attempts.iter().sum::<u64>());
+            }
+        }
+    }
+
+    #[test]
+    fn test_progress_counts_attempts() {
+        let template = template(dec!(0.0002));
+        let progress = MiningProgress::new();
+        let outcome = ParallelMiner::new(Box::new(crate::core::mining::deterministic::DeterministicMiner), 2)
+            .search(&template, &progress);
+        let nonce = outcome.header().unwrap().nonce;
+        // Every attempt up to the winning one was made, by some worker.
+        assert!(outcome.stats().attempts > nonce);
+        assert!(progress.attempts() > nonce);
+    }
+
+    #[test]
+    fn test_cancel_stops_workers() {
+        let template = template(MIN_AREA_THRESHOLD);
+        let progress = Arc::new(MiningProgress::new());
+        let watcher = Arc::clone(&progress);
+        let handle = thread::spawn(move || ParallelMiner::new(MiningStrategy::Chaotic.miner(), 4).search(&template, &watcher));
+        while progress.attempts() == 0 {
+            thread::yield_now();
+        }
+        progress.cancel();
+        let outcome = handle.join().unwrap();
+        assert!(matches!(outcome, MiningOutcome::Cancelled(_)));
+        assert!(outcome.stats().attempts > 0);
+    }
+
+    #[test]
+    fn test_unreachable_target_is_exhausted_at_once() {
+        let mut template = template(dec!(0.0025));
+        template.difficulty.depth = 1;
+        let outcome = ParallelMiner::new(MiningStrategy::Deterministic.miner(), 4).search(&template, &MiningProgress::new());
+        assert_eq!(outcome, MiningOutcome::Exhausted(outcome.stats().clone()));
+        assert_eq!(outcome.stats().attempts, 0);
+    }
+}
@@ -1 +1,37 @@
+//! Geometric checks shared by territory claims and conquests.
 
+use crate::geometry::triangle::{is_equilateral, Triangle};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+
+/// A claimable triangle is non-degenerate and equilateral to within `epsilon`,
+/// as every triangle of the genesis subdivision is.
+pub fn verify_geometric_proof(triangle: &Triangle, epsilon: f64) -> bool {
+    let Some(epsilon) = Decimal::from_f64(epsilon) else {
+        return false;
+    };
+    !triangle.is_degenerate() && is_equilateral(triangle, epsilon)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::lattice::LatticeTriangle;
+    use crate::geometry::hierarchy::FractalAddress;
+    use crate::geometry::point::Point;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_subdivision_triangles_pass() {
+        let address = FractalAddress::from_digits(&[3, 1, 2, 0, 3]).unwrap();
+        assert!(verify_geometric_proof(&Triangle::from(LatticeTriangle::at(&address)), 1e-8));
+    }
+
+    #[test]
+    fn test_skewed_and_flat_triangles_fail() {
+        let skewed = Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(2), dec!(0)), Point::new(dec!(0), dec!(2)));
+        assert!(!verify_geometric_proof(&skewed, 1e-8));
+        let p = Point::new(dec!(1), dec!(1));
+        assert!(!verify_geometric_proof(&Triangle::new(p, p, p), 1e-8));
+    }
+}
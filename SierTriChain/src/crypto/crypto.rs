@@ -1,8 +1,549 @@
 #[cfg(test)]
 mod tests {
+	use super::*;
+
 	#[test]
 	fn test_crypto_crypto_basic() {
 		assert_eq!(2 + 2, 4);
 	}
+
+	#[test]
+	fn test_ed25519_rfc8032_vector() {
+		// RFC 8032, section 7.1, test 1: the empty message.
+		let seed: [u8; 32] = hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").unwrap().try_into().unwrap();
+		let keypair = Ed25519.keypair_from_seed(&seed);
+		assert_eq!(hex::encode(&keypair.public.bytes), "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+		let signature = keypair.secret.sign(b"").unwrap();
+		assert_eq!(
+			hex::encode(&signature.bytes),
+			"e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+		);
+		assert_eq!(keypair.public.verify(b"", &signature), Ok(()));
+	}
+
+	#[test]
+	fn test_verification_fails_for_other_message_or_key() {
+		let keypair = Ed25519.generate_keypair();
+		let other = Ed25519.generate_keypair();
+		let signature = keypair.secret.sign(b"triangle").unwrap();
+		assert_eq!(keypair.public.verify(b"triangle", &signature), Ok(()));
+		assert_eq!(keypair.public.verify(b"triangles", &signature), Err(CryptoError::VerificationFailed));
+		assert_eq!(other.public.verify(b"triangle", &signature), Err(CryptoError::VerificationFailed));
+		let truncated = Signature { algorithm: SignatureAlgorithm::Ed25519, bytes: signature.bytes[1..].to_vec() };
+		assert_eq!(keypair.public.verify(b"triangle", &truncated), Err(CryptoError::InvalidSignature));
+	}
+
+	#[test]
+	fn test_addresses() {
+		let keypair = Ed25519.keypair_from_seed(&[7; 32]);
+		let address = Address::from_public_key(&keypair.public);
+		assert_eq!(address.algorithm(), Some(SignatureAlgorithm::Ed25519));
+		assert_eq!(address.to_string().len(), 2 * ADDRESS_LEN);
+		assert_eq!(address.to_string().parse(), Ok(address));
+		assert_ne!(address, Address::from_public_key(&Ed25519.keypair_from_seed(&[8; 32]).public));
+		assert_eq!("00ff".parse::<Address>(), Err(CryptoError::InvalidAddress));
+		assert_eq!("zz".repeat(ADDRESS_LEN).parse::<Address>(), Err(CryptoError::InvalidAddress));
+		assert_eq!(address.to_string().to_uppercase().parse::<Address>(), Err(CryptoError::InvalidAddress));
+	}
+
+	#[test]
+	fn test_witness_round_trip_and_signer() {
+		let keypair = Ed25519.keypair_from_seed(&[1; 32]);
+		let witness = Witness::sign(&keypair, b"block").unwrap();
+		let bytes = witness.encode();
+		assert_eq!(bytes.len(), 1 + 32 + 64);
+		assert_eq!(Witness::decode(&bytes), Ok(witness.clone()));
+		assert_eq!(witness.verify(&Address::from_public_key(&keypair.public), b"block"), Ok(()));
+		let stranger = Address::from_public_key(&Ed25519.keypair_from_seed(&[2; 32]).public);
+		assert_eq!(witness.verify(&stranger, b"block"), Err(CryptoError::WrongSigner));
+		assert_eq!(Witness::decode(&bytes[..40]), Err(CryptoError::InvalidSignature));
+		assert_eq!(Witness::decode(&[9]), Err(CryptoError::UnknownAlgorithm(9)));
+	}
+
+	#[test]
+	fn test_secp256k1_signatures_are_low_s() {
+		let keypair = Secp256k1.keypair_from_seed(&[0x11; 32]);
+		assert_eq!(keypair.public.bytes.len(), 33);
+		assert_eq!(keypair.address().algorithm(), Some(SignatureAlgorithm::Secp256k1));
+		let signature = keypair.secret.sign(b"triangle").unwrap();
+		assert_eq!(keypair.public.verify(b"triangle", &signature), Ok(()));
+		assert_eq!(keypair.public.verify(b"triangles", &signature), Err(CryptoError::VerificationFailed));
+
+		// Negating s gives the other, high-s encoding of the same signature.
+		let parsed = k256::ecdsa::Signature::from_slice(&signature.bytes).unwrap();
+		let (r, s) = parsed.split_scalars();
+		let high = k256::ecdsa::Signature::from_scalars(r, -*s).unwrap();
+		let high = Signature { algorithm: SignatureAlgorithm::Secp256k1, bytes: high.to_bytes().to_vec() };
+		assert_eq!(keypair.public.verify(b"triangle", &high), Err(CryptoError::InvalidSignature));
+	}
 }
 // Moved from src/crypto.rs
+
+// Signatures, keys and the addresses derived from them.
+//
+// Every key and signature is tagged with the `SignatureAlgorithm` that made
+// it, and an address starts with the tag of the key it hashes, so the chain
+// can verify anything it sees without knowing the scheme up front. Each
+// account picks its own algorithm when its key is made. Key encapsulation
+// sits behind `KeyEncapsulation` the same way; the post-quantum schemes
+// live in `crypto::pq`.
+
+use crate::crypto::pq::{MlDsa65, MlKem768};
+use ed25519_dalek::{Signer, SigningKey, VerifyingKey};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Bytes in an address: the algorithm tag, then the key hash.
+pub const ADDRESS_LEN: usize = 21;
+
+/// Bytes of key seed every scheme derives its key pair from.
+pub const SEED_LEN: usize = 32;
+
+/// Bytes of secret a key encapsulation agrees on.
+pub const SHARED_SECRET_LEN: usize = 32;
+
+/// Why a key, signature or address was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoError {
+	UnknownAlgorithm(u8),
+	InvalidKey,
+	/// The signature is malformed for its algorithm.
+	InvalidSignature,
+	VerificationFailed,
+	/// A key or signature was used with a different algorithm's.
+	AlgorithmMismatch,
+	/// The signing key does not hash to the expected address.
+	WrongSigner,
+	InvalidAddress,
+	InvalidCiphertext,
+	/// The backend could not make a key.
+	KeyGeneration,
+	/// A multisig policy needs 1 <= M <= N distinct keys, N at most
+	/// `MAX_COSIGNERS`.
+	InvalidPolicy,
+	/// The key is not one of the multisig account's.
+	NotACosigner,
+	/// Fewer valid signatures than the multisig threshold.
+	ThresholdNotMet { signatures: usize, threshold: usize },
+}
+
+impl fmt::Display for CryptoError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CryptoError::UnknownAlgorithm(tag) => write!(f, "unknown signature algorithm {}", tag),
+			CryptoError::InvalidKey => write!(f, "malformed key"),
+			CryptoError::InvalidSignature => write!(f, "malformed signature"),
+			CryptoError::VerificationFailed => write!(f, "signature does not verify"),
+			CryptoError::AlgorithmMismatch => write!(f, "key and signature use different algorithms"),
+			CryptoError::WrongSigner => write!(f, "signed by a key other than the address's"),
+			CryptoError::InvalidAddress => write!(f, "not an address"),
+			CryptoError::InvalidCiphertext => write!(f, "malformed ciphertext"),
+			CryptoError::KeyGeneration => write!(f, "key generation failed"),
+			CryptoError::InvalidPolicy => write!(f, "not a valid multisig policy"),
+			CryptoError::NotACosigner => write!(f, "key is not a cosigner of the account"),
+			CryptoError::ThresholdNotMet { signatures, threshold } => write!(f, "{} of {} required signatures", signatures, threshold),
+		}
+	}
+}
+
+impl std::error::Error for CryptoError {}
+
+/// The signature schemes the chain verifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SignatureAlgorithm {
+	Ed25519,
+	/// ML-DSA-65 from FIPS 204, the post-quantum option.
+	MlDsa65,
+	/// ECDSA over secp256k1, the curve hierarchical deterministic keys use.
+	Secp256k1,
+}
+
+impl SignatureAlgorithm {
+	pub fn as_u8(self) -> u8 {
+		match self {
+			SignatureAlgorithm::Ed25519 => 0,
+			SignatureAlgorithm::MlDsa65 => 1,
+			SignatureAlgorithm::Secp256k1 => 2,
+		}
+	}
+
+	pub fn from_u8(tag: u8) -> Option<Self> {
+		match tag {
+			0 => Some(SignatureAlgorithm::Ed25519),
+			1 => Some(SignatureAlgorithm::MlDsa65),
+			2 => Some(SignatureAlgorithm::Secp256k1),
+			_ => None,
+		}
+	}
+
+	/// The implementation behind this tag.
+	pub fn scheme(self) -> &'static dyn SignatureScheme {
+		match self {
+			SignatureAlgorithm::Ed25519 => &Ed25519,
+			SignatureAlgorithm::MlDsa65 => &MlDsa65,
+			SignatureAlgorithm::Secp256k1 => &Secp256k1,
+		}
+	}
+
+	pub fn public_key_len(self) -> usize {
+		match self {
+			SignatureAlgorithm::Ed25519 => 32,
+			SignatureAlgorithm::MlDsa65 => 1952,
+			SignatureAlgorithm::Secp256k1 => 33,
+		}
+	}
+
+	pub fn signature_len(self) -> usize {
+		match self {
+			SignatureAlgorithm::Ed25519 => 64,
+			SignatureAlgorithm::MlDsa65 => 3309,
+			SignatureAlgorithm::Secp256k1 => 64,
+		}
+	}
+}
+
+impl fmt::Display for SignatureAlgorithm {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SignatureAlgorithm::Ed25519 => write!(f, "ed25519"),
+			SignatureAlgorithm::MlDsa65 => write!(f, "ml-dsa-65"),
+			SignatureAlgorithm::Secp256k1 => write!(f, "secp256k1"),
+		}
+	}
+}
+
+impl FromStr for SignatureAlgorithm {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"ed25519" => Ok(SignatureAlgorithm::Ed25519),
+			"ml-dsa-65" => Ok(SignatureAlgorithm::MlDsa65),
+			"secp256k1" => Ok(SignatureAlgorithm::Secp256k1),
+			_ => Err(format!("unknown signature algorithm {:?}", s)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PublicKey {
+	pub algorithm: SignatureAlgorithm,
+	pub bytes: Vec<u8>,
+}
+
+impl PublicKey {
+	pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), CryptoError> {
+		if signature.algorithm != self.algorithm {
+			return Err(CryptoError::AlgorithmMismatch);
+		}
+		self.algorithm.scheme().verify(self, message, signature)
+	}
+}
+
+/// The private half of a key pair. Its bytes are wiped when dropped and
+/// kept out of `Debug` output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretKey {
+	pub algorithm: SignatureAlgorithm,
+	pub bytes: Vec<u8>,
+}
+
+impl SecretKey {
+	pub fn sign(&self, message: &[u8]) -> Result<Signature, CryptoError> {
+		self.algorithm.scheme().sign(self, message)
+	}
+}
+
+impl fmt::Debug for SecretKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "SecretKey({}, ..)", self.algorithm)
+	}
+}
+
+impl Drop for SecretKey {
+	fn drop(&mut self) {
+		self.bytes.fill(0);
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+	pub algorithm: SignatureAlgorithm,
+	pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPair {
+	pub public: PublicKey,
+	pub secret: SecretKey,
+}
+
+impl KeyPair {
+	pub fn address(&self) -> Address {
+		Address::from_public_key(&self.public)
+	}
+}
+
+/// Key generation, signing and verification for one algorithm.
+pub trait SignatureScheme: Send + Sync {
+	fn algorithm(&self) -> SignatureAlgorithm;
+
+	/// The key pair `seed` determines, so keys can be backed up as seeds.
+	fn keypair_from_seed(&self, seed: &[u8; SEED_LEN]) -> KeyPair;
+
+	fn sign(&self, secret: &SecretKey, message: &[u8]) -> Result<Signature, CryptoError>;
+
+	fn verify(&self, public: &PublicKey, message: &[u8], signature: &Signature) -> Result<(), CryptoError>;
+
+	/// A fresh key pair from the operating system's random number generator.
+	fn generate_keypair(&self) -> KeyPair {
+		let mut seed = [0; SEED_LEN];
+		OsRng.fill_bytes(&mut seed);
+		let keypair = self.keypair_from_seed(&seed);
+		seed.fill(0);
+		keypair
+	}
+}
+
+/// Ed25519 as in RFC 8032, the default scheme. Verification is strict, so
+/// a signature has one valid encoding.
+pub struct Ed25519;
+
+impl SignatureScheme for Ed25519 {
+	fn algorithm(&self) -> SignatureAlgorithm {
+		SignatureAlgorithm::Ed25519
+	}
+
+	fn keypair_from_seed(&self, seed: &[u8; SEED_LEN]) -> KeyPair {
+		let signing = SigningKey::from_bytes(seed);
+		KeyPair {
+			public: PublicKey { algorithm: SignatureAlgorithm::Ed25519, bytes: signing.verifying_key().to_bytes().to_vec() },
+			secret: SecretKey { algorithm: SignatureAlgorithm::Ed25519, bytes: seed.to_vec() },
+		}
+	}
+
+	fn sign(&self, secret: &SecretKey, message: &[u8]) -> Result<Signature, CryptoError> {
+		let seed: &[u8; SEED_LEN] = secret.bytes.as_slice().try_into().map_err(|_| CryptoError::InvalidKey)?;
+		let signature = SigningKey::from_bytes(seed).sign(message);
+		Ok(Signature { algorithm: SignatureAlgorithm::Ed25519, bytes: signature.to_bytes().to_vec() })
+	}
+
+	fn verify(&self, public: &PublicKey, message: &[u8], signature: &Signature) -> Result<(), CryptoError> {
+		let key: &[u8; 32] = public.bytes.as_slice().try_into().map_err(|_| CryptoError::InvalidKey)?;
+		let key = VerifyingKey::from_bytes(key).map_err(|_| CryptoError::InvalidKey)?;
+		let signature: &[u8; 64] = signature.bytes.as_slice().try_into().map_err(|_| CryptoError::InvalidSignature)?;
+		key.verify_strict(message, &ed25519_dalek::Signature::from_bytes(signature))
+			.map_err(|_| CryptoError::VerificationFailed)
+	}
+}
+
+/// ECDSA over secp256k1 with SHA-256 and RFC 6979 nonces. Public keys are
+/// SEC1 compressed points and signatures are `r || s` with `s` in the low
+/// half, so, as with Ed25519, a signature has one valid encoding. The seed is
+/// the secret scalar itself, which is what hierarchical derivation yields.
+pub struct Secp256k1;
+
+impl SignatureScheme for Secp256k1 {
+	fn algorithm(&self) -> SignatureAlgorithm {
+		SignatureAlgorithm::Secp256k1
+	}
+
+	/// Panics if `seed` is zero or not below the group order, which a random
+	/// or derived seed is with overwhelming probability.
+	fn keypair_from_seed(&self, seed: &[u8; SEED_LEN]) -> KeyPair {
+		let signing = k256::ecdsa::SigningKey::from_slice(seed).expect("seed is a valid secp256k1 scalar");
+		KeyPair {
+			public: PublicKey {
+				algorithm: SignatureAlgorithm::Secp256k1,
+				bytes: signing.verifying_key().to_encoded_point(true).as_bytes().to_vec(),
+			},
+			secret: SecretKey { algorithm: SignatureAlgorithm::Secp256k1, bytes: seed.to_vec() },
+		}
+	}
+
+	fn sign(&self, secret: &SecretKey, message: &[u8]) -> Result<Signature, CryptoError> {
+		let signing = k256::ecdsa::SigningKey::from_slice(&secret.bytes).map_err(|_| CryptoError::InvalidKey)?;
+		let signature: k256::ecdsa::Signature = k256::ecdsa::signature::Signer::sign(&signing, message);
+		let signature = signature.normalize_s().unwrap_or(signature);
+		Ok(Signature { algorithm: SignatureAlgorithm::Secp256k1, bytes: signature.to_bytes().to_vec() })
+	}
+
+	fn verify(&self, public: &PublicKey, message: &[u8], signature: &Signature) -> Result<(), CryptoError> {
+		if public.bytes.len() != SignatureAlgorithm::Secp256k1.public_key_len() {
+			return Err(CryptoError::InvalidKey);
+		}
+		let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&public.bytes).map_err(|_| CryptoError::InvalidKey)?;
+		let signature = k256::ecdsa::Signature::from_slice(&signature.bytes).map_err(|_| CryptoError::InvalidSignature)?;
+		if signature.normalize_s().is_some() {
+			return Err(CryptoError::InvalidSignature);
+		}
+		k256::ecdsa::signature::Verifier::verify(&key, message, &signature).map_err(|_| CryptoError::VerificationFailed)
+	}
+}
+
+/// Who can authorise spending from an account: the algorithm tag, then the
+/// first 20 bytes of SHA-256 over the tag and the public key. Written as
+/// lowercase hex.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Address([u8; ADDRESS_LEN]);
+
+impl Address {
+	pub fn from_public_key(public: &PublicKey) -> Self {
+		let tag = public.algorithm.as_u8();
+		let mut hasher = Sha256::new();
+		hasher.update([tag]);
+		hasher.update(&public.bytes);
+		let digest = hasher.finalize();
+		let mut bytes = [0; ADDRESS_LEN];
+		bytes[0] = tag;
+		bytes[1..].copy_from_slice(&digest[..ADDRESS_LEN - 1]);
+		Address(bytes)
+	}
+
+	pub fn from_bytes(bytes: [u8; ADDRESS_LEN]) -> Self {
+		Address(bytes)
+	}
+
+	pub fn as_bytes(&self) -> &[u8; ADDRESS_LEN] {
+		&self.0
+	}
+
+	/// The algorithm of the key behind the address, if it is a known one.
+	pub fn algorithm(&self) -> Option<SignatureAlgorithm> {
+		SignatureAlgorithm::from_u8(self.0[0])
+	}
+}
+
+impl fmt::Display for Address {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", hex::encode(self.0))
+	}
+}
+
+impl FromStr for Address {
+	type Err = CryptoError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let bytes = hex::decode(s).map_err(|_| CryptoError::InvalidAddress)?;
+		let address = Address(bytes.try_into().map_err(|_| CryptoError::InvalidAddress)?);
+		// One spelling per address, since accounts are keyed by it.
+		if address.to_string() != s {
+			return Err(CryptoError::InvalidAddress);
+		}
+		Ok(address)
+	}
+}
+
+/// A signature together with the key that made it, which is what signed
+/// data carries since addresses only hash the key. Encoded as the algorithm
+/// tag, the public key, then the signature, each at the algorithm's length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+	pub public_key: PublicKey,
+	pub signature: Signature,
+}
+
+impl Witness {
+	pub fn sign(keypair: &KeyPair, message: &[u8]) -> Result<Self, CryptoError> {
+		Ok(Witness { public_key: keypair.public.clone(), signature: keypair.secret.sign(message)? })
+	}
+
+	/// Checks that the key hashes to `signer` and signed `message`.
+	pub fn verify(&self, signer: &Address, message: &[u8]) -> Result<(), CryptoError> {
+		if Address::from_public_key(&self.public_key) != *signer {
+			return Err(CryptoError::WrongSigner);
+		}
+		self.public_key.verify(message, &self.signature)
+	}
+
+	pub fn encode(&self) -> Vec<u8> {
+		let mut out = vec![self.public_key.algorithm.as_u8()];
+		out.extend_from_slice(&self.public_key.bytes);
+		out.extend_from_slice(&self.signature.bytes);
+		out
+	}
+
+	pub fn decode(bytes: &[u8]) -> Result<Self, CryptoError> {
+		let (&tag, rest) = bytes.split_first().ok_or(CryptoError::InvalidSignature)?;
+		let algorithm = SignatureAlgorithm::from_u8(tag).ok_or(CryptoError::UnknownAlgorithm(tag))?;
+		let key_len = algorithm.public_key_len();
+		if rest.len() != key_len + algorithm.signature_len() {
+			return Err(CryptoError::InvalidSignature);
+		}
+		let (key, signature) = rest.split_at(key_len);
+		Ok(Witness {
+			public_key: PublicKey { algorithm, bytes: key.to_vec() },
+			signature: Signature { algorithm, bytes: signature.to_vec() },
+		})
+	}
+}
+
+/// The key encapsulation mechanisms peers can agree secrets with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KemAlgorithm {
+	/// ML-KEM-768 from FIPS 203.
+	MlKem768,
+}
+
+impl KemAlgorithm {
+	/// The implementation behind this algorithm.
+	pub fn scheme(self) -> &'static dyn KeyEncapsulation {
+		match self {
+			KemAlgorithm::MlKem768 => &MlKem768,
+		}
+	}
+}
+
+impl fmt::Display for KemAlgorithm {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			KemAlgorithm::MlKem768 => write!(f, "ml-kem-768"),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EncapsulationKey {
+	pub algorithm: KemAlgorithm,
+	pub bytes: Vec<u8>,
+}
+
+/// The private half of a KEM key pair, wiped on drop like a `SecretKey`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DecapsulationKey {
+	pub algorithm: KemAlgorithm,
+	pub bytes: Vec<u8>,
+}
+
+impl fmt::Debug for DecapsulationKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "DecapsulationKey({}, ..)", self.algorithm)
+	}
+}
+
+impl Drop for DecapsulationKey {
+	fn drop(&mut self) {
+		self.bytes.fill(0);
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KemKeyPair {
+	pub public: EncapsulationKey,
+	pub secret: DecapsulationKey,
+}
+
+/// Agreeing a shared secret with the holder of a decapsulation key.
+pub trait KeyEncapsulation: Send + Sync {
+	fn algorithm(&self) -> KemAlgorithm;
+
+	fn generate_keypair(&self) -> Result<KemKeyPair, CryptoError>;
+
+	/// A fresh secret and the ciphertext that carries it to `public`'s holder.
+	fn encapsulate(&self, public: &EncapsulationKey) -> Result<(Vec<u8>, [u8; SHARED_SECRET_LEN]), CryptoError>;
+
+	fn decapsulate(&self, secret: &DecapsulationKey, ciphertext: &[u8]) -> Result<[u8; SHARED_SECRET_LEN], CryptoError>;
+}
@@ -1,8 +1,82 @@
 #[cfg(test)]
 mod tests {
+	use super::*;
+
 	#[test]
 	fn test_crypto_hash_basic() {
 		assert_eq!(2 + 2, 4);
 	}
+
+	#[test]
+	fn test_merkle_root_empty_is_zero() {
+		assert_eq!(merkle_root(&[]), [0; 32]);
+	}
+
+	#[test]
+	fn test_merkle_root_single_leaf_is_leaf() {
+		assert_eq!(merkle_root(&[[7; 32]]), [7; 32]);
+	}
+
+	#[test]
+	fn test_merkle_root_pairs_leaves() {
+		let mut hasher = Sha256::new();
+		hasher.update([1; 32]);
+		hasher.update([2; 32]);
+		assert_eq!(merkle_root(&[[1; 32], [2; 32]]), <[u8; 32]>::from(hasher.finalize()));
+	}
+
+	#[test]
+	fn test_merkle_root_depends_on_order() {
+		assert_ne!(merkle_root(&[[1; 32], [2; 32]]), merkle_root(&[[2; 32], [1; 32]]));
+	}
+
+	#[test]
+	fn test_geometric_hash_ignores_digits_past_precision() {
+		use crate::geometry::point::Point;
+		use rust_decimal_macros::dec;
+		let t = |y| Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(1.0), dec!(0)), Point::new(dec!(0.5), y));
+		assert_eq!(geometric_hash(&t(dec!(0.866025403)), 8), geometric_hash(&t(dec!(0.866025404)), 8));
+		assert_ne!(geometric_hash(&t(dec!(0.86602540)), 8), geometric_hash(&t(dec!(0.86602541)), 8));
+	}
 }
 // Moved from src/hash.rs
+
+use crate::geometry::triangle::Triangle;
+use sha2::{Digest, Sha256};
+
+/// Binary SHA-256 Merkle root over leaf hashes such as txids.
+///
+/// An odd node at any level is paired with itself; an empty list commits to
+/// the all-zero hash.
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+	if leaves.is_empty() {
+		return [0; 32];
+	}
+	let mut level = leaves.to_vec();
+	while level.len() > 1 {
+		level = level
+			.chunks(2)
+			.map(|pair| {
+				let mut hasher = Sha256::new();
+				hasher.update(pair[0]);
+				hasher.update(pair.get(1).unwrap_or(&pair[0]));
+				hasher.finalize().into()
+			})
+			.collect();
+	}
+	level[0]
+}
+
+/// Hex SHA-256 of a triangle's vertices rounded to `precision` decimal places.
+///
+/// Coordinates are normalised first, so `1.0` and `1` hash the same, and
+/// vertices are taken in the order given.
+pub fn geometric_hash(triangle: &Triangle, precision: u32) -> String {
+	let mut hasher = Sha256::new();
+	for point in [triangle.a, triangle.b, triangle.c] {
+		for coordinate in [point.x, point.y] {
+			hasher.update(coordinate.round_dp(precision).normalize().serialize());
+		}
+	}
+	hex::encode(hasher.finalize())
+}
@@ -0,0 +1,374 @@
+//! Hierarchical deterministic keys, as in BIP32.
+//!
+//! A master key and chain code come from a seed, and every key derives
+//! children by index. Hardened children (index at or above
+//! [`HARDENED_OFFSET`]) need the parent's private key; normal ones can also
+//! be derived from the parent's extended public key, which lets a
+//! watch-only wallet follow a whole subtree of addresses. Keys are
+//! secp256k1 and sign as [`SignatureAlgorithm::Secp256k1`].
+//!
+//! Territories map onto the tree by their fractal address: each digit is
+//! the child index of one level, so the key for a territory is a descendant
+//! of the key for any territory containing it.
+//!
+//! Derivation and the `xprv`/`xpub` encodings follow BIP32 exactly, so its
+//! test vectors apply.
+
+use crate::crypto::crypto::{Address, KeyPair, PublicKey, Secp256k1, SignatureAlgorithm, SignatureScheme};
+use crate::geometry::hierarchy::FractalAddress;
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::group::prime::PrimeCurveAffine;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::elliptic_curve::PrimeField;
+use k256::{ProjectivePoint, Scalar};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::str::FromStr;
+
+/// Indices from here up derive hardened children.
+pub const HARDENED_OFFSET: u32 = 1 << 31;
+
+const MASTER_KEY: &[u8] = b"Bitcoin seed";
+const XPRV_VERSION: [u8; 4] = [0x04, 0x88, 0xad, 0xe4];
+const XPUB_VERSION: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const ENCODED_LEN: usize = 78;
+
+/// Why a key could not be derived or decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdError {
+    /// Seeds are 16 to 64 bytes.
+    InvalidSeedLength(usize),
+    /// The seed or a child index gives no valid key; BIP32 says to move on
+    /// to the next one, which happens with probability below 2^-127.
+    InvalidKey,
+    /// Hardened children cannot be derived from a public key.
+    HardenedFromPublic(u32),
+    /// Keys are at most 255 levels deep.
+    TooDeep,
+    InvalidEncoding,
+}
+
+impl fmt::Display for HdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HdError::InvalidSeedLength(len) => write!(f, "seed of {} bytes is not 16 to 64", len),
+            HdError::InvalidKey => write!(f, "derivation gives no valid key"),
+            HdError::HardenedFromPublic(index) => write!(f, "cannot derive hardened child {} from a public key", index),
+            HdError::TooDeep => write!(f, "key is too deep to derive further"),
+            HdError::InvalidEncoding => write!(f, "not an extended key"),
+        }
+    }
+}
+
+impl std::error::Error for HdError {}
+
+/// Where a key sits in its tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    depth: u8,
+    parent_fingerprint: [u8; 4],
+    child_number: u32,
+    chain_code: [u8; 32],
+}
+
+impl Position {
+    fn child(&self, parent_fingerprint: [u8; 4], index: u32, chain_code: [u8; 32]) -> Result<Self, HdError> {
+        let depth = self.depth.checked_add(1).ok_or(HdError::TooDeep)?;
+        Ok(Position { depth, parent_fingerprint, child_number: index, chain_code })
+    }
+
+    fn encode(&self, version: [u8; 4], key: &[u8; 33]) -> String {
+        let mut bytes = Vec::with_capacity(ENCODED_LEN);
+        bytes.extend_from_slice(&version);
+        bytes.push(self.depth);
+        bytes.extend_from_slice(&self.parent_fingerprint);
+        bytes.extend_from_slice(&self.child_number.to_be_bytes());
+        bytes.extend_from_slice(&self.chain_code);
+        bytes.extend_from_slice(key);
+        bs58::encode(bytes).with_check().into_string()
+    }
+
+    fn decode(s: &str, version: [u8; 4]) -> Result<(Self, [u8; 33]), HdError> {
+        let bytes = bs58::decode(s).with_check(None).into_vec().map_err(|_| HdError::InvalidEncoding)?;
+        if bytes.len() != ENCODED_LEN || bytes[..4] != version {
+            return Err(HdError::InvalidEncoding);
+        }
+        let position = Position {
+            depth: bytes[4],
+            parent_fingerprint: bytes[5..9].try_into().expect("4 bytes"),
+            child_number: u32::from_be_bytes(bytes[9..13].try_into().expect("4 bytes")),
+            chain_code: bytes[13..45].try_into().expect("32 bytes"),
+        };
+        // A master key has no parent.
+        if position.depth == 0 && (position.parent_fingerprint != [0; 4] || position.child_number != 0) {
+            return Err(HdError::InvalidEncoding);
+        }
+        Ok((position, bytes[45..].try_into().expect("33 bytes")))
+    }
+}
+
+/// HMAC-SHA512 split into the key tweak and the chain code.
+fn hmac_halves(key: &[u8], data: &[&[u8]]) -> (Scalar, [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes any key length");
+    for part in data {
+        mac.update(part);
+    }
+    let output = mac.finalize().into_bytes();
+    let tweak: [u8; 32] = output[..32].try_into().expect("32 bytes");
+    let chain_code = output[32..].try_into().expect("32 bytes");
+    // Out-of-range tweaks come back as zero, which every caller refuses.
+    let tweak = Option::<Scalar>::from(Scalar::from_repr(tweak.into())).unwrap_or(Scalar::ZERO);
+    (tweak, chain_code)
+}
+
+fn compressed(point: &k256::AffinePoint) -> [u8; 33] {
+    point.to_encoded_point(true).as_bytes().try_into().expect("compressed points are 33 bytes")
+}
+
+fn fingerprint(point: &k256::AffinePoint) -> [u8; 4] {
+    let hash = Ripemd160::digest(Sha256::digest(compressed(point)));
+    hash[..4].try_into().expect("4 bytes")
+}
+
+/// A private key with the chain code to derive its children.
+#[derive(Clone)]
+pub struct ExtendedPrivateKey {
+    position: Position,
+    secret: k256::NonZeroScalar,
+}
+
+impl ExtendedPrivateKey {
+    /// The master key for `seed`.
+    pub fn master(seed: &[u8]) -> Result<Self, HdError> {
+        if !(16..=64).contains(&seed.len()) {
+            return Err(HdError::InvalidSeedLength(seed.len()));
+        }
+        let (secret, chain_code) = hmac_halves(MASTER_KEY, &[seed]);
+        let secret = Option::from(k256::NonZeroScalar::new(secret)).ok_or(HdError::InvalidKey)?;
+        Ok(ExtendedPrivateKey {
+            position: Position { depth: 0, parent_fingerprint: [0; 4], child_number: 0, chain_code },
+            secret,
+        })
+    }
+
+    pub fn derive_child(&self, index: u32) -> Result<Self, HdError> {
+        let point = self.point();
+        let (tweak, chain_code) = if index >= HARDENED_OFFSET {
+            let secret: [u8; 32] = self.secret.to_repr().into();
+            hmac_halves(&self.position.chain_code, &[&[0], &secret, &index.to_be_bytes()])
+        } else {
+            hmac_halves(&self.position.chain_code, &[&compressed(&point), &index.to_be_bytes()])
+        };
+        if bool::from(tweak.is_zero()) {
+            return Err(HdError::InvalidKey);
+        }
+        let secret = Option::from(k256::NonZeroScalar::new(tweak + *self.secret)).ok_or(HdError::InvalidKey)?;
+        Ok(ExtendedPrivateKey { position: self.position.child(fingerprint(&point), index, chain_code)?, secret })
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, HdError> {
+        path.iter().try_fold(self.clone(), |key, &index| key.derive_child(index))
+    }
+
+    pub fn public(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey { position: self.position, point: self.point() }
+    }
+
+    /// The key for signing as this node of the tree.
+    pub fn keypair(&self) -> KeyPair {
+        Secp256k1.keypair_from_seed(&self.secret.to_repr().into())
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.position.depth
+    }
+
+    fn point(&self) -> k256::AffinePoint {
+        (ProjectivePoint::GENERATOR * *self.secret).to_affine()
+    }
+}
+
+impl fmt::Debug for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ExtendedPrivateKey(depth {}, ..)", self.position.depth)
+    }
+}
+
+/// The `xprv` encoding. It is as secret as the key.
+impl fmt::Display for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut key = [0; 33];
+        key[1..].copy_from_slice(&self.secret.to_repr());
+        write!(f, "{}", self.position.encode(XPRV_VERSION, &key))
+    }
+}
+
+impl FromStr for ExtendedPrivateKey {
+    type Err = HdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, key) = Position::decode(s, XPRV_VERSION)?;
+        if key[0] != 0 {
+            return Err(HdError::InvalidEncoding);
+        }
+        let secret: [u8; 32] = key[1..].try_into().expect("32 bytes");
+        let secret = Option::from(k256::NonZeroScalar::from_repr(secret.into())).ok_or(HdError::InvalidEncoding)?;
+        Ok(ExtendedPrivateKey { position, secret })
+    }
+}
+
+/// A public key with the chain code to derive its normal children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    position: Position,
+    point: k256::AffinePoint,
+}
+
+impl ExtendedPublicKey {
+    pub fn derive_child(&self, index: u32) -> Result<Self, HdError> {
+        if index >= HARDENED_OFFSET {
+            return Err(HdError::HardenedFromPublic(index));
+        }
+        let (tweak, chain_code) = hmac_halves(&self.position.chain_code, &[&compressed(&self.point), &index.to_be_bytes()]);
+        if bool::from(tweak.is_zero()) {
+            return Err(HdError::InvalidKey);
+        }
+        let point = (ProjectivePoint::GENERATOR * tweak + ProjectivePoint::from(self.point)).to_affine();
+        if bool::from(point.is_identity()) {
+            return Err(HdError::InvalidKey);
+        }
+        Ok(ExtendedPublicKey { position: self.position.child(fingerprint(&self.point), index, chain_code)?, point })
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> Result<Self, HdError> {
+        path.iter().try_fold(*self, |key, &index| key.derive_child(index))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey { algorithm: SignatureAlgorithm::Secp256k1, bytes: compressed(&self.point).to_vec() }
+    }
+
+    pub fn address(&self) -> Address {
+        Address::from_public_key(&self.public_key())
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.position.depth
+    }
+}
+
+/// The `xpub` encoding.
+impl fmt::Display for ExtendedPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.position.encode(XPUB_VERSION, &compressed(&self.point)))
+    }
+}
+
+impl FromStr for ExtendedPublicKey {
+    type Err = HdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, key) = Position::decode(s, XPUB_VERSION)?;
+        let point = k256::PublicKey::from_sec1_bytes(&key).map_err(|_| HdError::InvalidEncoding)?;
+        if key[0] != 2 && key[0] != 3 {
+            return Err(HdError::InvalidEncoding);
+        }
+        Ok(ExtendedPublicKey { position, point: *point.as_affine() })
+    }
+}
+
+/// Child indices from the key for `ancestor` to the key for `descendant`,
+/// one digit per level, or `None` if `descendant` is not inside `ancestor`.
+pub fn fractal_path(ancestor: &FractalAddress, descendant: &FractalAddress, hardened: bool) -> Option<Vec<u32>> {
+    if !ancestor.is_ancestor_of(descendant) && ancestor != descendant {
+        return None;
+    }
+    let offset = if hardened { HARDENED_OFFSET } else { 0 };
+    Some(descendant.digits().skip(ancestor.depth()).map(|digit| offset + digit as u32).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const H: u32 = HARDENED_OFFSET;
+
+    /// BIP32 test vector 1, as (path, xpub, xprv).
+    const VECTOR_1: [(&[u32], &str, &str); 4] = [
+        (
+            &[],
+            "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+            "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+        ),
+        (
+            &[H],
+            "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+            "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+        ),
+        (
+            &[H, 1],
+            "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+            "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+        ),
+        (
+            &[H, 1, H + 2],
+            "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+            "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+        ),
+    ];
+
+    fn master() -> ExtendedPrivateKey {
+        ExtendedPrivateKey::master(&hex::decode("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_bip32_vector_1() {
+        for (path, xpub, xprv) in VECTOR_1 {
+            let key = master().derive_path(path).unwrap();
+            assert_eq!(key.to_string(), xprv);
+            assert_eq!(key.public().to_string(), xpub);
+            assert_eq!(xprv.parse::<ExtendedPrivateKey>().unwrap().to_string(), xprv);
+            assert_eq!(xpub.parse::<ExtendedPublicKey>(), Ok(key.public()));
+        }
+    }
+
+    #[test]
+    fn test_public_derivation_matches_private_for_normal_children() {
+        let account = master().derive_child(H).unwrap();
+        let watch_only = account.public();
+        assert_eq!(watch_only.derive_path(&[1, 7]), Ok(account.derive_path(&[1, 7]).unwrap().public()));
+        assert_eq!(watch_only.derive_child(H + 2), Err(HdError::HardenedFromPublic(H + 2)));
+        assert_ne!(account.derive_child(H + 1).unwrap().public(), account.derive_child(1).unwrap().public());
+    }
+
+    #[test]
+    fn test_territory_keys_follow_fractal_addresses() {
+        let region = FractalAddress::from_digits(&[3, 1]).unwrap();
+        let territory = FractalAddress::from_digits(&[3, 1, 0, 2]).unwrap();
+        assert_eq!(fractal_path(&FractalAddress::root(), &territory, false), Some(vec![3, 1, 0, 2]));
+        assert_eq!(fractal_path(&region, &territory, true), Some(vec![H, H + 2]));
+        assert_eq!(fractal_path(&territory, &region, false), None);
+        assert_eq!(fractal_path(&region, &region, false), Some(vec![]));
+
+        // A region's xpub watches every territory inside it.
+        let region_key = master().derive_path(&fractal_path(&FractalAddress::root(), &region, false).unwrap()).unwrap();
+        let territory_key = master().derive_path(&fractal_path(&FractalAddress::root(), &territory, false).unwrap()).unwrap();
+        let watched = region_key.public().derive_path(&fractal_path(&region, &territory, false).unwrap()).unwrap();
+        assert_eq!(watched.address(), territory_key.keypair().address());
+        assert_eq!(watched.depth(), 4);
+
+        let signature = territory_key.keypair().secret.sign(b"claim").unwrap();
+        assert_eq!(watched.public_key().verify(b"claim", &signature), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_bad_seeds_and_encodings() {
+        assert_eq!(ExtendedPrivateKey::master(&[0; 15]).err(), Some(HdError::InvalidSeedLength(15)));
+        let xpub = VECTOR_1[1].1;
+        let mut corrupted = xpub.to_string();
+        corrupted.replace_range(20..21, if &xpub[20..21] == "a" { "b" } else { "a" });
+        assert_eq!(corrupted.parse::<ExtendedPublicKey>(), Err(HdError::InvalidEncoding));
+        assert_eq!(VECTOR_1[1].2.parse::<ExtendedPublicKey>(), Err(HdError::InvalidEncoding));
+    }
+}
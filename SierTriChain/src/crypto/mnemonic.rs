@@ -0,0 +1,272 @@
+//! Mnemonic seed phrases, as in BIP39.
+//!
+//! A phrase spells out 128 to 256 bits of entropy followed by a checksum of
+//! one bit per 32 bits of entropy, 11 bits to each word of the English
+//! wordlist. The wallet seed stretches the phrase with PBKDF2-HMAC-SHA512
+//! over 2048 rounds, salted with an optional passphrase, so a different
+//! passphrase opens a different wallet rather than failing.
+//!
+//! The same entropy and checksum can also be written geometrically, as the
+//! subdivision choices (0 to 3) of a walk down the fractal triangle: two
+//! bits per step, with the checksum padded to a whole step by a zero bit.
+//!
+//! Phrase, entropy and seed all match the BIP39 test vectors.
+
+use pbkdf2::pbkdf2_hmac;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+/// Bytes of the seed a phrase stretches to.
+pub const SEED_LEN: usize = 64;
+
+const ROUNDS: u32 = 2048;
+const BITS_PER_WORD: usize = 11;
+const ENGLISH: &str = include_str!("bip39_english.txt");
+
+/// The 2048 English words, in order.
+fn wordlist() -> &'static [&'static str] {
+    static WORDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    WORDS.get_or_init(|| ENGLISH.lines().collect())
+}
+
+/// Why a phrase was not accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MnemonicError {
+    /// Entropy is 16 to 32 bytes, in steps of 4.
+    InvalidEntropyLength(usize),
+    /// Phrases are 12 to 24 words, in steps of 3.
+    InvalidWordCount(usize),
+    UnknownWord(String),
+    /// The geometric encoding has the wrong number of choices, or one
+    /// outside 0 to 3.
+    InvalidGeometric,
+    /// Every word is known but the checksum does not match, as when a word
+    /// is mistyped for another one or two are swapped.
+    InvalidChecksum,
+}
+
+impl fmt::Display for MnemonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MnemonicError::InvalidEntropyLength(len) => write!(f, "{} bytes of entropy is not 16 to 32 in steps of 4", len),
+            MnemonicError::InvalidWordCount(count) => write!(f, "{} words is not 12 to 24 in steps of 3", count),
+            MnemonicError::UnknownWord(word) => write!(f, "'{}' is not in the wordlist", word),
+            MnemonicError::InvalidGeometric => write!(f, "not a geometric phrase"),
+            MnemonicError::InvalidChecksum => write!(f, "phrase checksum does not match"),
+        }
+    }
+}
+
+impl std::error::Error for MnemonicError {}
+
+/// The entropy a phrase stands for. It is as secret as the wallet it opens.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+impl Mnemonic {
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, MnemonicError> {
+        if !(16..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err(MnemonicError::InvalidEntropyLength(entropy.len()));
+        }
+        Ok(Mnemonic { entropy: entropy.to_vec() })
+    }
+
+    /// A fresh phrase of `word_count` words.
+    pub fn generate(word_count: usize) -> Result<Self, MnemonicError> {
+        if !(12..=24).contains(&word_count) || !word_count.is_multiple_of(3) {
+            return Err(MnemonicError::InvalidWordCount(word_count));
+        }
+        let mut entropy = vec![0; word_count / 3 * 4];
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic = Self::from_entropy(&entropy);
+        entropy.fill(0);
+        mnemonic
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.entropy.len() * 8 / 32 * 3
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let bits = self.bits();
+        (0..self.word_count()).map(move |word| {
+            let index = bits[word * BITS_PER_WORD..(word + 1) * BITS_PER_WORD].iter().fold(0, |index, &bit| index << 1 | bit as usize);
+            wordlist()[index]
+        })
+    }
+
+    /// The words separated by single spaces.
+    pub fn phrase(&self) -> String {
+        self.words().collect::<Vec<_>>().join(" ")
+    }
+
+    /// The wallet seed. Any passphrase, including none, gives a valid seed.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; SEED_LEN] {
+        let phrase: String = self.phrase().nfkd().collect();
+        let salt: String = format!("mnemonic{}", passphrase).nfkd().collect();
+        let mut seed = [0; SEED_LEN];
+        pbkdf2_hmac::<Sha512>(phrase.as_bytes(), salt.as_bytes(), ROUNDS, &mut seed);
+        seed
+    }
+
+    /// Entropy and checksum as subdivision choices, one per two bits.
+    pub fn to_geometric(&self) -> Vec<u8> {
+        let mut bits = self.bits();
+        if bits.len() % 2 == 1 {
+            bits.push(0);
+        }
+        bits.chunks(2).map(|pair| pair[0] << 1 | pair[1]).collect()
+    }
+
+    pub fn from_geometric(choices: &[u8]) -> Result<Self, MnemonicError> {
+        if choices.iter().any(|&choice| choice > 3) {
+            return Err(MnemonicError::InvalidGeometric);
+        }
+        let entropy_bytes = (16..=32usize)
+            .step_by(4)
+            .find(|bytes| bytes * 4 + (bytes / 4).div_ceil(2) == choices.len())
+            .ok_or(MnemonicError::InvalidGeometric)?;
+        let mut bits: Vec<u8> = choices.iter().flat_map(|choice| [choice >> 1, choice & 1]).collect();
+        let checksum_bits = entropy_bytes / 4;
+        if bits.len() > entropy_bytes * 8 + checksum_bits && bits.pop() != Some(0) {
+            return Err(MnemonicError::InvalidGeometric);
+        }
+        Self::from_bits(&bits)
+    }
+
+    /// Entropy bits followed by checksum bits.
+    fn bits(&self) -> Vec<u8> {
+        let checksum = Sha256::digest(&self.entropy);
+        let bits_of = |bytes: &[u8]| bytes.iter().flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1)).collect::<Vec<_>>();
+        let mut bits = bits_of(&self.entropy);
+        bits.extend(bits_of(&checksum).into_iter().take(self.entropy.len() / 4));
+        bits
+    }
+
+    fn from_bits(bits: &[u8]) -> Result<Self, MnemonicError> {
+        let entropy_bits = bits.len() * 32 / 33;
+        let entropy: Vec<u8> = bits[..entropy_bits].chunks(8).map(|byte| byte.iter().fold(0, |acc, &bit| acc << 1 | bit)).collect();
+        let mnemonic = Self::from_entropy(&entropy)?;
+        if mnemonic.bits() != bits {
+            return Err(MnemonicError::InvalidChecksum);
+        }
+        Ok(mnemonic)
+    }
+}
+
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mnemonic({} words)", self.word_count())
+    }
+}
+
+impl Drop for Mnemonic {
+    fn drop(&mut self) {
+        self.entropy.fill(0);
+    }
+}
+
+/// Reads a phrase in any case and spacing.
+impl FromStr for Mnemonic {
+    type Err = MnemonicError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s.nfkd().collect::<String>().to_lowercase();
+        let words: Vec<&str> = normalized.split_whitespace().collect();
+        if !(12..=24).contains(&words.len()) || !words.len().is_multiple_of(3) {
+            return Err(MnemonicError::InvalidWordCount(words.len()));
+        }
+        let mut bits = Vec::with_capacity(words.len() * BITS_PER_WORD);
+        for word in words {
+            let index = wordlist().binary_search(&word).map_err(|_| MnemonicError::UnknownWord(word.to_string()))?;
+            bits.extend((0..BITS_PER_WORD).rev().map(|i| (index >> i & 1) as u8));
+        }
+        Self::from_bits(&bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn vectors() -> Value {
+        serde_json::from_str(include_str!("../../testdata/bip39_vectors.json")).unwrap()
+    }
+
+    #[test]
+    fn test_wordlist_is_the_bip39_english_list() {
+        assert_eq!(wordlist().len(), 2048);
+        assert!(wordlist().windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(hex::encode(Sha256::digest(ENGLISH)), "2f5eed53a4727b4bf8880d8f3f199efc90e58503646d9ff8eff3a2ed3b24dbda");
+    }
+
+    #[test]
+    fn test_bip39_vectors() {
+        let vectors = vectors();
+        let passphrase = vectors["passphrase"].as_str().unwrap();
+        for vector in vectors["english"].as_array().unwrap() {
+            let [entropy, phrase, seed] = [0, 1, 2].map(|i| vector[i].as_str().unwrap());
+            let mnemonic = Mnemonic::from_entropy(&hex::decode(entropy).unwrap()).unwrap();
+            assert_eq!(mnemonic.phrase(), phrase);
+            assert_eq!(phrase.parse(), Ok(mnemonic.clone()));
+            assert_eq!(hex::encode(mnemonic.to_seed(passphrase)), seed);
+            assert_eq!(Mnemonic::from_geometric(&mnemonic.to_geometric()), Ok(mnemonic));
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_phrases() {
+        let phrase = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        assert!(phrase.to_uppercase().replace(' ', "  ").parse::<Mnemonic>().is_ok());
+        assert_eq!(phrase.replace("yellow", "year").parse::<Mnemonic>(), Err(MnemonicError::InvalidChecksum));
+        assert_eq!(phrase.replace("yellow", "yelow").parse::<Mnemonic>(), Err(MnemonicError::UnknownWord("yelow".to_string())));
+        assert_eq!(phrase.replace(" yellow", "").parse::<Mnemonic>(), Err(MnemonicError::InvalidWordCount(11)));
+        assert_eq!(Mnemonic::from_entropy(&[0; 20]).map(|m| m.word_count()), Ok(15));
+        assert_eq!(Mnemonic::from_entropy(&[0; 15]), Err(MnemonicError::InvalidEntropyLength(15)));
+        assert_eq!(Mnemonic::generate(13), Err(MnemonicError::InvalidWordCount(13)));
+    }
+
+    #[test]
+    fn test_geometric_encoding() {
+        // 128 bits of entropy and a 4-bit checksum make 66 choices.
+        let mnemonic: Mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".parse().unwrap();
+        let mut choices = mnemonic.to_geometric();
+        assert_eq!(choices.len(), 66);
+        assert_eq!(choices[64..], [0, 3]);
+        assert!(choices[..64].iter().all(|&choice| choice == 0));
+
+        // 160 bits and 5 checksum bits need a padding bit, which must be zero.
+        let generated = Mnemonic::generate(15).unwrap();
+        let mut padded = generated.to_geometric();
+        assert_eq!(padded.len(), 83);
+        assert_eq!(Mnemonic::from_geometric(&padded), Ok(generated.clone()));
+        *padded.last_mut().unwrap() |= 1;
+        assert_eq!(Mnemonic::from_geometric(&padded), Err(MnemonicError::InvalidGeometric));
+
+        choices[65] = 2;
+        assert_eq!(Mnemonic::from_geometric(&choices), Err(MnemonicError::InvalidChecksum));
+        choices[65] = 4;
+        assert_eq!(Mnemonic::from_geometric(&choices), Err(MnemonicError::InvalidGeometric));
+        assert_eq!(Mnemonic::from_geometric(&choices[1..]), Err(MnemonicError::InvalidGeometric));
+    }
+
+    #[test]
+    fn test_passphrase_opens_a_different_seed() {
+        let mnemonic = Mnemonic::generate(24).unwrap();
+        assert_eq!(mnemonic.word_count(), 24);
+        assert_eq!(mnemonic.to_seed(""), mnemonic.phrase().parse::<Mnemonic>().unwrap().to_seed(""));
+        assert_ne!(mnemonic.to_seed(""), mnemonic.to_seed("TREZOR"));
+        assert_ne!(Mnemonic::generate(24).unwrap(), mnemonic);
+    }
+}
@@ -1,3 +1,8 @@
 pub mod crypto;
 pub mod hash;
-pub mod wallet;
\ No newline at end of file
+pub mod hd;
+pub mod mnemonic;
+pub mod multisig;
+pub mod pq;
+pub mod wallet;
+pub mod zk;
\ No newline at end of file
@@ -0,0 +1,267 @@
+//! M-of-N multisig accounts.
+//!
+//! A [`MultisigPolicy`] is N public keys, of any mix of algorithms, and a
+//! threshold M. Its address is tagged [`MULTISIG_TAG`] and hashes the
+//! policy the way a single-key address hashes its key, so a transaction
+//! from a multisig account carries the whole policy in its witness along
+//! with at least M signatures:
+//!
+//! ```text
+//! MULTISIG_TAG | M: u8 | N: u8 | N x (tag | public key) | count: u8 | count x (key index: u8 | signature)
+//! ```
+//!
+//! Keys are sorted, so the same keys and threshold give the same address
+//! whatever order co-owners list them in. Signatures are in increasing key
+//! order, at most one per key, and every one present must verify.
+
+use crate::crypto::crypto::{Address, CryptoError, PublicKey, Signature, SignatureAlgorithm, Witness, ADDRESS_LEN};
+use sha2::{Digest, Sha256};
+
+/// First byte of a multisig address or witness, clear of the signature
+/// algorithm tags.
+pub const MULTISIG_TAG: u8 = 0x80;
+
+/// Most keys a policy may list.
+pub const MAX_COSIGNERS: usize = 16;
+
+/// The keys of a multisig account and how many of them must sign.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigPolicy {
+    threshold: usize,
+    keys: Vec<PublicKey>,
+}
+
+impl MultisigPolicy {
+    pub fn new(threshold: usize, mut keys: Vec<PublicKey>) -> Result<Self, CryptoError> {
+        if threshold == 0 || threshold > keys.len() || keys.len() > MAX_COSIGNERS {
+            return Err(CryptoError::InvalidPolicy);
+        }
+        for key in &keys {
+            if key.bytes.len() != key.algorithm.public_key_len() {
+                return Err(CryptoError::InvalidKey);
+            }
+        }
+        keys.sort_by(|a, b| (a.algorithm.as_u8(), &a.bytes).cmp(&(b.algorithm.as_u8(), &b.bytes)));
+        if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(CryptoError::InvalidPolicy);
+        }
+        Ok(MultisigPolicy { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The keys, in their canonical order.
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    pub fn index_of(&self, key: &PublicKey) -> Option<usize> {
+        self.keys.iter().position(|k| k == key)
+    }
+
+    pub fn address(&self) -> Address {
+        let encoded = self.encode();
+        let mut hasher = Sha256::new();
+        hasher.update([MULTISIG_TAG]);
+        hasher.update(&encoded);
+        let digest = hasher.finalize();
+        let mut bytes = [0; ADDRESS_LEN];
+        bytes[0] = MULTISIG_TAG;
+        bytes[1..].copy_from_slice(&digest[..ADDRESS_LEN - 1]);
+        Address::from_bytes(bytes)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![self.threshold as u8, self.keys.len() as u8];
+        for key in &self.keys {
+            out.push(key.algorithm.as_u8());
+            out.extend_from_slice(&key.bytes);
+        }
+        out
+    }
+
+    /// Parses a canonical encoding from the front of `bytes`, returning the
+    /// rest.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, &[u8]), CryptoError> {
+        let [threshold, count, rest @ ..] = bytes else {
+            return Err(CryptoError::InvalidPolicy);
+        };
+        let mut rest = rest;
+        let mut keys = Vec::with_capacity(*count as usize);
+        for _ in 0..*count {
+            let (&tag, after) = rest.split_first().ok_or(CryptoError::InvalidPolicy)?;
+            let algorithm = SignatureAlgorithm::from_u8(tag).ok_or(CryptoError::UnknownAlgorithm(tag))?;
+            if after.len() < algorithm.public_key_len() {
+                return Err(CryptoError::InvalidPolicy);
+            }
+            let (key, after) = after.split_at(algorithm.public_key_len());
+            keys.push(PublicKey { algorithm, bytes: key.to_vec() });
+            rest = after;
+        }
+        let policy = Self::new(*threshold as usize, keys.clone())?;
+        // Only the sorted order is canonical.
+        if policy.keys != keys {
+            return Err(CryptoError::InvalidPolicy);
+        }
+        Ok((policy, rest))
+    }
+}
+
+/// A multisig account's policy with signatures by its keys, which is what
+/// a transaction from the account carries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigWitness {
+    pub policy: MultisigPolicy,
+    /// Signatures by key index, in increasing order.
+    pub signatures: Vec<(usize, Signature)>,
+}
+
+impl MultisigWitness {
+    /// Checks that the policy hashes to `signer` and that at least its
+    /// threshold of keys, and every signature present, signed `message`.
+    pub fn verify(&self, signer: &Address, message: &[u8]) -> Result<(), CryptoError> {
+        if self.policy.address() != *signer {
+            return Err(CryptoError::WrongSigner);
+        }
+        if self.signatures.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(CryptoError::InvalidSignature);
+        }
+        for (index, signature) in &self.signatures {
+            self.policy.keys.get(*index).ok_or(CryptoError::InvalidSignature)?.verify(message, signature)?;
+        }
+        if self.signatures.len() < self.policy.threshold {
+            return Err(CryptoError::ThresholdNotMet { signatures: self.signatures.len(), threshold: self.policy.threshold });
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![MULTISIG_TAG];
+        out.extend_from_slice(&self.policy.encode());
+        out.push(self.signatures.len() as u8);
+        for (index, signature) in &self.signatures {
+            out.push(*index as u8);
+            out.extend_from_slice(&signature.bytes);
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, CryptoError> {
+        let [MULTISIG_TAG, rest @ ..] = bytes else {
+            return Err(CryptoError::InvalidSignature);
+        };
+        let (policy, rest) = MultisigPolicy::decode(rest)?;
+        let (&count, mut rest) = rest.split_first().ok_or(CryptoError::InvalidSignature)?;
+        let mut signatures = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (&index, after) = rest.split_first().ok_or(CryptoError::InvalidSignature)?;
+            let algorithm = policy.keys.get(index as usize).ok_or(CryptoError::InvalidSignature)?.algorithm;
+            if after.len() < algorithm.signature_len() {
+                return Err(CryptoError::InvalidSignature);
+            }
+            let (signature, after) = after.split_at(algorithm.signature_len());
+            signatures.push((index as usize, Signature { algorithm, bytes: signature.to_vec() }));
+            rest = after;
+        }
+        if !rest.is_empty() {
+            return Err(CryptoError::InvalidSignature);
+        }
+        Ok(MultisigWitness { policy, signatures })
+    }
+}
+
+pub fn is_multisig(address: &Address) -> bool {
+    address.as_bytes()[0] == MULTISIG_TAG
+}
+
+/// Checks that `witness` authorises `signer` to sign `message`, as a
+/// [`Witness`] for a single-key address or a [`MultisigWitness`] for a
+/// multisig one.
+pub fn verify_witness(signer: &Address, witness: &[u8], message: &[u8]) -> Result<(), CryptoError> {
+    if is_multisig(signer) {
+        MultisigWitness::decode(witness)?.verify(signer, message)
+    } else {
+        Witness::decode(witness)?.verify(signer, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::crypto::{Ed25519, KeyPair, Secp256k1, SignatureScheme};
+
+    fn cosigners() -> Vec<KeyPair> {
+        vec![Ed25519.keypair_from_seed(&[1; 32]), Secp256k1.keypair_from_seed(&[2; 32]), Ed25519.keypair_from_seed(&[3; 32])]
+    }
+
+    fn policy(threshold: usize) -> MultisigPolicy {
+        MultisigPolicy::new(threshold, cosigners().into_iter().map(|k| k.public.clone()).collect()).unwrap()
+    }
+
+    fn witness(signers: &[usize]) -> MultisigWitness {
+        let policy = policy(2);
+        let mut signatures: Vec<_> = signers
+            .iter()
+            .map(|&signer| {
+                let keypair = &cosigners()[signer];
+                (policy.index_of(&keypair.public).unwrap(), keypair.secret.sign(b"tx").unwrap())
+            })
+            .collect();
+        signatures.sort_by_key(|(index, _)| *index);
+        MultisigWitness { policy, signatures }
+    }
+
+    #[test]
+    fn test_policy_address_ignores_key_order() {
+        let mut keys: Vec<_> = cosigners().into_iter().map(|k| k.public.clone()).collect();
+        keys.reverse();
+        let address = policy(2).address();
+        assert_eq!(MultisigPolicy::new(2, keys.clone()).unwrap().address(), address);
+        assert!(is_multisig(&address));
+        assert_eq!(address.algorithm(), None);
+        assert_ne!(policy(3).address(), address);
+        assert_eq!(MultisigPolicy::decode(&policy(2).encode()), Ok((policy(2), &[][..])));
+
+        assert_eq!(MultisigPolicy::new(0, keys.clone()), Err(CryptoError::InvalidPolicy));
+        assert_eq!(MultisigPolicy::new(4, keys.clone()), Err(CryptoError::InvalidPolicy));
+        keys.push(keys[0].clone());
+        assert_eq!(MultisigPolicy::new(2, keys), Err(CryptoError::InvalidPolicy));
+    }
+
+    #[test]
+    fn test_threshold_of_signatures() {
+        let address = policy(2).address();
+        for signers in [&[0, 1][..], &[1, 2], &[0, 1, 2]] {
+            let witness = witness(signers);
+            assert_eq!(verify_witness(&address, &witness.encode(), b"tx"), Ok(()));
+            assert_eq!(MultisigWitness::decode(&witness.encode()), Ok(witness));
+        }
+        assert_eq!(witness(&[2]).verify(&address, b"tx"), Err(CryptoError::ThresholdNotMet { signatures: 1, threshold: 2 }));
+        assert_eq!(witness(&[0, 1]).verify(&address, b"other"), Err(CryptoError::VerificationFailed));
+        assert_eq!(witness(&[0, 1]).verify(&policy(3).address(), b"tx"), Err(CryptoError::WrongSigner));
+    }
+
+    #[test]
+    fn test_rejects_repeated_and_forged_signatures() {
+        let address = policy(2).address();
+        let mut repeated = witness(&[0]);
+        repeated.signatures.push(repeated.signatures[0].clone());
+        assert_eq!(repeated.verify(&address, b"tx"), Err(CryptoError::InvalidSignature));
+
+        // A signature by an outsider, claimed for a cosigner's slot.
+        let mut forged = witness(&[0]);
+        let mallory = Ed25519.keypair_from_seed(&[9; 32]);
+        let slot = (0..3).find(|&i| i != forged.signatures[0].0 && policy(2).keys()[i].algorithm == mallory.public.algorithm).unwrap();
+        forged.signatures.push((slot, mallory.secret.sign(b"tx").unwrap()));
+        forged.signatures.sort_by_key(|(index, _)| *index);
+        assert_eq!(forged.verify(&address, b"tx"), Err(CryptoError::VerificationFailed));
+
+        let single = Witness::sign(&cosigners()[0], b"tx").unwrap().encode();
+        assert_eq!(verify_witness(&address, &single, b"tx"), Err(CryptoError::InvalidSignature));
+        let mut trailing = witness(&[0, 1]).encode();
+        trailing.push(0);
+        assert_eq!(MultisigWitness::decode(&trailing), Err(CryptoError::InvalidSignature));
+    }
+}
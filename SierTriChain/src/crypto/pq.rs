@@ -0,0 +1,165 @@
+//! Post-quantum schemes, backed by aws-lc: ML-DSA-65 signatures (FIPS 204)
+//! and ML-KEM-768 key encapsulation (FIPS 203).
+//!
+//! An ML-DSA secret key is stored as its 32-byte seed, like an Ed25519 one,
+//! and expanded whenever it signs. Signing is randomized, so the same key
+//! and message give different signatures that all verify. ML-KEM keys are
+//! generated at random and kept in their FIPS 203 encodings.
+//!
+//! Known answers for both live in `testdata/pq_kat.json`.
+
+use crate::crypto::crypto::{
+    CryptoError, DecapsulationKey, EncapsulationKey, KemAlgorithm, KemKeyPair, KeyEncapsulation, KeyPair, PublicKey,
+    SecretKey, Signature, SignatureAlgorithm, SignatureScheme, SEED_LEN, SHARED_SECRET_LEN,
+};
+use aws_lc_rs::kem;
+use aws_lc_rs::signature::{self, KeyPair as _, PqdsaKeyPair, UnparsedPublicKey};
+
+/// Encoded ML-KEM-768 ciphertext length.
+const ML_KEM_768_CIPHERTEXT_LEN: usize = 1088;
+
+/// ML-DSA-65, the post-quantum signature scheme.
+pub struct MlDsa65;
+
+impl MlDsa65 {
+    fn expand(seed: &[u8]) -> Result<PqdsaKeyPair, CryptoError> {
+        PqdsaKeyPair::from_seed(&signature::ML_DSA_65_SIGNING, seed).map_err(|_| CryptoError::InvalidKey)
+    }
+}
+
+impl SignatureScheme for MlDsa65 {
+    fn algorithm(&self) -> SignatureAlgorithm {
+        SignatureAlgorithm::MlDsa65
+    }
+
+    fn keypair_from_seed(&self, seed: &[u8; SEED_LEN]) -> KeyPair {
+        let expanded = Self::expand(seed).expect("every 32-byte seed is an ML-DSA key");
+        KeyPair {
+            public: PublicKey { algorithm: SignatureAlgorithm::MlDsa65, bytes: expanded.public_key().as_ref().to_vec() },
+            secret: SecretKey { algorithm: SignatureAlgorithm::MlDsa65, bytes: seed.to_vec() },
+        }
+    }
+
+    fn sign(&self, secret: &SecretKey, message: &[u8]) -> Result<Signature, CryptoError> {
+        let expanded = Self::expand(&secret.bytes)?;
+        let mut bytes = vec![0; SignatureAlgorithm::MlDsa65.signature_len()];
+        expanded.sign(message, &mut bytes).map_err(|_| CryptoError::InvalidKey)?;
+        Ok(Signature { algorithm: SignatureAlgorithm::MlDsa65, bytes })
+    }
+
+    fn verify(&self, public: &PublicKey, message: &[u8], signature: &Signature) -> Result<(), CryptoError> {
+        if public.bytes.len() != SignatureAlgorithm::MlDsa65.public_key_len() {
+            return Err(CryptoError::InvalidKey);
+        }
+        if signature.bytes.len() != SignatureAlgorithm::MlDsa65.signature_len() {
+            return Err(CryptoError::InvalidSignature);
+        }
+        UnparsedPublicKey::new(&signature::ML_DSA_65, &public.bytes)
+            .verify(message, &signature.bytes)
+            .map_err(|_| CryptoError::VerificationFailed)
+    }
+}
+
+/// ML-KEM-768, the post-quantum key encapsulation.
+pub struct MlKem768;
+
+impl KeyEncapsulation for MlKem768 {
+    fn algorithm(&self) -> KemAlgorithm {
+        KemAlgorithm::MlKem768
+    }
+
+    fn generate_keypair(&self) -> Result<KemKeyPair, CryptoError> {
+        let secret = kem::DecapsulationKey::generate(&kem::ML_KEM_768).map_err(|_| CryptoError::KeyGeneration)?;
+        let public = secret.encapsulation_key().map_err(|_| CryptoError::KeyGeneration)?;
+        let public = public.key_bytes().map_err(|_| CryptoError::KeyGeneration)?;
+        let secret = secret.key_bytes().map_err(|_| CryptoError::KeyGeneration)?;
+        Ok(KemKeyPair {
+            public: EncapsulationKey { algorithm: KemAlgorithm::MlKem768, bytes: public.as_ref().to_vec() },
+            secret: DecapsulationKey { algorithm: KemAlgorithm::MlKem768, bytes: secret.as_ref().to_vec() },
+        })
+    }
+
+    fn encapsulate(&self, public: &EncapsulationKey) -> Result<(Vec<u8>, [u8; SHARED_SECRET_LEN]), CryptoError> {
+        let key = kem::EncapsulationKey::new(&kem::ML_KEM_768, &public.bytes).map_err(|_| CryptoError::InvalidKey)?;
+        let (ciphertext, secret) = key.encapsulate().map_err(|_| CryptoError::InvalidKey)?;
+        let secret = secret.as_ref().try_into().expect("ML-KEM shares 32 bytes");
+        Ok((ciphertext.as_ref().to_vec(), secret))
+    }
+
+    fn decapsulate(&self, secret: &DecapsulationKey, ciphertext: &[u8]) -> Result<[u8; SHARED_SECRET_LEN], CryptoError> {
+        let key = kem::DecapsulationKey::new(&kem::ML_KEM_768, &secret.bytes).map_err(|_| CryptoError::InvalidKey)?;
+        if ciphertext.len() != ML_KEM_768_CIPHERTEXT_LEN {
+            return Err(CryptoError::InvalidCiphertext);
+        }
+        let shared = key.decapsulate(kem::Ciphertext::from(ciphertext)).map_err(|_| CryptoError::InvalidCiphertext)?;
+        Ok(shared.as_ref().try_into().expect("ML-KEM shares 32 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::crypto::{Address, Witness};
+    use serde_json::Value;
+
+    fn known_answers() -> Value {
+        serde_json::from_str(include_str!("../../testdata/pq_kat.json")).unwrap()
+    }
+
+    fn bytes(vector: &Value, field: &str) -> Vec<u8> {
+        hex::decode(vector[field].as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ml_dsa_65_known_answer() {
+        let vector = &known_answers()["ml_dsa_65"];
+        let seed: [u8; SEED_LEN] = bytes(vector, "seed").try_into().unwrap();
+        let keypair = MlDsa65.keypair_from_seed(&seed);
+        assert_eq!(keypair.public.bytes, bytes(vector, "public_key"));
+
+        let message = bytes(vector, "message");
+        let mut signature = Signature { algorithm: SignatureAlgorithm::MlDsa65, bytes: bytes(vector, "signature") };
+        assert_eq!(keypair.public.verify(&message, &signature), Ok(()));
+        signature.bytes[100] ^= 1;
+        assert_eq!(keypair.public.verify(&message, &signature), Err(CryptoError::VerificationFailed));
+    }
+
+    #[test]
+    fn test_ml_dsa_65_signs_and_addresses() {
+        let keypair = MlDsa65.keypair_from_seed(&[4; SEED_LEN]);
+        let signature = keypair.secret.sign(b"triangle").unwrap();
+        assert_eq!(keypair.public.verify(b"triangle", &signature), Ok(()));
+        assert_eq!(keypair.public.verify(b"triangles", &signature), Err(CryptoError::VerificationFailed));
+
+        // The tag keeps an ML-DSA account apart from an Ed25519 one.
+        let address = keypair.address();
+        assert_eq!(address.algorithm(), Some(SignatureAlgorithm::MlDsa65));
+        assert!(address.to_string().starts_with("01"));
+        let witness = Witness::sign(&keypair, b"block").unwrap();
+        assert_eq!(witness.encode().len(), 1 + 1952 + 3309);
+        assert_eq!(Witness::decode(&witness.encode()).unwrap().verify(&address, b"block"), Ok(()));
+        let ed25519 = crate::crypto::crypto::Ed25519.keypair_from_seed(&[4; SEED_LEN]);
+        assert_ne!(Address::from_public_key(&ed25519.public), address);
+        assert_eq!(ed25519.public.verify(b"triangle", &signature), Err(CryptoError::AlgorithmMismatch));
+    }
+
+    #[test]
+    fn test_ml_kem_768_known_answer() {
+        let vector = &known_answers()["ml_kem_768"];
+        let secret = DecapsulationKey { algorithm: KemAlgorithm::MlKem768, bytes: bytes(vector, "decapsulation_key") };
+        let ciphertext = bytes(vector, "ciphertext");
+        assert_eq!(MlKem768.decapsulate(&secret, &ciphertext).unwrap().to_vec(), bytes(vector, "shared_secret"));
+        // FIPS 203 embeds the encapsulation key in the decapsulation key.
+        assert_eq!(secret.bytes[1152..2336], bytes(vector, "encapsulation_key")[..]);
+        assert_eq!(MlKem768.decapsulate(&secret, &ciphertext[1..]), Err(CryptoError::InvalidCiphertext));
+    }
+
+    #[test]
+    fn test_ml_kem_768_agrees_secrets() {
+        let keypair = MlKem768.generate_keypair().unwrap();
+        let (ciphertext, shared) = MlKem768.encapsulate(&keypair.public).unwrap();
+        assert_eq!(MlKem768.decapsulate(&keypair.secret, &ciphertext), Ok(shared));
+        let other = MlKem768.generate_keypair().unwrap();
+        assert_ne!(MlKem768.decapsulate(&other.secret, &ciphertext), Ok(shared));
+    }
+}
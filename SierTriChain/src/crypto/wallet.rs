@@ -1,8 +1,366 @@
 #[cfg(test)]
 mod tests {
+	use super::*;
+	use crate::geometry::lattice::LatticeTriangle;
+
 	#[test]
 	fn test_crypto_wallet_basic() {
 		assert_eq!(2 + 2, 4);
 	}
+
+	// Cheap enough for tests; real wallets use the default.
+	const FAST: KdfParams = KdfParams { memory_kib: 64, iterations: 1, parallelism: 1 };
+
+	fn wallet() -> Wallet {
+		let mut wallet = Wallet::from_seed("alice".to_string(), &[9; 32]).unwrap();
+		let home = FractalAddress::from_digits(&[2, 1]).unwrap();
+		wallet.derive_hd_key(&home).unwrap();
+		wallet.derive_hd_key(&FractalAddress::from_digits(&[0]).unwrap()).unwrap();
+		wallet.owned_territories.insert(home, Triangle::from(LatticeTriangle::at(&home)));
+		wallet.set_label(&home, "home");
+		wallet
+	}
+
+	fn assert_same(a: &Wallet, b: &Wallet) {
+		assert_eq!(a.owner, b.owner);
+		assert_eq!(a.seed(), b.seed());
+		assert_eq!(a.hd_keys, b.hd_keys);
+		assert_eq!(a.owned_territories, b.owned_territories);
+		assert_eq!(a.labels, b.labels);
+	}
+
+	#[test]
+	fn test_lock_and_unlock() {
+		let file = WalletFile::lock_with(&wallet(), "correct horse", FAST).unwrap();
+		assert_same(&file.unlock("correct horse").unwrap(), &wallet());
+		assert!(matches!(file.unlock("wrong horse"), Err(WalletFileError::WrongPassword)));
+
+		// The seed and labels never appear in the clear, and every lock is salted afresh.
+		let encoded = file.encode();
+		assert!(!encoded.windows(4).any(|w| w == b"home"));
+		assert!(!encoded.windows(32).any(|w| w == [9; 32]));
+		assert_ne!(WalletFile::lock_with(&wallet(), "correct horse", FAST).unwrap().encode(), encoded);
+	}
+
+	#[test]
+	fn test_save_and_load() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("wallet.dat");
+		WalletFile::lock_with(&wallet(), "pw", FAST).unwrap().save(&path).unwrap();
+		let loaded = WalletFile::load(&path).unwrap();
+		assert_eq!(loaded.kdf, FAST);
+		let mut unlocked = loaded.unlock("pw").unwrap();
+		assert_same(&unlocked, &wallet());
+
+		// Unlocked keys are the wallet's keys, derived again from the seed.
+		let home = FractalAddress::from_digits(&[2, 1]).unwrap();
+		assert_eq!(unlocked.derive_hd_key(&home).unwrap().public, wallet().hd_keys[&home]);
+		assert!(matches!(WalletFile::load(dir.path().join("missing")), Err(WalletFileError::Io(_))));
+	}
+
+	#[test]
+	fn test_change_password() {
+		let mut file = WalletFile::lock_with(&wallet(), "old", FAST).unwrap();
+		assert!(matches!(file.change_password("wrong", "new"), Err(WalletFileError::WrongPassword)));
+		file.change_password("old", "new").unwrap();
+		assert!(matches!(file.unlock("old"), Err(WalletFileError::WrongPassword)));
+		assert_same(&file.unlock("new").unwrap(), &wallet());
+	}
+
+	#[test]
+	fn test_detects_corruption() {
+		let encoded = WalletFile::lock_with(&wallet(), "pw", FAST).unwrap().encode();
+		for at in [0, 4, 10, HEADER_LEN + 3, encoded.len() - 1] {
+			let mut corrupted = encoded.clone();
+			corrupted[at] ^= 1;
+			let expected = match at {
+				0 => "NotAWallet",
+				4 => "UnsupportedVersion",
+				_ => "Corrupt",
+			};
+			let error = WalletFile::decode(&corrupted).err().unwrap();
+			assert!(format!("{:?}", error).starts_with(expected), "byte {}: {:?}", at, error);
+		}
+		assert!(matches!(WalletFile::decode(&encoded[..HEADER_LEN]), Err(WalletFileError::Corrupt)));
+	}
 }
 // Moved from src/wallet.rs
+
+// Encrypted wallet files.
+//
+// A `Wallet` is locked into a `WalletFile` under a password: Argon2id
+// stretches the password and a random salt into a key, and
+// ChaCha20-Poly1305 seals the seed, derived accounts, owned territories and
+// labels under it with the header as associated data. Unlocking derives the
+// account keys again from the seed, so no private key is stored but the seed.
+//
+// Layout, integers big-endian:
+//
+//   magic "STCW" | version | Argon2 memory KiB (4) | iterations (4) |
+//   parallelism (4) | salt (16) | nonce (12) | ciphertext and tag |
+//   SHA-256 of everything before it (32)
+//
+// The checksum tells a damaged file apart from a wrong password, which
+// only the AEAD tag can catch.
+
+use crate::geometry::subdivision::FractalAddress;
+use crate::geometry::triangle::Triangle;
+use crate::wallet::geo_wallet::{Wallet, WalletError};
+use argon2::{Algorithm, Argon2, Params, Version};
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"STCW";
+
+/// Format version this code writes and reads.
+pub const WALLET_VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const CHECKSUM_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN + NONCE_LEN;
+
+/// Files asking for more memory than this are refused rather than tried.
+const MAX_MEMORY_KIB: u32 = 4 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum WalletFileError {
+	Io(io::Error),
+	/// The file does not start with the wallet magic.
+	NotAWallet,
+	/// Written by a newer version of the format.
+	UnsupportedVersion(u8),
+	/// The checksum does not match, or the file is truncated.
+	Corrupt,
+	/// The password does not open the file.
+	WrongPassword,
+	/// Key derivation parameters out of range.
+	InvalidParameters,
+	/// The file decrypted but does not hold a wallet.
+	Contents(String),
+}
+
+impl fmt::Display for WalletFileError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			WalletFileError::Io(e) => write!(f, "wallet file I/O error: {}", e),
+			WalletFileError::NotAWallet => write!(f, "not a wallet file"),
+			WalletFileError::UnsupportedVersion(version) => write!(f, "wallet file version {} is not supported", version),
+			WalletFileError::Corrupt => write!(f, "wallet file is corrupt"),
+			WalletFileError::WrongPassword => write!(f, "wrong wallet password"),
+			WalletFileError::InvalidParameters => write!(f, "wallet key derivation parameters out of range"),
+			WalletFileError::Contents(e) => write!(f, "wallet contents: {}", e),
+		}
+	}
+}
+
+impl std::error::Error for WalletFileError {}
+
+impl From<io::Error> for WalletFileError {
+	fn from(e: io::Error) -> Self {
+		WalletFileError::Io(e)
+	}
+}
+
+impl From<WalletError> for WalletFileError {
+	fn from(e: WalletError) -> Self {
+		WalletFileError::Contents(e.to_string())
+	}
+}
+
+/// Argon2id cost of turning a password into a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+	pub memory_kib: u32,
+	pub iterations: u32,
+	pub parallelism: u32,
+}
+
+/// 19 MiB and two passes, the OWASP recommendation for Argon2id.
+impl Default for KdfParams {
+	fn default() -> Self {
+		KdfParams { memory_kib: 19 * 1024, iterations: 2, parallelism: 1 }
+	}
+}
+
+impl KdfParams {
+	fn derive_key(&self, password: &str, salt: &[u8]) -> Result<[u8; KEY_LEN], WalletFileError> {
+		if self.memory_kib > MAX_MEMORY_KIB {
+			return Err(WalletFileError::InvalidParameters);
+		}
+		let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LEN)).map_err(|_| WalletFileError::InvalidParameters)?;
+		let mut key = [0; KEY_LEN];
+		Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+			.hash_password_into(password.as_bytes(), salt, &mut key)
+			.map_err(|_| WalletFileError::InvalidParameters)?;
+		Ok(key)
+	}
+}
+
+/// What the ciphertext holds. Keys are derived again on unlock.
+#[derive(Serialize, Deserialize)]
+struct Contents {
+	owner: String,
+	seed: Vec<u8>,
+	accounts: Vec<FractalAddress>,
+	territories: BTreeMap<FractalAddress, Triangle>,
+	labels: BTreeMap<FractalAddress, String>,
+}
+
+/// A locked wallet, as stored on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalletFile {
+	pub kdf: KdfParams,
+	salt: [u8; SALT_LEN],
+	nonce: [u8; NONCE_LEN],
+	ciphertext: Vec<u8>,
+}
+
+impl WalletFile {
+	/// Locks `wallet` under `password` at the default cost.
+	pub fn lock(wallet: &Wallet, password: &str) -> Result<Self, WalletFileError> {
+		Self::lock_with(wallet, password, KdfParams::default())
+	}
+
+	pub fn lock_with(wallet: &Wallet, password: &str, kdf: KdfParams) -> Result<Self, WalletFileError> {
+		let mut accounts: Vec<FractalAddress> = wallet.hd_keys.keys().copied().collect();
+		accounts.sort();
+		let contents = Contents {
+			owner: wallet.owner.clone(),
+			seed: wallet.seed().to_vec(),
+			accounts,
+			territories: wallet.owned_territories.clone(),
+			labels: wallet.labels.clone(),
+		};
+		let mut plaintext = serde_json::to_vec(&contents).map_err(|e| WalletFileError::Contents(e.to_string()))?;
+		let Contents { mut seed, .. } = contents;
+		seed.fill(0);
+		let file = Self::seal(&plaintext, password, kdf);
+		plaintext.fill(0);
+		file
+	}
+
+	/// Unlocks the wallet, with the account keys it had when locked.
+	pub fn unlock(&self, password: &str) -> Result<Wallet, WalletFileError> {
+		let mut plaintext = self.open(password)?;
+		let contents = serde_json::from_slice::<Contents>(&plaintext);
+		plaintext.fill(0);
+		let Contents { owner, mut seed, accounts, territories, labels } = contents.map_err(|e| WalletFileError::Contents(e.to_string()))?;
+		let wallet = Wallet::from_seed(owner, &seed).map_err(WalletError::from);
+		seed.fill(0);
+		let mut wallet = wallet?;
+		for account in &accounts {
+			wallet.derive_hd_key(account).map_err(WalletError::from)?;
+		}
+		wallet.owned_territories = territories;
+		wallet.labels = labels;
+		Ok(wallet)
+	}
+
+	/// Locks the same contents under a new password, with a fresh salt.
+	pub fn change_password(&mut self, old: &str, new: &str) -> Result<(), WalletFileError> {
+		let mut plaintext = self.open(old)?;
+		let file = Self::seal(&plaintext, new, self.kdf);
+		plaintext.fill(0);
+		*self = file?;
+		Ok(())
+	}
+
+	pub fn encode(&self) -> Vec<u8> {
+		let mut bytes = self.header();
+		bytes.extend_from_slice(&self.ciphertext);
+		let checksum = Sha256::digest(&bytes);
+		bytes.extend_from_slice(&checksum);
+		bytes
+	}
+
+	pub fn decode(bytes: &[u8]) -> Result<Self, WalletFileError> {
+		if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+			return Err(WalletFileError::NotAWallet);
+		}
+		if bytes[MAGIC.len()] != WALLET_VERSION {
+			return Err(WalletFileError::UnsupportedVersion(bytes[MAGIC.len()]));
+		}
+		if bytes.len() < HEADER_LEN + TAG_LEN + CHECKSUM_LEN {
+			return Err(WalletFileError::Corrupt);
+		}
+		let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+		if Sha256::digest(body)[..] != *checksum {
+			return Err(WalletFileError::Corrupt);
+		}
+		let word = |at: usize| u32::from_be_bytes(body[at..at + 4].try_into().expect("4 bytes"));
+		let at = MAGIC.len() + 1;
+		Ok(WalletFile {
+			kdf: KdfParams { memory_kib: word(at), iterations: word(at + 4), parallelism: word(at + 8) },
+			salt: body[at + 12..at + 12 + SALT_LEN].try_into().expect("salt"),
+			nonce: body[at + 12 + SALT_LEN..HEADER_LEN].try_into().expect("nonce"),
+			ciphertext: body[HEADER_LEN..].to_vec(),
+		})
+	}
+
+	/// Writes the file in full or not at all: to a temporary file first,
+	/// then renamed over `path`.
+	pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WalletFileError> {
+		let path = path.as_ref();
+		let temporary = path.with_extension("tmp");
+		let mut file = File::create(&temporary)?;
+		file.write_all(&self.encode())?;
+		file.sync_all()?;
+		fs::rename(&temporary, path)?;
+		Ok(())
+	}
+
+	pub fn load(path: impl AsRef<Path>) -> Result<Self, WalletFileError> {
+		Self::decode(&fs::read(path)?)
+	}
+
+	fn seal(plaintext: &[u8], password: &str, kdf: KdfParams) -> Result<Self, WalletFileError> {
+		let mut file = WalletFile { kdf, salt: [0; SALT_LEN], nonce: [0; NONCE_LEN], ciphertext: plaintext.to_vec() };
+		OsRng.fill_bytes(&mut file.salt);
+		OsRng.fill_bytes(&mut file.nonce);
+		let key = file.cipher(password)?;
+		let header = file.header();
+		key.seal_in_place_append_tag(Nonce::assume_unique_for_key(file.nonce), Aad::from(&header), &mut file.ciphertext)
+			.map_err(|_| WalletFileError::Contents("encryption failed".to_string()))?;
+		Ok(file)
+	}
+
+	fn open(&self, password: &str) -> Result<Vec<u8>, WalletFileError> {
+		let key = self.cipher(password)?;
+		let mut plaintext = self.ciphertext.clone();
+		let len = key
+			.open_in_place(Nonce::assume_unique_for_key(self.nonce), Aad::from(&self.header()), &mut plaintext)
+			.map_err(|_| WalletFileError::WrongPassword)?
+			.len();
+		plaintext.truncate(len);
+		Ok(plaintext)
+	}
+
+	fn cipher(&self, password: &str) -> Result<LessSafeKey, WalletFileError> {
+		let mut key = self.kdf.derive_key(password, &self.salt)?;
+		let cipher = UnboundKey::new(&CHACHA20_POLY1305, &key).map(LessSafeKey::new);
+		key.fill(0);
+		cipher.map_err(|_| WalletFileError::InvalidParameters)
+	}
+
+	fn header(&self) -> Vec<u8> {
+		let mut header = Vec::with_capacity(HEADER_LEN);
+		header.extend_from_slice(MAGIC);
+		header.push(WALLET_VERSION);
+		for word in [self.kdf.memory_kib, self.kdf.iterations, self.kdf.parallelism] {
+			header.extend_from_slice(&word.to_be_bytes());
+		}
+		header.extend_from_slice(&self.salt);
+		header.extend_from_slice(&self.nonce);
+		header
+	}
+}
@@ -0,0 +1,715 @@
+//! Zero-knowledge proofs about a territory that do not reveal which one.
+//!
+//! A holder commits to a territory's fractal address with Pedersen
+//! commitments on secp256k1: one to its depth and one to the digit at every
+//! level down to [`MAX_DEPTH`], with digits past the depth committed as
+//! zero, so the commitment does not give away the depth either. The
+//! triangle is the lattice triangle at the address, so committing to the
+//! address commits to it. The commitment carries a proof that the depth is
+//! at most [`MAX_DEPTH`].
+//!
+//! A territory claim carries the commitment and an [`AddressProof`] that it
+//! opens to the claimed address, so the state can record it against the
+//! claim. Nothing else about the opening is revealed.
+//!
+//! From the opening the holder can then prove a [`Statement`] about the
+//! committed territory and nothing else:
+//!
+//! - [`Statement::InsideRegion`]: the address is inside a public region at
+//!   a minimum depth. The region's digits are shown equal to the committed
+//!   ones and the depth bound is a range proof.
+//! - [`Statement::AreaAbove`]: the triangle's area exceeds a bound. Area
+//!   quarters with each level, so this is an upper bound on the depth. The
+//!   area is taken as the genesis triangle's over 4^depth, which lattice
+//!   triangles match up to decimal rounding.
+//!
+//! Range proofs decompose a committed value into eight committed bits, each
+//! with an OR-proof that it is 0 or 1. Every proof is made non-interactive
+//! with a SHA-256 transcript over the commitment, the statement and a
+//! caller-chosen context, such as the sender of the transaction carrying
+//! the proof, so a proof cannot be replayed elsewhere. The second generator
+//! is hashed to the curve, so nobody knows its discrete logarithm, and no
+//! trusted setup is involved.
+
+use crate::geometry::hierarchy::{FractalAddress, MAX_DEPTH};
+use crate::geometry::subdivision::triangle_at;
+use crate::geometry::triangle::{genesis_triangle, Triangle};
+use k256::elliptic_curve::group::GroupEncoding;
+use k256::elliptic_curve::ops::Reduce;
+use k256::elliptic_curve::{Field, PrimeField};
+use k256::{AffinePoint, CompressedPoint, FieldBytes, ProjectivePoint, Scalar, U256};
+use rand_core::OsRng;
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Bits in a range proof, enough for any difference of two depths.
+const RANGE_BITS: usize = 8;
+const POINT_LEN: usize = 33;
+const SCALAR_LEN: usize = 32;
+const BIT_PROOF_LEN: usize = POINT_LEN + 4 * SCALAR_LEN;
+const RANGE_PROOF_LEN: usize = RANGE_BITS * BIT_PROOF_LEN;
+
+/// Encoded length of a [`TerritoryCommitment`].
+pub const COMMITMENT_LEN: usize = (1 + MAX_DEPTH) * POINT_LEN + 2 * RANGE_PROOF_LEN;
+/// Encoded length of an [`AddressProof`].
+pub const ADDRESS_PROOF_LEN: usize = 2 * SCALAR_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZkError {
+    /// The triangle is not the lattice triangle at the address.
+    TriangleMismatch,
+    /// The statement is not true of the committed territory, so it cannot
+    /// be proved.
+    FalseStatement,
+    InvalidProof,
+    Malformed,
+}
+
+impl fmt::Display for ZkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZkError::TriangleMismatch => write!(f, "triangle is not the one at the address"),
+            ZkError::FalseStatement => write!(f, "statement does not hold for the territory"),
+            ZkError::InvalidProof => write!(f, "proof does not verify"),
+            ZkError::Malformed => write!(f, "malformed commitment or proof"),
+        }
+    }
+}
+
+impl std::error::Error for ZkError {}
+
+/// The second Pedersen generator: the first point whose x coordinate is a
+/// hash of the domain and a counter.
+fn h() -> ProjectivePoint {
+    static H: OnceLock<ProjectivePoint> = OnceLock::new();
+    *H.get_or_init(|| {
+        (0u32..)
+            .find_map(|counter| {
+                let mut bytes = vec![2];
+                bytes.extend(Sha256::new().chain_update(b"SierTriChain pedersen H").chain_update(counter.to_le_bytes()).finalize());
+                Option::<AffinePoint>::from(AffinePoint::from_bytes(&CompressedPoint::clone_from_slice(&bytes))).map(ProjectivePoint::from)
+            })
+            .expect("about half of all x coordinates are on the curve")
+    })
+}
+
+fn g() -> ProjectivePoint {
+    ProjectivePoint::GENERATOR
+}
+
+fn commit(value: u64, blinding: &Scalar) -> ProjectivePoint {
+    g() * Scalar::from(value) + h() * blinding
+}
+
+fn random() -> Scalar {
+    Scalar::random(&mut OsRng)
+}
+
+/// Fiat-Shamir transcript: everything a proof depends on, in order.
+struct Transcript(Sha256);
+
+impl Transcript {
+    fn new(domain: &[u8]) -> Self {
+        let mut transcript = Transcript(Sha256::new());
+        transcript.append(b"domain", domain);
+        transcript
+    }
+
+    fn append(&mut self, label: &[u8], bytes: &[u8]) {
+        for part in [label, bytes] {
+            self.0.update((part.len() as u32).to_le_bytes());
+            self.0.update(part);
+        }
+    }
+
+    fn point(&mut self, label: &[u8], point: &ProjectivePoint) {
+        self.append(label, &point.to_bytes());
+    }
+
+    fn challenge(&mut self, label: &[u8]) -> Scalar {
+        self.append(b"challenge", label);
+        let digest = self.0.clone().finalize();
+        self.0.update(digest);
+        <Scalar as Reduce<U256>>::reduce_bytes(&digest)
+    }
+}
+
+/// Reads points and scalars off the front of a byte string.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], ZkError> {
+        if self.0.len() < len {
+            return Err(ZkError::Malformed);
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn point(&mut self) -> Result<ProjectivePoint, ZkError> {
+        let bytes = CompressedPoint::clone_from_slice(self.take(POINT_LEN)?);
+        Option::from(ProjectivePoint::from_bytes(&bytes)).ok_or(ZkError::Malformed)
+    }
+
+    fn scalar(&mut self) -> Result<Scalar, ZkError> {
+        let bytes = FieldBytes::clone_from_slice(self.take(SCALAR_LEN)?);
+        Option::from(Scalar::from_repr(bytes)).ok_or(ZkError::Malformed)
+    }
+
+    fn finish(&self) -> Result<(), ZkError> {
+        if self.0.is_empty() { Ok(()) } else { Err(ZkError::Malformed) }
+    }
+}
+
+/// Proof of `x` with `target = x * H`, that is, a commitment to zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ZeroProof {
+    challenge: Scalar,
+    response: Scalar,
+}
+
+impl ZeroProof {
+    fn prove(transcript: &mut Transcript, target: &ProjectivePoint, blinding: &Scalar) -> Self {
+        let nonce = random();
+        transcript.point(b"zero target", target);
+        transcript.point(b"zero announcement", &(h() * nonce));
+        let challenge = transcript.challenge(b"zero");
+        ZeroProof { challenge, response: nonce + challenge * blinding }
+    }
+
+    fn verify(&self, transcript: &mut Transcript, target: &ProjectivePoint) -> bool {
+        transcript.point(b"zero target", target);
+        transcript.point(b"zero announcement", &(h() * self.response - target * &self.challenge));
+        transcript.challenge(b"zero") == self.challenge
+    }
+}
+
+/// A commitment to a bit with an OR-proof that it commits to 0 or to 1.
+/// One branch is proved and the other simulated, and the two challenges
+/// must add up to the transcript's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BitProof {
+    commitment: ProjectivePoint,
+    challenges: [Scalar; 2],
+    responses: [Scalar; 2],
+}
+
+impl BitProof {
+    fn prove(transcript: &mut Transcript, bit: bool, blinding: &Scalar) -> Self {
+        let commitment = commit(bit as u64, blinding);
+        let targets = [commitment, commitment - g()];
+        let (real, fake) = (bit as usize, !bit as usize);
+        let mut challenges = [Scalar::ZERO; 2];
+        let mut responses = [Scalar::ZERO; 2];
+        let mut announcements = [ProjectivePoint::IDENTITY; 2];
+        challenges[fake] = random();
+        responses[fake] = random();
+        announcements[fake] = h() * responses[fake] - targets[fake] * challenges[fake];
+        let nonce = random();
+        announcements[real] = h() * nonce;
+
+        let challenge = Self::challenge(transcript, &commitment, &announcements);
+        challenges[real] = challenge - challenges[fake];
+        responses[real] = nonce + challenges[real] * blinding;
+        BitProof { commitment, challenges, responses }
+    }
+
+    fn verify(&self, transcript: &mut Transcript) -> bool {
+        let targets = [self.commitment, self.commitment - g()];
+        let announcements = [0, 1].map(|i| h() * self.responses[i] - targets[i] * self.challenges[i]);
+        Self::challenge(transcript, &self.commitment, &announcements) == self.challenges[0] + self.challenges[1]
+    }
+
+    fn challenge(transcript: &mut Transcript, commitment: &ProjectivePoint, announcements: &[ProjectivePoint; 2]) -> Scalar {
+        transcript.point(b"bit", commitment);
+        transcript.point(b"bit announcement 0", &announcements[0]);
+        transcript.point(b"bit announcement 1", &announcements[1]);
+        transcript.challenge(b"bit")
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.commitment.to_bytes());
+        for scalar in self.challenges.iter().chain(&self.responses) {
+            out.extend_from_slice(&scalar.to_bytes());
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, ZkError> {
+        Ok(BitProof {
+            commitment: reader.point()?,
+            challenges: [reader.scalar()?, reader.scalar()?],
+            responses: [reader.scalar()?, reader.scalar()?],
+        })
+    }
+}
+
+/// Proof that a commitment holds a value below 2^`RANGE_BITS`. The bit
+/// commitments, weighted by powers of two, add up to it exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RangeProof {
+    bits: Vec<BitProof>,
+}
+
+impl RangeProof {
+    fn prove(transcript: &mut Transcript, value: u64, blinding: &Scalar) -> Self {
+        debug_assert!(value < 1 << RANGE_BITS);
+        let mut blindings: Vec<Scalar> = (0..RANGE_BITS - 1).map(|_| random()).collect();
+        let weighted = blindings.iter().enumerate().fold(Scalar::ZERO, |sum, (i, r)| sum + Scalar::from(1u64 << i) * r);
+        let top = Scalar::from(1u64 << (RANGE_BITS - 1)).invert().expect("powers of two are invertible");
+        blindings.push((blinding - &weighted) * top);
+        let bits = blindings.iter().enumerate().map(|(i, r)| BitProof::prove(transcript, value >> i & 1 == 1, r)).collect();
+        RangeProof { bits }
+    }
+
+    fn verify(&self, transcript: &mut Transcript, commitment: &ProjectivePoint) -> bool {
+        let sum = self.bits.iter().enumerate().fold(ProjectivePoint::IDENTITY, |sum, (i, bit)| sum + bit.commitment * Scalar::from(1u64 << i));
+        self.bits.len() == RANGE_BITS && sum == *commitment && self.bits.iter().all(|bit| bit.verify(transcript))
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.bits.iter().for_each(|bit| bit.encode(out));
+    }
+
+    fn decode(reader: &mut Reader) -> Result<Self, ZkError> {
+        Ok(RangeProof { bits: (0..RANGE_BITS).map(|_| BitProof::decode(reader)).collect::<Result<_, _>>()? })
+    }
+}
+
+/// The secret behind a [`TerritoryCommitment`]: the address and the
+/// blinding factors. Only the holder keeps it.
+#[derive(Clone)]
+pub struct TerritoryOpening {
+    address: FractalAddress,
+    depth_blinding: Scalar,
+    digit_blindings: Vec<Scalar>,
+}
+
+impl TerritoryOpening {
+    pub fn address(&self) -> &FractalAddress {
+        &self.address
+    }
+}
+
+impl fmt::Debug for TerritoryOpening {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TerritoryOpening(..)")
+    }
+}
+
+impl Drop for TerritoryOpening {
+    fn drop(&mut self) {
+        self.depth_blinding = Scalar::ZERO;
+        self.digit_blindings.fill(Scalar::ZERO);
+    }
+}
+
+/// A hiding, binding commitment to a territory's address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerritoryCommitment {
+    depth: ProjectivePoint,
+    digits: Vec<ProjectivePoint>,
+    /// The depth, and `MAX_DEPTH` less the depth, are both in range.
+    depth_bounds: [RangeProof; 2],
+}
+
+impl TerritoryCommitment {
+    /// Commits to `address`, whose lattice triangle must be `triangle`.
+    pub fn commit(triangle: &Triangle, address: &FractalAddress) -> Result<(Self, TerritoryOpening), ZkError> {
+        if triangle_at(address) != *triangle {
+            return Err(ZkError::TriangleMismatch);
+        }
+        let opening = TerritoryOpening {
+            address: *address,
+            depth_blinding: random(),
+            digit_blindings: (0..MAX_DEPTH).map(|_| random()).collect(),
+        };
+        let depth = address.depth() as u64;
+        let mut commitment = TerritoryCommitment {
+            depth: commit(depth, &opening.depth_blinding),
+            digits: (0..MAX_DEPTH).map(|level| commit(address.digit(level).unwrap_or(0) as u64, &opening.digit_blindings[level])).collect(),
+            depth_bounds: [RangeProof { bits: vec![] }, RangeProof { bits: vec![] }],
+        };
+        let mut transcript = commitment.transcript();
+        commitment.depth_bounds = [
+            RangeProof::prove(&mut transcript, depth, &opening.depth_blinding),
+            RangeProof::prove(&mut transcript, MAX_DEPTH as u64 - depth, &-opening.depth_blinding),
+        ];
+        Ok((commitment, opening))
+    }
+
+    /// Checks that the committed depth is a valid one.
+    pub fn verify(&self) -> Result<(), ZkError> {
+        let mut transcript = self.transcript();
+        let below_max = g() * Scalar::from(MAX_DEPTH as u64) - self.depth;
+        if self.depth_bounds[0].verify(&mut transcript, &self.depth) && self.depth_bounds[1].verify(&mut transcript, &below_max) {
+            Ok(())
+        } else {
+            Err(ZkError::InvalidProof)
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(COMMITMENT_LEN);
+        for point in std::iter::once(&self.depth).chain(&self.digits) {
+            out.extend_from_slice(&point.to_bytes());
+        }
+        self.depth_bounds.iter().for_each(|proof| proof.encode(&mut out));
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ZkError> {
+        let mut reader = Reader(bytes);
+        let commitment = TerritoryCommitment {
+            depth: reader.point()?,
+            digits: (0..MAX_DEPTH).map(|_| reader.point()).collect::<Result<_, _>>()?,
+            depth_bounds: [RangeProof::decode(&mut reader)?, RangeProof::decode(&mut reader)?],
+        };
+        reader.finish()?;
+        Ok(commitment)
+    }
+
+    /// SHA-256 of the encoding, which is how proofs refer to the commitment.
+    pub fn id(&self) -> [u8; 32] {
+        Sha256::digest(self.encode()).into()
+    }
+
+    fn transcript(&self) -> Transcript {
+        let mut transcript = Transcript::new(b"SierTriChain territory commitment");
+        for point in std::iter::once(&self.depth).chain(&self.digits) {
+            transcript.point(b"commitment", point);
+        }
+        transcript
+    }
+}
+
+/// A proof that a commitment opens to a public address, bound to a context
+/// such as the claimant. Every committed value less the address's is shown
+/// to commit to zero, folded into one check with random weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressProof(ZeroProof);
+
+impl AddressProof {
+    pub fn prove(commitment: &TerritoryCommitment, opening: &TerritoryOpening, context: &[u8]) -> Self {
+        let mut transcript = Self::transcript(commitment, &opening.address, context);
+        let weights = Self::weights(&mut transcript);
+        let target = Self::target(commitment, &opening.address, &weights);
+        let blindings = std::iter::once(&opening.depth_blinding).chain(&opening.digit_blindings);
+        let blinding = weights.iter().zip(blindings).fold(Scalar::ZERO, |sum, (w, r)| sum + w * r);
+        AddressProof(ZeroProof::prove(&mut transcript, &target, &blinding))
+    }
+
+    /// Checks the commitment and that it opens to `address`.
+    pub fn verify(&self, commitment: &TerritoryCommitment, address: &FractalAddress, context: &[u8]) -> Result<(), ZkError> {
+        commitment.verify()?;
+        let mut transcript = Self::transcript(commitment, address, context);
+        let weights = Self::weights(&mut transcript);
+        if self.0.verify(&mut transcript, &Self::target(commitment, address, &weights)) {
+            Ok(())
+        } else {
+            Err(ZkError::InvalidProof)
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        [self.0.challenge.to_bytes(), self.0.response.to_bytes()].concat()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ZkError> {
+        let mut reader = Reader(bytes);
+        let proof = ZeroProof { challenge: reader.scalar()?, response: reader.scalar()? };
+        reader.finish()?;
+        Ok(AddressProof(proof))
+    }
+
+    fn transcript(commitment: &TerritoryCommitment, address: &FractalAddress, context: &[u8]) -> Transcript {
+        let mut transcript = Transcript::new(b"SierTriChain territory address");
+        transcript.append(b"commitment", &commitment.id());
+        transcript.append(b"address", address.to_string().as_bytes());
+        transcript.append(b"context", context);
+        transcript
+    }
+
+    /// One weight for the depth and one for every level.
+    fn weights(transcript: &mut Transcript) -> Vec<Scalar> {
+        (0..=MAX_DEPTH).map(|_| transcript.challenge(b"address weight")).collect()
+    }
+
+    /// The weighted sum of each committed value less the address's, digits
+    /// past its depth being zero.
+    fn target(commitment: &TerritoryCommitment, address: &FractalAddress, weights: &[Scalar]) -> ProjectivePoint {
+        let depth = (&commitment.depth, address.depth() as u64);
+        let digits = commitment.digits.iter().enumerate().map(|(level, c)| (c, address.digit(level).unwrap_or(0) as u64));
+        std::iter::once(depth).chain(digits).zip(weights).fold(ProjectivePoint::IDENTITY, |sum, ((committed, value), weight)| {
+            sum + (committed - &(g() * Scalar::from(value))) * weight
+        })
+    }
+}
+
+/// What a holder can prove about a committed territory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// The territory is `region` or inside it, at depth `min_depth` or
+    /// deeper.
+    InsideRegion { region: FractalAddress, min_depth: usize },
+    /// The territory's level area, the genesis area over 4^depth, is
+    /// greater than this.
+    AreaAbove(Decimal),
+}
+
+impl Statement {
+    pub fn holds(&self, address: &FractalAddress) -> bool {
+        match self {
+            Statement::InsideRegion { region, min_depth } => {
+                address.depth() >= *min_depth && (region == address || region.is_ancestor_of(address))
+            }
+            Statement::AreaAbove(area) => max_depth_above(*area).is_some_and(|depth| address.depth() <= depth),
+        }
+    }
+
+    fn append_to(&self, transcript: &mut Transcript) {
+        match self {
+            Statement::InsideRegion { region, min_depth } => {
+                transcript.append(b"inside region", region.to_string().as_bytes());
+                transcript.append(b"min depth", &(*min_depth as u64).to_le_bytes());
+            }
+            Statement::AreaAbove(area) => transcript.append(b"area above", &area.serialize()),
+        }
+    }
+}
+
+/// Deepest level whose triangles have more than `area`, if any.
+fn max_depth_above(area: Decimal) -> Option<usize> {
+    let mut level_area = genesis_triangle().area();
+    let mut deepest = None;
+    for depth in 0..=MAX_DEPTH {
+        if level_area <= area {
+            break;
+        }
+        deepest = Some(depth);
+        level_area /= Decimal::from(4);
+    }
+    deepest
+}
+
+/// A proof that a committed territory satisfies a [`Statement`], bound to
+/// the commitment and a context. Encoded as the range proof, then a byte
+/// saying whether a digit proof follows, then the digit proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipProof {
+    depth_range: RangeProof,
+    /// The committed digits down to the region's depth are the region's.
+    region_digits: Option<ZeroProof>,
+}
+
+impl OwnershipProof {
+    pub fn prove(
+        commitment: &TerritoryCommitment,
+        opening: &TerritoryOpening,
+        statement: &Statement,
+        context: &[u8],
+    ) -> Result<Self, ZkError> {
+        if !statement.holds(&opening.address) {
+            return Err(ZkError::FalseStatement);
+        }
+        let mut transcript = Self::transcript(commitment, statement, context);
+        let depth = opening.address.depth() as u64;
+        match statement {
+            Statement::InsideRegion { region, min_depth } => {
+                let floor = (*min_depth).max(region.depth()) as u64;
+                let depth_range = RangeProof::prove(&mut transcript, depth - floor, &opening.depth_blinding);
+                let weights = Self::digit_weights(&mut transcript, region.depth());
+                let target = Self::region_target(commitment, region, &weights);
+                let blinding = weights.iter().zip(&opening.digit_blindings).fold(Scalar::ZERO, |sum, (w, r)| sum + w * r);
+                Ok(OwnershipProof { depth_range, region_digits: Some(ZeroProof::prove(&mut transcript, &target, &blinding)) })
+            }
+            Statement::AreaAbove(area) => {
+                let ceiling = max_depth_above(*area).expect("the statement holds") as u64;
+                let depth_range = RangeProof::prove(&mut transcript, ceiling - depth, &-opening.depth_blinding);
+                Ok(OwnershipProof { depth_range, region_digits: None })
+            }
+        }
+    }
+
+    /// Checks the commitment and that this proves `statement` about it in
+    /// `context`.
+    pub fn verify(&self, commitment: &TerritoryCommitment, statement: &Statement, context: &[u8]) -> Result<(), ZkError> {
+        commitment.verify()?;
+        let mut transcript = Self::transcript(commitment, statement, context);
+        let valid = match (statement, &self.region_digits) {
+            (Statement::InsideRegion { region, min_depth }, Some(digits)) => {
+                let floor = (*min_depth).max(region.depth());
+                floor <= MAX_DEPTH && self.depth_range.verify(&mut transcript, &(commitment.depth - g() * Scalar::from(floor as u64))) && {
+                    let weights = Self::digit_weights(&mut transcript, region.depth());
+                    digits.verify(&mut transcript, &Self::region_target(commitment, region, &weights))
+                }
+            }
+            (Statement::AreaAbove(area), None) => max_depth_above(*area).is_some_and(|ceiling| {
+                self.depth_range.verify(&mut transcript, &(g() * Scalar::from(ceiling as u64) - commitment.depth))
+            }),
+            _ => false,
+        };
+        if valid { Ok(()) } else { Err(ZkError::InvalidProof) }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(RANGE_PROOF_LEN + 1 + 2 * SCALAR_LEN);
+        self.depth_range.encode(&mut out);
+        match &self.region_digits {
+            Some(proof) => {
+                out.push(1);
+                out.extend_from_slice(&proof.challenge.to_bytes());
+                out.extend_from_slice(&proof.response.to_bytes());
+            }
+            None => out.push(0),
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, ZkError> {
+        let mut reader = Reader(bytes);
+        let depth_range = RangeProof::decode(&mut reader)?;
+        let region_digits = match reader.take(1)?[0] {
+            0 => None,
+            1 => Some(ZeroProof { challenge: reader.scalar()?, response: reader.scalar()? }),
+            _ => return Err(ZkError::Malformed),
+        };
+        reader.finish()?;
+        Ok(OwnershipProof { depth_range, region_digits })
+    }
+
+    fn transcript(commitment: &TerritoryCommitment, statement: &Statement, context: &[u8]) -> Transcript {
+        let mut transcript = Transcript::new(b"SierTriChain territory ownership");
+        transcript.append(b"commitment", &commitment.id());
+        statement.append_to(&mut transcript);
+        transcript.append(b"context", context);
+        transcript
+    }
+
+    /// Random weights folding the region's digit checks into one, so a
+    /// single wrong digit makes the sum fail.
+    fn digit_weights(transcript: &mut Transcript, levels: usize) -> Vec<Scalar> {
+        (0..levels).map(|_| transcript.challenge(b"digit weight")).collect()
+    }
+
+    /// The weighted sum of each committed digit less the region's, which
+    /// commits to zero exactly when they all match.
+    fn region_target(commitment: &TerritoryCommitment, region: &FractalAddress, weights: &[Scalar]) -> ProjectivePoint {
+        region.digits().zip(&commitment.digits).zip(weights).fold(ProjectivePoint::IDENTITY, |sum, ((digit, committed), weight)| {
+            sum + (committed - &(g() * Scalar::from(digit as u64))) * weight
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn address(digits: &[u8]) -> FractalAddress {
+        FractalAddress::from_digits(digits).unwrap()
+    }
+
+    fn committed(digits: &[u8]) -> (TerritoryCommitment, TerritoryOpening) {
+        let address = address(digits);
+        TerritoryCommitment::commit(&triangle_at(&address), &address).unwrap()
+    }
+
+    #[test]
+    fn test_commitment_hides_the_address() {
+        let (commitment, opening) = committed(&[2, 0, 3]);
+        assert_eq!(commitment.verify(), Ok(()));
+        assert_eq!(opening.address(), &address(&[2, 0, 3]));
+        assert_eq!(commitment.encode().len(), COMMITMENT_LEN);
+        assert_eq!(TerritoryCommitment::decode(&commitment.encode()), Ok(commitment.clone()));
+        // Committing again to the same address looks unrelated.
+        assert_ne!(committed(&[2, 0, 3]).0.depth, commitment.depth);
+
+        let other = address(&[1]);
+        assert_eq!(
+            TerritoryCommitment::commit(&triangle_at(&other), &address(&[2])).err(),
+            Some(ZkError::TriangleMismatch)
+        );
+    }
+
+    #[test]
+    fn test_proves_depth_inside_region() {
+        let (commitment, opening) = committed(&[2, 0, 3, 1]);
+        let statement = Statement::InsideRegion { region: address(&[2, 0]), min_depth: 3 };
+        let proof = OwnershipProof::prove(&commitment, &opening, &statement, b"alice").unwrap();
+        assert_eq!(proof.verify(&commitment, &statement, b"alice"), Ok(()));
+        assert_eq!(OwnershipProof::decode(&proof.encode()), Ok(proof.clone()));
+
+        // The proof speaks to this statement, commitment and context only.
+        assert_eq!(proof.verify(&commitment, &statement, b"mallory"), Err(ZkError::InvalidProof));
+        let elsewhere = Statement::InsideRegion { region: address(&[2, 1]), min_depth: 3 };
+        assert_eq!(proof.verify(&commitment, &elsewhere, b"alice"), Err(ZkError::InvalidProof));
+        let deeper = Statement::InsideRegion { region: address(&[2, 0]), min_depth: 5 };
+        assert_eq!(proof.verify(&commitment, &deeper, b"alice"), Err(ZkError::InvalidProof));
+        assert_eq!(proof.verify(&committed(&[2, 0, 3, 1]).0, &statement, b"alice"), Err(ZkError::InvalidProof));
+
+        // False statements cannot be proved.
+        assert_eq!(OwnershipProof::prove(&commitment, &opening, &elsewhere, b"alice"), Err(ZkError::FalseStatement));
+        assert_eq!(OwnershipProof::prove(&commitment, &opening, &deeper, b"alice"), Err(ZkError::FalseStatement));
+    }
+
+    #[test]
+    fn test_forged_proofs_fail() {
+        // A holder of [2, 1] borrows a proof-shaped answer for [2, 0] by
+        // proving against a statement that is false for them.
+        let (commitment, opening) = committed(&[2, 1, 1]);
+        let honest = Statement::InsideRegion { region: address(&[2, 1]), min_depth: 0 };
+        let claimed = Statement::InsideRegion { region: address(&[2, 0]), min_depth: 0 };
+        let proof = OwnershipProof::prove(&commitment, &opening, &honest, b"").unwrap();
+        assert_eq!(proof.verify(&commitment, &claimed, b""), Err(ZkError::InvalidProof));
+
+        let mut tampered = proof.encode();
+        tampered[POINT_LEN + 5] ^= 1;
+        assert!(OwnershipProof::decode(&tampered).map_or(true, |proof| proof.verify(&commitment, &honest, b"").is_err()));
+        let mut bad_commitment = commitment.encode();
+        bad_commitment[COMMITMENT_LEN - 1] ^= 1;
+        assert!(TerritoryCommitment::decode(&bad_commitment).map_or(true, |c| c.verify().is_err()));
+        assert_eq!(OwnershipProof::decode(&proof.encode()[1..]), Err(ZkError::Malformed));
+    }
+
+    #[test]
+    fn test_address_proof_opens_to_the_claim() {
+        let (commitment, opening) = committed(&[1, 3, 0]);
+        let proof = AddressProof::prove(&commitment, &opening, b"alice");
+        assert_eq!(AddressProof::decode(&proof.encode()), Ok(proof));
+        assert_eq!(proof.encode().len(), ADDRESS_PROOF_LEN);
+        assert_eq!(proof.verify(&commitment, &address(&[1, 3, 0]), b"alice"), Ok(()));
+
+        // Not another address, however close, nor another claimant.
+        for other in [&[1, 3][..], &[1, 3, 0, 0], &[1, 3, 1]] {
+            assert_eq!(proof.verify(&commitment, &address(other), b"alice"), Err(ZkError::InvalidProof));
+        }
+        assert_eq!(proof.verify(&commitment, &address(&[1, 3, 0]), b"mallory"), Err(ZkError::InvalidProof));
+        // An opening of another commitment proves nothing about this one.
+        let (_, other_opening) = committed(&[1, 3, 0]);
+        let borrowed = AddressProof::prove(&commitment, &other_opening, b"alice");
+        assert_eq!(borrowed.verify(&commitment, &address(&[1, 3, 0]), b"alice"), Err(ZkError::InvalidProof));
+    }
+
+    #[test]
+    fn test_proves_area_above() {
+        let genesis = genesis_triangle().area();
+        let (commitment, opening) = committed(&[3, 3]);
+        let area = triangle_at(&address(&[3, 3])).area();
+        assert!((area - genesis / dec!(16)).abs() < dec!(1e-20));
+
+        let statement = Statement::AreaAbove(genesis / dec!(20));
+        assert!(!Statement::AreaAbove(genesis / dec!(20)).holds(&address(&[3, 3, 0])));
+        let proof = OwnershipProof::prove(&commitment, &opening, &statement, b"").unwrap();
+        assert_eq!(proof.verify(&commitment, &statement, b""), Ok(()));
+        assert_eq!(proof.verify(&commitment, &Statement::AreaAbove(genesis / dec!(10)), b""), Err(ZkError::InvalidProof));
+        assert_eq!(OwnershipProof::prove(&commitment, &opening, &Statement::AreaAbove(genesis / dec!(16)), b""), Err(ZkError::FalseStatement));
+        assert_eq!(OwnershipProof::prove(&commitment, &opening, &Statement::AreaAbove(genesis), b""), Err(ZkError::FalseStatement));
+
+        // A region proof does not pass for an area statement, nor the reverse.
+        let region = Statement::InsideRegion { region: address(&[3]), min_depth: 0 };
+        assert_eq!(proof.verify(&commitment, &region, b""), Err(ZkError::InvalidProof));
+    }
+}
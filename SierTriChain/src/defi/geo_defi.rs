@@ -1,99 +1,166 @@
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::geometry::hierarchy::FractalAddress;
+    use crate::geometry::lattice::LatticeTriangle;
+    use crate::geometry::point::Point;
+    use rust_decimal_macros::dec;
+
     #[test]
     fn test_defi_geo_defi_basic() {
         assert_eq!(2 + 2, 4);
     }
+
+    fn triangle(digits: &[u8]) -> Triangle {
+        Triangle::from(LatticeTriangle::at(&FractalAddress::from_digits(digits).unwrap()))
+    }
+
+    /// A right triangle with legs of `leg`, so its area is exact.
+    fn right(leg: Decimal) -> Triangle {
+        Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(leg, dec!(0)), Point::new(dec!(0), leg))
+    }
+
+    fn tokens(n: u64) -> Amount {
+        Amount::from_tokens(n).unwrap()
+    }
+
+    #[test]
+    fn test_swap_cannot_drain_reserves_below_zero() {
+        // Pool A is four times the area of pool B, so one A buys two B.
+        let mut amm = AMM { pool_a: right(dec!(2)), pool_b: right(dec!(1)), reserve_a: tokens(10), reserve_b: tokens(5) };
+        assert_eq!(amm.price(), dec!(2));
+        assert_eq!(amm.swap_a_for_b(tokens(2)), Ok(tokens(4)));
+        assert_eq!((amm.reserve_a, amm.reserve_b), (tokens(12), tokens(1)));
+        assert_eq!(amm.swap_a_for_b(tokens(1)), Err(AmountError::Negative));
+        assert_eq!((amm.reserve_a, amm.reserve_b), (tokens(12), tokens(1)));
+        assert_eq!(amm.swap_b_for_a(tokens(1)), Ok(Amount::from_base_units(50_000_000)));
+    }
+
+    #[test]
+    fn test_swap_rounds_in_the_pools_favour() {
+        let mut amm = AMM { pool_a: right(dec!(2)), pool_b: right(dec!(1)), reserve_a: tokens(10), reserve_b: tokens(10) };
+        assert_eq!(amm.swap_b_for_a(Amount::from_base_units(3)), Ok(Amount::from_base_units(1)));
+        let flat = Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(1), dec!(0)), Point::new(dec!(2), dec!(0)));
+        let mut degenerate = AMM { pool_b: flat, ..amm };
+        assert_eq!(degenerate.swap_b_for_a(tokens(1)), Err(AmountError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_lending_and_synthetics() {
+        let lending = Lending { triangle: triangle(&[0]), depth: 1, collateral: tokens(3) };
+        assert_eq!(lending.collateral_ratio(), Decimal::ONE);
+        assert_eq!(lending.max_loan(), Ok(tokens(3)));
+
+        assert_eq!(yield_farming(16, tokens(1)), Ok(tokens(4)));
+
+        // Each depth-1 triangle is a quarter of the genesis area.
+        let genesis_area = triangle(&[]).area();
+        let synthetic = SyntheticAsset { triangles: vec![triangle(&[1]), triangle(&[2])], total_value: Amount::ZERO };
+        let expected = Amount::from_base_units(1).checked_div_decimal(genesis_area / dec!(4), Rounding::Down).unwrap();
+        assert_eq!(synthetic.value(Amount::from_base_units(1)), Ok(expected.checked_mul(2).unwrap()));
+    }
 }
 // Geometric DeFi module for fractal territory system
-// Includes AMM, lending, yield farming, futures, options, and synthetics
+// Includes AMM, lending, yield farming, futures, options, and synthetics.
+// Token quantities are fixed-point `Amount`s and every rounding is explicit,
+// so all nodes agree on the results.
 
+use crate::defi::token::{Amount, AmountError, Rounding};
 use crate::geometry::triangle::Triangle;
-use rust_decimal::prelude::ToPrimitive;
-use crate::geometry::subdivision::FractalAddress;
+use rust_decimal::{Decimal, MathematicalOps};
 
 pub struct AMM {
     pub pool_a: Triangle,
     pub pool_b: Triangle,
-    pub reserve_a: f64,
-    pub reserve_b: f64,
+    pub reserve_a: Amount,
+    pub reserve_b: Amount,
 }
 
 impl AMM {
-    // Price = sqrt(area_A / area_B)
-    pub fn price(&self) -> f64 {
-    let area_a = crate::geometry::area::triangle_area(&self.pool_a);
-    let area_b = crate::geometry::area::triangle_area(&self.pool_b);
-        if area_b <= 0.0 {
-            return 0.0;
+    // Price = sqrt(area_A / area_B), zero if pool B has no area
+    pub fn price(&self) -> Decimal {
+        let area_b = self.pool_b.area();
+        if area_b.is_zero() {
+            return Decimal::ZERO;
         }
-        (area_a / area_b).sqrt()
+        self.pool_a.area().checked_div(area_b).and_then(|ratio| ratio.sqrt()).unwrap_or(Decimal::ZERO)
     }
-    // Swap A for B
-    pub fn swap_a_for_b(&mut self, amount_a: f64) -> f64 {
-        let price = self.price();
-        let amount_b = amount_a * price;
-        self.reserve_a += amount_a;
-        self.reserve_b -= amount_b;
-        amount_b
+    // Swap A for B, rounding the payout down. Fails, leaving the reserves
+    // alone, if pool B cannot cover it.
+    pub fn swap_a_for_b(&mut self, amount_a: Amount) -> Result<Amount, AmountError> {
+        let amount_b = amount_a.checked_mul_decimal(self.price(), Rounding::Down)?;
+        let reserve_a = self.reserve_a.checked_add(amount_a)?;
+        self.reserve_b = self.reserve_b.checked_sub(amount_b)?;
+        self.reserve_a = reserve_a;
+        Ok(amount_b)
     }
-    // Swap B for A
-    pub fn swap_b_for_a(&mut self, amount_b: f64) -> f64 {
-        let price = self.price();
-        let amount_a = amount_b / price;
-        self.reserve_b += amount_b;
-        self.reserve_a -= amount_a;
-        amount_a
+    // Swap B for A, rounding the payout down
+    pub fn swap_b_for_a(&mut self, amount_b: Amount) -> Result<Amount, AmountError> {
+        let amount_a = amount_b.checked_div_decimal(self.price(), Rounding::Down)?;
+        let reserve_b = self.reserve_b.checked_add(amount_b)?;
+        self.reserve_a = self.reserve_a.checked_sub(amount_a)?;
+        self.reserve_b = reserve_b;
+        Ok(amount_a)
     }
 }
 
 pub struct Lending {
     pub triangle: Triangle,
     pub depth: usize,
-    pub collateral: f64,
+    pub collateral: Amount,
 }
 
 impl Lending {
-    // Collateral ratio increases with depth
-    pub fn collateral_ratio(&self) -> f64 {
-        1.0 + (self.depth as f64).ln()
+    // Collateral ratio increases with depth: 1 + ln(depth), at least 1
+    pub fn collateral_ratio(&self) -> Decimal {
+        Decimal::ONE + Decimal::from(self.depth.max(1) as u64).ln()
+    }
+
+    // Largest loan the collateral backs, rounded down
+    pub fn max_loan(&self) -> Result<Amount, AmountError> {
+        self.collateral.checked_div_decimal(self.collateral_ratio(), Rounding::Down)
     }
 }
 
-// Yield farming: rewards proportional to geometric computational work
-pub fn yield_farming(computational_work: f64, base_reward: f64) -> f64 {
-    base_reward * computational_work.sqrt()
+// Yield farming: rewards proportional to geometric computational work, rounded down
+pub fn yield_farming(computational_work: u64, base_reward: Amount) -> Result<Amount, AmountError> {
+    let factor = Decimal::from(computational_work).sqrt().ok_or(AmountError::Overflow)?;
+    base_reward.checked_mul_decimal(factor, Rounding::Down)
 }
 
 // Futures contract stub for triangle subdivision events
 pub struct TriangleFuture {
     pub triangle: Triangle,
     pub event_block: usize,
-    pub volatility: f64,
+    pub volatility: Decimal,
 }
 
 impl TriangleFuture {
-    pub fn price(&self) -> f64 {
+    pub fn price(&self) -> Decimal {
         // Geometric volatility pricing stub
-        self.volatility * self.triangle.area().to_f64().unwrap_or(0.0)
+        self.volatility * self.triangle.area()
     }
 }
 
 // Options market stub for territory acquisition rights
 pub struct TriangleOption {
     pub triangle: Triangle,
-    pub strike_price: f64,
+    pub strike_price: Amount,
     pub expiry_block: usize,
 }
 
 // Synthetic asset backed by geometric territory portfolio
 pub struct SyntheticAsset {
     pub triangles: Vec<Triangle>,
-    pub total_value: f64,
+    pub total_value: Amount,
 }
 
 impl SyntheticAsset {
-    pub fn value(&self, base_value: f64) -> f64 {
-        self.triangles.iter().map(|t| base_value / t.area().to_f64().unwrap().max(1e-8)).sum()
+    // Sum of base_value / area over the portfolio, each term rounded down
+    pub fn value(&self, base_value: Amount) -> Result<Amount, AmountError> {
+        let min_area = Decimal::new(1, 8);
+        self.triangles.iter().try_fold(Amount::ZERO, |total, triangle| {
+            total.checked_add(base_value.checked_div_decimal(triangle.area().max(min_area), Rounding::Down)?)
+        })
     }
 }
@@ -1,8 +1,236 @@
 #[cfg(test)]
 mod tests {
+	use super::*;
+	use rust_decimal_macros::dec;
+
 	#[test]
 	fn test_defi_token_basic() {
 		assert_eq!(2 + 2, 4);
 	}
+
+	#[test]
+	fn test_denominations() {
+		assert_eq!(Amount::from_tokens(3), Ok(Amount::from_base_units(300_000_000)));
+		assert_eq!(Amount::from_tokens(u64::MAX), Err(AmountError::Overflow));
+		assert_eq!(Amount::from_base_units(150_000_000).to_tokens(), dec!(1.5));
+		assert_eq!(Amount::from_base_units(150_000_000).to_string(), "1.50000000");
+		assert_eq!("1.5".parse(), Ok(Amount::from_base_units(150_000_000)));
+		assert_eq!("0.00000001".parse(), Ok(Amount::ONE_UNIT));
+		assert_eq!("0.000000001".parse::<Amount>(), Err(AmountError::Inexact));
+		assert_eq!("-1".parse::<Amount>(), Err(AmountError::Negative));
+	}
+
+	#[test]
+	fn test_checked_arithmetic() {
+		let one = Amount::from_base_units(1);
+		assert_eq!(Amount::MAX.checked_add(one), Err(AmountError::Overflow));
+		assert_eq!(Amount::ZERO.checked_sub(one), Err(AmountError::Negative));
+		assert_eq!(Amount::MAX.checked_mul(2), Err(AmountError::Overflow));
+		assert_eq!(one.checked_div(0, Rounding::Down), Err(AmountError::DivisionByZero));
+		assert_eq!(Amount::from_base_units(10).saturating_sub(Amount::from_base_units(11)), Amount::ZERO);
+	}
+
+	#[test]
+	fn test_rounding_modes() {
+		let seven = Amount::from_base_units(7);
+		assert_eq!(seven.checked_div(2, Rounding::Down), Ok(Amount::from_base_units(3)));
+		assert_eq!(seven.checked_div(2, Rounding::Up), Ok(Amount::from_base_units(4)));
+		// Ties go to the even neighbour.
+		assert_eq!(seven.checked_div(2, Rounding::HalfEven), Ok(Amount::from_base_units(4)));
+		assert_eq!(Amount::from_base_units(5).checked_div(2, Rounding::HalfEven), Ok(Amount::from_base_units(2)));
+		assert_eq!(Amount::from_base_units(5).checked_mul_ratio(1, 3, Rounding::HalfEven), Ok(Amount::from_base_units(2)));
+
+		let ten = Amount::from_base_units(10);
+		assert_eq!(ten.checked_mul_decimal(dec!(0.15), Rounding::Down), Ok(Amount::from_base_units(1)));
+		assert_eq!(ten.checked_mul_decimal(dec!(0.15), Rounding::Up), Ok(Amount::from_base_units(2)));
+		assert_eq!(ten.checked_mul_decimal(dec!(0.15), Rounding::HalfEven), Ok(Amount::from_base_units(2)));
+		assert_eq!(ten.checked_mul_decimal(dec!(-1), Rounding::Down), Err(AmountError::Negative));
+		assert_eq!(Amount::from_tokens_decimal(dec!(0.123456789), Rounding::Up), Ok(Amount::from_base_units(12_345_679)));
+	}
 }
 // Moved from src/token.rs
+
+// Token quantities.
+//
+// An `Amount` is a whole number of base units, `BASE_UNITS_PER_TOKEN` to
+// the token, so every node computes the same balances. Arithmetic is
+// checked, and anything that divides says which way it rounds.
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Decimal places of a token that base units resolve.
+pub const TOKEN_DECIMALS: u32 = 8;
+pub const BASE_UNITS_PER_TOKEN: u64 = 10u64.pow(TOKEN_DECIMALS);
+
+/// Why an amount could not be computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmountError {
+	Overflow,
+	/// The result would be below zero.
+	Negative,
+	DivisionByZero,
+	/// The value has more decimal places than base units resolve.
+	Inexact,
+	Invalid,
+}
+
+impl fmt::Display for AmountError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			AmountError::Overflow => write!(f, "amount overflows"),
+			AmountError::Negative => write!(f, "amount would be negative"),
+			AmountError::DivisionByZero => write!(f, "amount divided by zero"),
+			AmountError::Inexact => write!(f, "amount is finer than one base unit"),
+			AmountError::Invalid => write!(f, "not a token amount"),
+		}
+	}
+}
+
+impl std::error::Error for AmountError {}
+
+/// Which way a result between two base units goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+	/// Towards zero.
+	Down,
+	/// Away from zero.
+	Up,
+	/// To the nearest, ties to the even one.
+	HalfEven,
+}
+
+impl Rounding {
+	fn strategy(self) -> RoundingStrategy {
+		match self {
+			Rounding::Down => RoundingStrategy::ToZero,
+			Rounding::Up => RoundingStrategy::AwayFromZero,
+			Rounding::HalfEven => RoundingStrategy::MidpointNearestEven,
+		}
+	}
+}
+
+/// A non-negative quantity of tokens, counted in base units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Amount(u64);
+
+impl Amount {
+	pub const ZERO: Amount = Amount(0);
+	pub const ONE_UNIT: Amount = Amount(1);
+	pub const MAX: Amount = Amount(u64::MAX);
+
+	pub const fn from_base_units(units: u64) -> Self {
+		Amount(units)
+	}
+
+	pub fn from_tokens(tokens: u64) -> Result<Self, AmountError> {
+		tokens.checked_mul(BASE_UNITS_PER_TOKEN).map(Amount).ok_or(AmountError::Overflow)
+	}
+
+	/// `tokens` rounded to a whole number of base units.
+	pub fn from_tokens_decimal(tokens: Decimal, rounding: Rounding) -> Result<Self, AmountError> {
+		Amount::from_tokens(1)?.checked_mul_decimal(tokens, rounding)
+	}
+
+	pub const fn base_units(self) -> u64 {
+		self.0
+	}
+
+	/// The amount in tokens, exactly.
+	pub fn to_tokens(self) -> Decimal {
+		Decimal::from_i128_with_scale(self.0 as i128, TOKEN_DECIMALS)
+	}
+
+	pub fn is_zero(self) -> bool {
+		self.0 == 0
+	}
+
+	pub fn checked_add(self, other: Amount) -> Result<Self, AmountError> {
+		self.0.checked_add(other.0).map(Amount).ok_or(AmountError::Overflow)
+	}
+
+	pub fn checked_sub(self, other: Amount) -> Result<Self, AmountError> {
+		self.0.checked_sub(other.0).map(Amount).ok_or(AmountError::Negative)
+	}
+
+	pub fn saturating_sub(self, other: Amount) -> Self {
+		Amount(self.0.saturating_sub(other.0))
+	}
+
+	pub fn checked_mul(self, factor: u64) -> Result<Self, AmountError> {
+		self.0.checked_mul(factor).map(Amount).ok_or(AmountError::Overflow)
+	}
+
+	pub fn checked_div(self, divisor: u64, rounding: Rounding) -> Result<Self, AmountError> {
+		self.checked_mul_ratio(1, divisor, rounding)
+	}
+
+	/// `self * numerator / denominator`, exact until the final rounding.
+	pub fn checked_mul_ratio(self, numerator: u64, denominator: u64, rounding: Rounding) -> Result<Self, AmountError> {
+		if denominator == 0 {
+			return Err(AmountError::DivisionByZero);
+		}
+		let product = self.0 as u128 * numerator as u128;
+		let (denominator, quotient, remainder) = (denominator as u128, product / denominator as u128, product % denominator as u128);
+		let round_up = match rounding {
+			Rounding::Down => false,
+			Rounding::Up => remainder > 0,
+			Rounding::HalfEven => remainder * 2 > denominator || (remainder * 2 == denominator && quotient % 2 == 1),
+		};
+		let units = quotient + round_up as u128;
+		u64::try_from(units).map(Amount).map_err(|_| AmountError::Overflow)
+	}
+
+	/// `self * factor`, computed in `Decimal` and rounded once to base units.
+	pub fn checked_mul_decimal(self, factor: Decimal, rounding: Rounding) -> Result<Self, AmountError> {
+		if factor.is_sign_negative() && !factor.is_zero() {
+			return Err(AmountError::Negative);
+		}
+		let product = Decimal::from(self.0).checked_mul(factor).ok_or(AmountError::Overflow)?;
+		product.round_dp_with_strategy(0, rounding.strategy()).to_u64().map(Amount).ok_or(AmountError::Overflow)
+	}
+
+	/// `self / divisor`, computed in `Decimal` and rounded once to base units.
+	pub fn checked_div_decimal(self, divisor: Decimal, rounding: Rounding) -> Result<Self, AmountError> {
+		if divisor.is_zero() {
+			return Err(AmountError::DivisionByZero);
+		}
+		if divisor.is_sign_negative() {
+			return Err(AmountError::Negative);
+		}
+		let quotient = Decimal::from(self.0).checked_div(divisor).ok_or(AmountError::Overflow)?;
+		quotient.round_dp_with_strategy(0, rounding.strategy()).to_u64().map(Amount).ok_or(AmountError::Overflow)
+	}
+}
+
+impl fmt::Display for Amount {
+	/// The amount in tokens with every decimal place, such as `1.50000000`.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}.{:0width$}", self.0 / BASE_UNITS_PER_TOKEN, self.0 % BASE_UNITS_PER_TOKEN, width = TOKEN_DECIMALS as usize)
+	}
+}
+
+impl FromStr for Amount {
+	type Err = AmountError;
+
+	/// Parses a token amount such as `1.5`, which must be a whole number of
+	/// base units.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let tokens = Decimal::from_str(s).map_err(|_| AmountError::Invalid)?;
+		let amount = Amount::from_tokens_decimal(tokens, Rounding::Down)?;
+		if amount.to_tokens() != tokens {
+			return Err(AmountError::Inexact);
+		}
+		Ok(amount)
+	}
+}
+
+impl std::iter::Sum for Amount {
+	/// Saturates at [`Amount::MAX`]; use `checked_add` where that matters.
+	fn sum<I: Iterator<Item = Amount>>(iter: I) -> Self {
+		iter.fold(Amount::ZERO, |total, amount| Amount(total.0.saturating_add(amount.0)))
+	}
+}
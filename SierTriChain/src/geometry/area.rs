@@ -6,3 +6,11 @@ mod tests {
 	}
 }
 // Moved from src/area.rs
+
+use crate::geometry::triangle::Triangle;
+use rust_decimal::prelude::ToPrimitive;
+
+/// Triangle area as `f64`, for the DeFi and protocol heuristics that work in floats.
+pub fn triangle_area(triangle: &Triangle) -> f64 {
+	triangle.area().to_f64().unwrap_or(0.0)
+}
@@ -1 +1,488 @@
+//! Hierarchical addressing of the Sierpinski subdivision.
+//!
+//! Each level of the subdivision splits a triangle into four children, so an
+//! address is a sequence of base-4 digits read from the root down. Digits are
+//! packed two bits apiece, most significant first, with the depth alongside.
 
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Deepest address that fits in the packed representation.
+pub const MAX_DEPTH: usize = 128;
+
+/// Most levels [`FractalAddress::neighbours_at_depth`] will refine below an
+/// address, which bounds its result to `3 * 2^12` addresses.
+pub const MAX_NEIGHBOUR_REFINEMENT: usize = 12;
+
+/// Errors from building or parsing a [`FractalAddress`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    InvalidDigit(u8),
+    TooDeep(usize),
+    /// Finer neighbours were asked for this many levels down, past
+    /// [`MAX_NEIGHBOUR_REFINEMENT`].
+    RefinementTooDeep(usize),
+    Malformed(String),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressError::InvalidDigit(digit) => write!(f, "invalid subdivision digit {}", digit),
+            AddressError::TooDeep(depth) => write!(f, "address depth {} exceeds {}", depth, MAX_DEPTH),
+            AddressError::RefinementTooDeep(levels) => {
+                write!(f, "neighbours {} levels finer exceed the limit of {}", levels, MAX_NEIGHBOUR_REFINEMENT)
+            }
+            AddressError::Malformed(s) => write!(f, "malformed fractal address {:?}", s),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+/// Position of a triangle in the subdivision tree.
+///
+/// Addresses order lexicographically by digit with a prefix before its
+/// extensions, which is the depth-first order of the subdivision.
+/// Bits past `depth` are always zero, so the derived comparisons and hash
+/// agree with that order.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FractalAddress {
+    packed: [u8; MAX_DEPTH / 4],
+    depth: u8,
+}
+
+impl FractalAddress {
+    /// The address of the root triangle itself.
+    pub const fn root() -> Self {
+        FractalAddress { packed: [0; MAX_DEPTH / 4], depth: 0 }
+    }
+
+    pub fn from_digits(digits: &[u8]) -> Result<Self, AddressError> {
+        if digits.len() > MAX_DEPTH {
+            return Err(AddressError::TooDeep(digits.len()));
+        }
+        digits.iter().try_fold(Self::root(), |address, &digit| {
+            address.child(digit).ok_or(AddressError::InvalidDigit(digit))
+        })
+    }
+
+    /// Takes the first `depth` digits of `bytes`, most significant bits first.
+    pub fn from_bytes(bytes: &[u8; MAX_DEPTH / 4], depth: usize) -> Result<Self, AddressError> {
+        if depth > MAX_DEPTH {
+            return Err(AddressError::TooDeep(depth));
+        }
+        let mut address = FractalAddress { packed: *bytes, depth: MAX_DEPTH as u8 };
+        address.truncate(depth);
+        Ok(address)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.depth == 0
+    }
+
+    /// Digit at `level`, counting from the root.
+    pub fn digit(&self, level: usize) -> Option<u8> {
+        (level < self.depth()).then(|| (self.packed[level / 4] >> Self::shift(level)) & 0b11)
+    }
+
+    pub fn digits(&self) -> impl Iterator<Item = u8> {
+        let address = *self;
+        (0..address.depth()).map(move |level| (address.packed[level / 4] >> Self::shift(level)) & 0b11)
+    }
+
+    pub fn to_digits(&self) -> Vec<u8> {
+        self.digits().collect()
+    }
+
+    /// The child reached by `digit`, or `None` for a bad digit or at [`MAX_DEPTH`].
+    pub fn child(&self, digit: u8) -> Option<Self> {
+        if digit > 3 || self.depth() == MAX_DEPTH {
+            return None;
+        }
+        let level = self.depth();
+        let mut child = *self;
+        child.packed[level / 4] |= digit << Self::shift(level);
+        child.depth += 1;
+        Some(child)
+    }
+
+    pub fn children(&self) -> Option<[Self; 4]> {
+        Some([self.child(0)?, self.child(1)?, self.child(2)?, self.child(3)?])
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        if self.is_root() {
+            return None;
+        }
+        Some(self.ancestor(self.depth() - 1))
+    }
+
+    /// The other three children of this address's parent.
+    pub fn siblings(&self) -> Option<[Self; 3]> {
+        let parent = self.parent()?;
+        let own = self.digit(self.depth() - 1)?;
+        let mut others = (0..4).filter(|&d| d != own).map(|d| parent.child(d).unwrap());
+        Some([others.next()?, others.next()?, others.next()?])
+    }
+
+    /// The prefix of this address at `depth`, or the address itself if it is
+    /// already that shallow.
+    pub fn ancestor(&self, depth: usize) -> Self {
+        let mut ancestor = *self;
+        ancestor.truncate(depth.min(self.depth()));
+        ancestor
+    }
+
+    /// True if `other` lies strictly below this address.
+    pub fn is_ancestor_of(&self, other: &Self) -> bool {
+        self.depth < other.depth && other.ancestor(self.depth()) == *self
+    }
+
+    /// The deepest address that is a prefix of both.
+    pub fn common_ancestor(&self, other: &Self) -> Self {
+        let shared = self
+            .digits()
+            .zip(other.digits())
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.ancestor(shared)
+    }
+
+    /// The next address at the same depth in address order, if any.
+    pub fn successor(&self) -> Option<Self> {
+        let level = (0..self.depth()).rev().find(|&level| self.digit(level) != Some(3))?;
+        let mut next = self.ancestor(level);
+        next = next.child(self.digit(level)? + 1)?;
+        next.depth = self.depth;
+        Some(next)
+    }
+
+    /// The edge-adjacent triangle at the same depth across `edge`, or `None`
+    /// on the boundary of the root triangle or for an edge index above 2.
+    ///
+    /// Edge `e` is the one opposite vertex `e`, with vertices numbered as in
+    /// [`Triangle::subdivide`](crate::geometry::triangle::Triangle::subdivide).
+    /// Only the digits are read: climb until the edge is shared by two
+    /// siblings, step across, then mirror the digits climbed past.
+    pub fn neighbour(&self, edge: u8) -> Option<Self> {
+        self.neighbour_across(edge).map(|(neighbour, _)| neighbour)
+    }
+
+    /// Same-depth neighbours across each edge that has one.
+    pub fn neighbours(&self) -> Vec<Self> {
+        (0..3).filter_map(|edge| self.neighbour(edge)).collect()
+    }
+
+    /// Triangles at `depth` that share part of an edge with this one.
+    ///
+    /// Coarser neighbours are the ancestors of the same-depth ones. Finer
+    /// neighbours are their descendants along the shared edge, of which there
+    /// are `2^(depth - self.depth())` per edge, so at most
+    /// [`MAX_NEIGHBOUR_REFINEMENT`] levels finer may be asked for.
+    pub fn neighbours_at_depth(&self, depth: usize) -> Result<Vec<Self>, AddressError> {
+        if depth > MAX_DEPTH {
+            return Err(AddressError::TooDeep(depth));
+        }
+        let refinement = depth.saturating_sub(self.depth());
+        if refinement > MAX_NEIGHBOUR_REFINEMENT {
+            return Err(AddressError::RefinementTooDeep(refinement));
+        }
+        let mut found = Vec::new();
+        for edge in 0..3 {
+            let Some((neighbour, shared)) = self.neighbour_across(edge) else {
+                continue;
+            };
+            if depth <= self.depth() {
+                let coarse = neighbour.ancestor(depth);
+                if coarse != self.ancestor(depth) && !found.contains(&coarse) {
+                    found.push(coarse);
+                }
+                continue;
+            }
+            // Children on an edge are the corners at its two ends and keep its index.
+            let ends = [(shared + 1) % 3, (shared + 2) % 3];
+            let mut frontier = vec![neighbour];
+            for _ in self.depth()..depth {
+                frontier = frontier.iter().flat_map(|t| ends.map(|d| t.child(d).unwrap())).collect();
+            }
+            found.extend(frontier);
+        }
+        Ok(found)
+    }
+
+    /// True if the two triangles share part of an edge, at any depths.
+    pub fn is_adjacent_to(&self, other: &Self) -> bool {
+        let (fine, coarse) = if self.depth >= other.depth { (self, other) } else { (other, self) };
+        // Never refines, as `coarse` is no deeper than `fine`.
+        fine.neighbours_at_depth(coarse.depth()).is_ok_and(|found| found.contains(coarse))
+    }
+
+    /// The neighbour across `edge` and the index of the shared edge in it.
+    fn neighbour_across(&self, edge: u8) -> Option<(Self, u8)> {
+        if edge > 2 {
+            return None;
+        }
+        let mut level = self.depth();
+        let (pivot, shared) = loop {
+            level = level.checked_sub(1)?;
+            let digit = self.digit(level)?;
+            if let Some((sibling, sibling_edge)) = Self::internal_edge(digit, edge) {
+                break (self.ancestor(level).child(sibling)?, sibling_edge);
+            }
+        };
+        // Below the pivot this triangle hugs `edge` and the neighbour hugs
+        // `shared`. Both run counter-clockwise, so they traverse the common
+        // edge in opposite directions and corners swap ends.
+        let mut neighbour = pivot;
+        for digit in self.digits().skip(level + 1) {
+            let mirrored = if digit == (edge + 1) % 3 { (shared + 2) % 3 } else { (shared + 1) % 3 };
+            neighbour = neighbour.child(mirrored)?;
+        }
+        Some((neighbour, shared))
+    }
+
+    /// The sibling and its edge on the other side of a child's edge, if that
+    /// edge is interior to the parent. Corner child `d` touches the middle
+    /// child across its edge `d`; its other two edges lie on the parent's.
+    fn internal_edge(digit: u8, edge: u8) -> Option<(u8, u8)> {
+        match (digit, edge) {
+            (0, 0) => Some((3, 1)),
+            (1, 1) => Some((3, 2)),
+            (2, 2) => Some((3, 0)),
+            (3, 1) => Some((0, 0)),
+            (3, 2) => Some((1, 1)),
+            (3, 0) => Some((2, 2)),
+            _ => None,
+        }
+    }
+
+    /// Packed digits, two bits per level, most significant first.
+    pub fn as_bytes(&self) -> &[u8; MAX_DEPTH / 4] {
+        &self.packed
+    }
+
+    fn shift(level: usize) -> u32 {
+        6 - 2 * (level % 4) as u32
+    }
+
+    fn truncate(&mut self, depth: usize) {
+        let (full_bytes, partial_digits) = (depth / 4, depth % 4);
+        let mut clear_from = full_bytes;
+        if partial_digits > 0 {
+            self.packed[full_bytes] &= 0xff << (8 - 2 * partial_digits);
+            clear_from += 1;
+        }
+        self.packed[clear_from..].fill(0);
+        self.depth = depth as u8;
+    }
+}
+
+impl Default for FractalAddress {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+/// Canonical form: digits joined with dots, e.g. `0.1.2.3`; the root is empty.
+impl fmt::Display for FractalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (level, digit) in self.digits().enumerate() {
+            if level > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", digit)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FractalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FractalAddress({})", self)
+    }
+}
+
+impl FromStr for FractalAddress {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Self::root());
+        }
+        let digits = s
+            .split('.')
+            .map(|part| match part.as_bytes() {
+                [c @ b'0'..=b'3'] => Ok(c - b'0'),
+                _ => Err(AddressError::Malformed(s.to_string())),
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+        Self::from_digits(&digits)
+    }
+}
+
+impl Serialize for FractalAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FractalAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(digits: &[u8]) -> FractalAddress {
+        FractalAddress::from_digits(digits).unwrap()
+    }
+
+    #[test]
+    fn test_digits_round_trip() {
+        let digits = vec![0, 1, 2, 3, 3, 2, 1];
+        assert_eq!(addr(&digits).to_digits(), digits);
+        assert_eq!(addr(&digits).depth(), 7);
+    }
+
+    #[test]
+    fn test_rejects_bad_digit_and_depth() {
+        assert_eq!(FractalAddress::from_digits(&[0, 4]), Err(AddressError::InvalidDigit(4)));
+        assert_eq!(FractalAddress::from_digits(&[0; MAX_DEPTH + 1]), Err(AddressError::TooDeep(MAX_DEPTH + 1)));
+        assert!(addr(&[0; MAX_DEPTH]).child(0).is_none());
+    }
+
+    #[test]
+    fn test_navigation() {
+        let a = addr(&[2, 1, 3]);
+        assert_eq!(a.parent(), Some(addr(&[2, 1])));
+        assert_eq!(FractalAddress::root().parent(), None);
+        assert_eq!(a.children().unwrap()[2], addr(&[2, 1, 3, 2]));
+        assert_eq!(a.siblings(), Some([addr(&[2, 1, 0]), addr(&[2, 1, 1]), addr(&[2, 1, 2])]));
+        assert_eq!(a.common_ancestor(&addr(&[2, 1, 0, 0])), addr(&[2, 1]));
+        assert_eq!(a.common_ancestor(&addr(&[3])), FractalAddress::root());
+    }
+
+    #[test]
+    fn test_is_ancestor_of() {
+        assert!(addr(&[1]).is_ancestor_of(&addr(&[1, 0, 2])));
+        assert!(FractalAddress::root().is_ancestor_of(&addr(&[3])));
+        assert!(!addr(&[1]).is_ancestor_of(&addr(&[1])));
+        assert!(!addr(&[1, 0]).is_ancestor_of(&addr(&[1])));
+        assert!(!addr(&[0]).is_ancestor_of(&addr(&[1, 0])));
+    }
+
+    #[test]
+    fn test_ordering_is_depth_first() {
+        let mut addresses = vec![addr(&[1]), addr(&[0, 3]), addr(&[0]), FractalAddress::root(), addr(&[0, 0]), addr(&[1, 0])];
+        addresses.sort();
+        assert_eq!(addresses, vec![FractalAddress::root(), addr(&[0]), addr(&[0, 0]), addr(&[0, 3]), addr(&[1]), addr(&[1, 0])]);
+    }
+
+    #[test]
+    fn test_successor() {
+        assert_eq!(addr(&[0, 3]).successor(), Some(addr(&[1, 0])));
+        assert_eq!(addr(&[2, 1]).successor(), Some(addr(&[2, 2])));
+        assert_eq!(addr(&[3, 3]).successor(), None);
+    }
+
+    #[test]
+    fn test_string_round_trip() {
+        let a = addr(&[0, 1, 2, 3]);
+        assert_eq!(a.to_string(), "0.1.2.3");
+        assert_eq!("0.1.2.3".parse::<FractalAddress>(), Ok(a));
+        assert_eq!("".parse::<FractalAddress>(), Ok(FractalAddress::root()));
+        assert!("0.4".parse::<FractalAddress>().is_err());
+        assert!("01".parse::<FractalAddress>().is_err());
+        assert!("0..1".parse::<FractalAddress>().is_err());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let a = addr(&[3, 0, 2]);
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, "\"3.0.2\"");
+        assert_eq!(serde_json::from_str::<FractalAddress>(&json).unwrap(), a);
+    }
+
+    #[test]
+    fn test_neighbours_of_children() {
+        assert_eq!(addr(&[3]).neighbours(), vec![addr(&[2]), addr(&[0]), addr(&[1])]);
+        assert_eq!(addr(&[0]).neighbours(), vec![addr(&[3])]);
+        assert!(FractalAddress::root().neighbours().is_empty());
+        assert_eq!(addr(&[0, 1]).neighbour(0), Some(addr(&[3, 0])));
+        assert_eq!(addr(&[0, 1]).neighbour(2), None);
+        assert_eq!(addr(&[0]).neighbour(3), None);
+    }
+
+    /// Checks every address against exact geometric adjacency of a triangle
+    /// whose subdivision stays exact in `Decimal`.
+    fn assert_matches_geometry(depth: usize, other_depth: usize) {
+        use crate::geometry::point::Point;
+        use crate::geometry::subdivision::Subdivisions;
+        use crate::geometry::triangle::Triangle;
+        use rust_decimal::Decimal;
+        let root = Triangle::new(
+            Point::new(Decimal::ZERO, Decimal::ZERO),
+            Point::new(Decimal::from(64), Decimal::ZERO),
+            Point::new(Decimal::ZERO, Decimal::from(64)),
+        );
+        let triangle_at = |address: &FractalAddress| address.digits().fold(root, |t, digit| t.subdivide()[digit as usize]);
+        let at_depth = |depth| Subdivisions::at_depth(FractalAddress::root(), depth).map(|(_, a)| (triangle_at(&a), a));
+        let others: Vec<_> = at_depth(other_depth).collect();
+        for (triangle, address) in at_depth(depth) {
+            let mut expected: Vec<_> = others.iter().filter(|(t, _)| t.shares_edge(&triangle)).map(|(_, a)| *a).collect();
+            let mut found = address.neighbours_at_depth(other_depth).unwrap();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "neighbours of {} at depth {}", address, other_depth);
+            assert!(found.iter().all(|other| other.is_adjacent_to(&address) && address.is_adjacent_to(other)));
+        }
+    }
+
+    #[test]
+    fn test_same_depth_neighbours_match_geometry() {
+        assert_matches_geometry(3, 3);
+    }
+
+    #[test]
+    fn test_coarser_and_finer_neighbours_match_geometry() {
+        assert_matches_geometry(3, 1);
+        assert_matches_geometry(2, 4);
+    }
+
+    #[test]
+    fn test_neighbour_at_max_depth() {
+        let deep = addr(&[3; MAX_DEPTH]);
+        let neighbour = deep.neighbour(0).unwrap();
+        assert_eq!(neighbour.depth(), MAX_DEPTH);
+        assert_eq!(neighbour.neighbour(2), Some(deep));
+    }
+
+    #[test]
+    fn test_finer_neighbours_are_bounded() {
+        let shallow = addr(&[0]);
+        assert_eq!(shallow.neighbours_at_depth(1 + MAX_NEIGHBOUR_REFINEMENT).unwrap().len(), 1 << MAX_NEIGHBOUR_REFINEMENT);
+        assert_eq!(shallow.neighbours_at_depth(MAX_DEPTH), Err(AddressError::RefinementTooDeep(MAX_DEPTH - 1)));
+        assert_eq!(shallow.neighbours_at_depth(MAX_DEPTH + 1), Err(AddressError::TooDeep(MAX_DEPTH + 1)));
+        let mut digits = [0; MAX_DEPTH];
+        digits[0] = 3;
+        assert_eq!(addr(&digits).neighbours_at_depth(1).unwrap(), vec![addr(&[0]), addr(&[1])]);
+    }
+
+    #[test]
+    fn test_from_bytes_masks_trailing_bits() {
+        let bytes = [0xff; MAX_DEPTH / 4];
+        let a = FractalAddress::from_bytes(&bytes, 5).unwrap();
+        assert_eq!(a, addr(&[3; 5]));
+    }
+}
@@ -0,0 +1,164 @@
+//! Exact coordinates for the subdivision of the genesis triangle.
+//!
+//! Every triangle produced by subdividing the genesis triangle has its
+//! vertices on the triangular lattice of spacing `2^-depth`. Points are kept
+//! in the lattice basis `e1 = (1, 0)`, `e2 = (1/2, sqrt(3)/2)` as integers
+//! over `2^depth`, so subdivision is integer arithmetic and never rounds.
+//! Cartesian [`Triangle`]s are derived from these coordinates the same way on
+//! every node.
+
+use crate::geometry::hierarchy::{FractalAddress, MAX_DEPTH};
+use crate::geometry::point::Point;
+use crate::geometry::triangle::{Triangle, SQRT_3_HALF};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+// Depth beyond which lattice numerators are rounded down before conversion
+// so that they fit a `Decimal` mantissa.
+const DECIMAL_BITS: usize = 90;
+
+/// A triangle of the genesis subdivision in exact lattice coordinates.
+///
+/// The lower-left corner of the triangle's bounding cell is `anchor`, and
+/// each vertex is `anchor` plus a 0/1 offset per axis, all in units of
+/// `2^-depth`. Storing the corner and offsets apart keeps every field in
+/// range down to [`MAX_DEPTH`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LatticeTriangle {
+    depth: u8,
+    anchor: [u128; 2],
+    offsets: [[u8; 2]; 3],
+}
+
+impl LatticeTriangle {
+    /// The genesis triangle: `(0, 0)`, `(1, 0)` and `(1/2, sqrt(3)/2)`.
+    pub const fn genesis() -> Self {
+        LatticeTriangle { depth: 0, anchor: [0, 0], offsets: [[0, 0], [1, 0], [0, 1]] }
+    }
+
+    /// The subdivision triangle at `address`, computed in `O(depth)`.
+    pub fn at(address: &FractalAddress) -> Self {
+        address.digits().fold(Self::genesis(), |triangle, digit| triangle.child(digit))
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+
+    /// Children in the same order as [`Triangle::subdivide`], or `None` at
+    /// [`MAX_DEPTH`].
+    pub fn subdivide(&self) -> Option<[Self; 4]> {
+        (self.depth() < MAX_DEPTH).then(|| [self.child(0), self.child(1), self.child(2), self.child(3)])
+    }
+
+    /// Points up (like the genesis triangle) rather than down.
+    pub fn is_upright(&self) -> bool {
+        self.offsets.iter().filter(|o| o[1] == 0).count() == 2
+    }
+
+    /// Exactly `4^-depth` of the genesis area; only the `Decimal` is rounded.
+    pub fn area(&self) -> Decimal {
+        let mut area = Triangle::from(Self::genesis()).area();
+        for _ in 0..self.depth {
+            area /= Decimal::from(4);
+        }
+        area
+    }
+
+    fn child(&self, digit: u8) -> Self {
+        let [a, b, c] = self.offsets;
+        let mid = |p: [u8; 2], q: [u8; 2]| [p[0] + q[0], p[1] + q[1]];
+        let corner = |p: [u8; 2]| [2 * p[0], 2 * p[1]];
+        let offsets = match digit {
+            0 => [corner(a), mid(a, b), mid(c, a)],
+            1 => [mid(a, b), corner(b), mid(b, c)],
+            2 => [mid(c, a), mid(b, c), corner(c)],
+            _ => [mid(a, b), mid(b, c), mid(c, a)],
+        };
+        let shift = [0, 1].map(|axis| offsets.iter().map(|o| o[axis]).min().unwrap_or(0));
+        LatticeTriangle {
+            depth: self.depth + 1,
+            anchor: [0, 1].map(|axis| 2 * self.anchor[axis] + shift[axis] as u128),
+            offsets: offsets.map(|o| [o[0] - shift[0], o[1] - shift[1]]),
+        }
+    }
+
+    fn vertex(&self, index: usize) -> Point {
+        let [u, v] = [0, 1].map(|axis| dyadic(self.anchor[axis], self.offsets[index][axis], self.depth()));
+        Point::new(u + v / Decimal::from(2), v * SQRT_3_HALF)
+    }
+}
+
+impl From<LatticeTriangle> for Triangle {
+    fn from(triangle: LatticeTriangle) -> Self {
+        Triangle::new(triangle.vertex(0), triangle.vertex(1), triangle.vertex(2))
+    }
+}
+
+/// `(anchor + offset) / 2^depth` as a `Decimal`, rounding toward zero past
+/// [`DECIMAL_BITS`] bits of precision.
+fn dyadic(anchor: u128, offset: u8, depth: usize) -> Decimal {
+    let dropped = depth.saturating_sub(DECIMAL_BITS);
+    // Split the sum so that anchor + offset cannot overflow at MAX_DEPTH.
+    let low = anchor & ((1u128 << dropped) - 1);
+    let numerator = (anchor >> dropped) + ((low + offset as u128) >> dropped);
+    let denominator = Decimal::from(1u128 << (depth - dropped));
+    Decimal::from(numerator) / denominator
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::triangle::genesis_triangle;
+
+    fn addr(digits: &[u8]) -> FractalAddress {
+        FractalAddress::from_digits(digits).unwrap()
+    }
+
+    #[test]
+    fn test_genesis_matches_cartesian_genesis() {
+        assert_eq!(Triangle::from(LatticeTriangle::genesis()), genesis_triangle());
+    }
+
+    #[test]
+    fn test_children_match_decimal_subdivision() {
+        let children = LatticeTriangle::genesis().subdivide().unwrap();
+        let expected = genesis_triangle().subdivide();
+        for (child, expected) in children.iter().zip(expected.iter()) {
+            assert_eq!(Triangle::from(*child), *expected);
+        }
+    }
+
+    #[test]
+    fn test_orientation() {
+        assert!(LatticeTriangle::genesis().is_upright());
+        assert!(LatticeTriangle::at(&addr(&[0])).is_upright());
+        assert!(!LatticeTriangle::at(&addr(&[3])).is_upright());
+        assert!(LatticeTriangle::at(&addr(&[3, 3])).is_upright());
+    }
+
+    #[test]
+    fn test_distinct_addresses_give_distinct_triangles() {
+        let a = LatticeTriangle::at(&addr(&[0, 1, 2, 3, 0, 1]));
+        let b = LatticeTriangle::at(&addr(&[0, 1, 2, 3, 0, 2]));
+        assert_ne!(a, b);
+        assert_eq!(a, LatticeTriangle::at(&addr(&[0, 1, 2, 3, 0, 1])));
+    }
+
+    #[test]
+    fn test_exact_at_max_depth() {
+        let deepest = LatticeTriangle::at(&addr(&[1; MAX_DEPTH]));
+        assert_eq!(deepest.depth(), MAX_DEPTH);
+        assert!(deepest.subdivide().is_none());
+        // The last corner child converges on vertex b = (1, 0).
+        let t = Triangle::from(deepest);
+        assert_eq!(t.b, Point::new(Decimal::ONE, Decimal::ZERO));
+        assert!(t.a.x < Decimal::ONE);
+    }
+
+    #[test]
+    fn test_area_is_quartered() {
+        let t = LatticeTriangle::at(&addr(&[2, 3]));
+        assert_eq!(t.area(), genesis_triangle().area() / Decimal::from(16));
+    }
+}
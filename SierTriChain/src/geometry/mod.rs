@@ -1,5 +1,6 @@
 pub mod area;
 pub mod hierarchy;
+pub mod lattice;
 pub mod point;
 pub mod subdivision;
 pub mod transform;
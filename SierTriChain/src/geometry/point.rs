@@ -19,10 +19,16 @@ impl Point {
         }
     }
 
-    pub fn distance(&self, other: &Point) -> Decimal {
-        let dx = self.x - other.x;
-        let dy = self.y - other.y;
-        (dx * dx + dy * dy).sqrt().unwrap()
+    /// Squared distance, exact unless it overflows `Decimal`.
+    pub fn distance_squared(&self, other: &Point) -> Option<Decimal> {
+        let dx = self.x.checked_sub(other.x)?;
+        let dy = self.y.checked_sub(other.y)?;
+        dx.checked_mul(dx)?.checked_add(dy.checked_mul(dy)?)
+    }
+
+    /// Euclidean distance, or `None` if the intermediate square overflows.
+    pub fn distance(&self, other: &Point) -> Option<Decimal> {
+        self.distance_squared(other)?.sqrt()
     }
 }
 
@@ -50,6 +56,13 @@ mod tests {
     fn test_distance() {
         let p1 = Point::new(dec!(0.0), dec!(0.0));
         let p2 = Point::new(dec!(3.0), dec!(4.0));
-        assert_eq!(p1.distance(&p2), dec!(5.0));
+        assert_eq!(p1.distance(&p2), Some(dec!(5.0)));
+    }
+
+    #[test]
+    fn test_distance_overflow_is_none() {
+        let p1 = Point::new(Decimal::MIN, Decimal::ZERO);
+        let p2 = Point::new(Decimal::MAX, Decimal::ZERO);
+        assert_eq!(p1.distance(&p2), None);
     }
 }
\ No newline at end of file
@@ -1,49 +1,272 @@
+//! Walking the subdivision of the genesis triangle.
+//!
+//! Everything here is built on the exact [`LatticeTriangle`] kernel, so the
+//! triangles handed to mining, territory claims and the VM are the ones
+//! consensus checks, at any depth.
+
+use crate::geometry::lattice::LatticeTriangle;
 use crate::geometry::triangle::Triangle;
-use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct FractalAddress(pub Vec<u8>);
+pub use crate::geometry::hierarchy::FractalAddress;
+use crate::geometry::hierarchy::MAX_DEPTH;
+
+/// Order in which [`Subdivisions`] visits the subdivision tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traversal {
+    /// Pre-order: each triangle is followed by all of its descendants.
+    DepthFirst,
+    /// Level order: every triangle at one depth before any at the next.
+    BreadthFirst,
+}
+
+/// The triangle at `address` in Cartesian coordinates, in `O(depth)`.
+pub fn triangle_at(address: &FractalAddress) -> Triangle {
+    Triangle::from(LatticeTriangle::at(address))
+}
+
+/// The address of `triangle` if it is one of the subdivision triangles.
+///
+/// Follows the child holding the triangle's centroid down from the genesis
+/// triangle until the two coincide, or [`MAX_DEPTH`] is passed.
+pub fn address_of(triangle: &Triangle) -> Option<FractalAddress> {
+    let centroid = triangle.centroid();
+    let (mut lattice, mut address) = (LatticeTriangle::genesis(), FractalAddress::root());
+    loop {
+        let cartesian = Triangle::from(lattice);
+        if cartesian == *triangle {
+            return Some(address);
+        }
+        let children = lattice.subdivide()?;
+        let digit = (0..4).find(|&d| Triangle::from(children[d]).strictly_contains_point(&centroid))?;
+        lattice = children[digit];
+        address = address.child(digit as u8)?;
+    }
+}
+
+/// The first address at `depth` below `root`: `root` followed by zeros.
+fn first_below(root: &FractalAddress, depth: usize) -> Option<FractalAddress> {
+    FractalAddress::from_bytes(root.as_bytes(), depth).ok()
+}
 
-pub fn fractal_subdivide(
-    triangle: &Triangle,
-    depth: usize,
+/// Lazily walks the subdivision tree below an address.
+///
+/// Only the current path is held in memory and addresses are `Copy`, so the
+/// iterator can be driven at depths where the full `4^depth` set of triangles
+/// would never fit. Depths are counted from the genesis triangle and stop at
+/// [`MAX_DEPTH`].
+pub struct Subdivisions {
+    root: FractalAddress,
+    min_depth: usize,
+    max_depth: usize,
+    order: Traversal,
+    started: bool,
+    // Depth-first: children of every triangle on the current path and the
+    // next child to visit in each.
+    frames: Vec<([LatticeTriangle; 4], u8)>,
+    // The most recently visited address.
     address: FractalAddress,
-    triangles: &mut Vec<(Triangle, FractalAddress)>,
-) {
-    if depth == 0 {
-        triangles.push((*triangle, address));
-        return;
+}
+
+impl Subdivisions {
+    /// `root` and every triangle below it down to `max_depth`.
+    pub fn new(root: FractalAddress, max_depth: usize, order: Traversal) -> Self {
+        Self::between(root, root.depth(), max_depth, order)
+    }
+
+    /// The triangles at exactly `depth` below `root`, in address order.
+    pub fn at_depth(root: FractalAddress, depth: usize) -> Self {
+        Self::between(root, depth, depth, Traversal::DepthFirst)
     }
 
-    let subdivided = triangle.subdivide();
+    fn between(root: FractalAddress, min_depth: usize, max_depth: usize, order: Traversal) -> Self {
+        let max_depth = max_depth.min(MAX_DEPTH);
+        Self {
+            root,
+            // An empty range when asked for depths above the root.
+            min_depth: min_depth.max(root.depth()),
+            max_depth,
+            order,
+            started: false,
+            frames: Vec::with_capacity(max_depth.saturating_sub(root.depth())),
+            address: root,
+        }
+    }
+
+    fn next_depth_first(&mut self) -> Option<(LatticeTriangle, FractalAddress)> {
+        let base = self.root.depth();
+        if !self.started {
+            self.started = true;
+            if self.min_depth > self.max_depth {
+                return None;
+            }
+            let root = LatticeTriangle::at(&self.root);
+            if self.max_depth > base {
+                self.frames.push((root.subdivide()?, 0));
+            }
+            if self.min_depth == base {
+                return Some((root, self.root));
+            }
+        }
+        loop {
+            let level = base + self.frames.len().checked_sub(1)?;
+            let (children, next) = self.frames.last_mut()?;
+            if *next == 4 {
+                self.frames.pop();
+                continue;
+            }
+            let digit = *next;
+            let child = children[digit as usize];
+            *next += 1;
+
+            self.address = self.address.ancestor(level).child(digit)?;
+            let depth = level + 1;
+            if depth < self.max_depth {
+                self.frames.push((child.subdivide()?, 0));
+            }
+            if depth >= self.min_depth {
+                return Some((child, self.address));
+            }
+        }
+    }
+
+    fn next_breadth_first(&mut self) -> Option<(LatticeTriangle, FractalAddress)> {
+        let next = if !self.started {
+            self.started = true;
+            if self.min_depth > self.max_depth {
+                return None;
+            }
+            first_below(&self.root, self.min_depth)?
+        } else {
+            match self.address.successor().filter(|next| next.ancestor(self.root.depth()) == self.root) {
+                Some(next) => next,
+                // Past the last triangle of this level: first triangle of the next.
+                None if self.address.depth() < self.max_depth => first_below(&self.root, self.address.depth() + 1)?,
+                None => return None,
+            }
+        };
+        self.address = next;
+        Some((LatticeTriangle::at(&next), next))
+    }
+}
 
-    for i in 0..4 {
-        let mut new_address = address.0.clone();
-        new_address.push(i as u8);
-        fractal_subdivide(&subdivided[i], depth - 1, FractalAddress(new_address), triangles);
+impl Iterator for Subdivisions {
+    type Item = (LatticeTriangle, FractalAddress);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.order {
+            Traversal::DepthFirst => self.next_depth_first(),
+            Traversal::BreadthFirst => self.next_breadth_first(),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::geometry::point::Point;
-    use crate::geometry::triangle::Triangle;
-    use rust_decimal_macros::dec;
+    use crate::geometry::triangle::genesis_triangle;
+
+    fn addr(digits: &[u8]) -> FractalAddress {
+        FractalAddress::from_digits(digits).unwrap()
+    }
+
+    fn root() -> FractalAddress {
+        FractalAddress::root()
+    }
 
     #[test]
-    fn test_fractal_subdivide() {
-        let a = Point::new(dec!(0.0), dec!(0.0));
-        let b = Point::new(dec!(1.0), dec!(0.0));
-        let c = Point::new(dec!(0.5), dec!(0.86602540378));
-        let t = Triangle::new(a, b, c);
+    fn test_at_depth_counts() {
+        assert_eq!(Subdivisions::at_depth(root(), 0).count(), 1);
+        assert_eq!(Subdivisions::at_depth(root(), 1).count(), 4);
+        assert_eq!(Subdivisions::at_depth(root(), 2).count(), 16);
+        assert_eq!(Subdivisions::at_depth(addr(&[2]), 3).count(), 16);
+        assert_eq!(Subdivisions::at_depth(addr(&[2, 1]), 1).count(), 0);
+    }
 
-        let mut triangles = Vec::new();
-        fractal_subdivide(&t, 1, FractalAddress(vec![]), &mut triangles);
-        assert_eq!(triangles.len(), 4);
+    #[test]
+    fn test_at_depth_is_in_address_order() {
+        let addresses: Vec<_> = Subdivisions::at_depth(root(), 2).map(|(_, a)| a.to_digits()).collect();
+        let mut sorted = addresses.clone();
+        sorted.sort();
+        assert_eq!(addresses, sorted);
+        assert_eq!(addresses[5], vec![1, 1]);
+    }
+
+    #[test]
+    fn test_depth_first_is_pre_order() {
+        let addresses: Vec<_> = Subdivisions::new(root(), 2, Traversal::DepthFirst)
+            .map(|(_, a)| a.to_digits())
+            .take(4)
+            .collect();
+        assert_eq!(addresses, vec![vec![], vec![0], vec![0, 0], vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_breadth_first_is_level_order() {
+        let addresses: Vec<_> = Subdivisions::new(root(), 2, Traversal::BreadthFirst)
+            .map(|(_, a)| a.to_digits())
+            .collect();
+        assert_eq!(addresses.len(), 1 + 4 + 16);
+        assert_eq!(&addresses[..6], &[vec![], vec![0], vec![1], vec![2], vec![3], vec![0, 0]]);
+        assert!(addresses.windows(2).all(|w| w[0].len() <= w[1].len()));
+    }
 
-        let mut triangles = Vec::new();
-        fractal_subdivide(&t, 2, FractalAddress(vec![]), &mut triangles);
-        assert_eq!(triangles.len(), 16);
+    #[test]
+    fn test_traversals_visit_same_triangles() {
+        for start in [root(), addr(&[1, 3])] {
+            let mut dfs: Vec<_> = Subdivisions::new(start, 5, Traversal::DepthFirst).map(|(_, a)| a.to_digits()).collect();
+            let mut bfs: Vec<_> = Subdivisions::new(start, 5, Traversal::BreadthFirst).map(|(_, a)| a.to_digits()).collect();
+            dfs.sort();
+            bfs.sort();
+            assert_eq!(dfs, bfs);
+        }
+    }
+
+    #[test]
+    fn test_walks_stay_below_root() {
+        let region = addr(&[1, 3]);
+        for order in [Traversal::DepthFirst, Traversal::BreadthFirst] {
+            let walked: Vec<_> = Subdivisions::new(region, 4, order).map(|(_, a)| a).collect();
+            assert_eq!(walked.len(), 1 + 4 + 16);
+            assert!(walked.iter().all(|a| a.ancestor(2) == region));
+        }
+    }
+
+    #[test]
+    fn test_iterator_matches_lattice_kernel() {
+        for (triangle, address) in Subdivisions::new(root(), 3, Traversal::DepthFirst) {
+            assert_eq!(triangle, LatticeTriangle::at(&address));
+            assert_eq!(triangle_at(&address), Triangle::from(triangle));
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_deep_iteration_is_lazy() {
+        let mut deep = Subdivisions::at_depth(root(), 40);
+        let (_, first) = deep.next().unwrap();
+        let (_, second) = deep.next().unwrap();
+        assert_eq!(first.depth(), 40);
+        assert_eq!(second.digit(39), Some(1));
+    }
+
+    #[test]
+    fn test_stops_at_max_depth() {
+        let deepest = addr(&[2; MAX_DEPTH]);
+        assert_eq!(Subdivisions::new(deepest, MAX_DEPTH + 5, Traversal::DepthFirst).count(), 1);
+        assert_eq!(Subdivisions::new(deepest, MAX_DEPTH + 5, Traversal::BreadthFirst).count(), 1);
+    }
+
+    #[test]
+    fn test_triangle_at_root() {
+        assert_eq!(triangle_at(&root()), genesis_triangle());
+    }
+
+    #[test]
+    fn test_address_of_inverts_triangle_at() {
+        for digits in [&[][..], &[3], &[0, 1, 2, 3, 3, 0], &[1; 40]] {
+            assert_eq!(address_of(&triangle_at(&addr(digits))), Some(addr(digits)));
+        }
+        let [corner, ..] = triangle_at(&addr(&[2])).subdivide();
+        let shifted = Triangle::new(corner.b, corner.c, corner.a);
+        assert_eq!(address_of(&shifted), None);
+    }
+}
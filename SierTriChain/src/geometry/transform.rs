@@ -0,0 +1,238 @@
+//! Affine transformations of points and triangles.
+//!
+//! All arithmetic is checked `Decimal`, so a transform gives the same result
+//! on every node and reports overflow as `None` instead of panicking.
+
+use crate::geometry::point::Point;
+use crate::geometry::triangle::Triangle;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// The map `(x, y) -> (a*x + b*y + tx, c*x + d*y + ty)`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AffineTransform {
+    pub a: Decimal,
+    pub b: Decimal,
+    pub c: Decimal,
+    pub d: Decimal,
+    pub tx: Decimal,
+    pub ty: Decimal,
+}
+
+impl AffineTransform {
+    pub const IDENTITY: AffineTransform = AffineTransform {
+        a: Decimal::ONE,
+        b: Decimal::ZERO,
+        c: Decimal::ZERO,
+        d: Decimal::ONE,
+        tx: Decimal::ZERO,
+        ty: Decimal::ZERO,
+    };
+
+    pub fn translation(dx: Decimal, dy: Decimal) -> Self {
+        AffineTransform { tx: dx, ty: dy, ..Self::IDENTITY }
+    }
+
+    /// Counter-clockwise rotation by `angle` radians about the origin.
+    pub fn rotation(angle: Decimal) -> Option<Self> {
+        let (sin, cos) = sin_cos(angle)?;
+        Some(AffineTransform { a: cos, b: -sin, c: sin, d: cos, ..Self::IDENTITY })
+    }
+
+    /// Counter-clockwise rotation by `angle` radians about `pivot`.
+    pub fn rotation_about(angle: Decimal, pivot: Point) -> Option<Self> {
+        Self::about(Self::rotation(angle)?, pivot)
+    }
+
+    pub fn scaling(sx: Decimal, sy: Decimal) -> Self {
+        AffineTransform { a: sx, d: sy, ..Self::IDENTITY }
+    }
+
+    pub fn uniform_scaling(factor: Decimal) -> Self {
+        Self::scaling(factor, factor)
+    }
+
+    /// Scaling by `sx`, `sy` that leaves `pivot` fixed.
+    pub fn scaling_about(sx: Decimal, sy: Decimal, pivot: Point) -> Option<Self> {
+        Self::about(Self::scaling(sx, sy), pivot)
+    }
+
+    /// Reflection across the line through `p` and `q`, or `None` if they coincide.
+    pub fn reflection(p: Point, q: Point) -> Option<Self> {
+        let dx = q.x.checked_sub(p.x)?;
+        let dy = q.y.checked_sub(p.y)?;
+        let norm = dx.checked_mul(dx)?.checked_add(dy.checked_mul(dy)?)?;
+        if norm.is_zero() {
+            return None;
+        }
+        // Householder form: reflect the direction (dx, dy) onto itself.
+        let two = Decimal::TWO;
+        let a = dx.checked_mul(dx)?.checked_sub(dy.checked_mul(dy)?)?.checked_div(norm)?;
+        let b = two.checked_mul(dx)?.checked_mul(dy)?.checked_div(norm)?;
+        let linear = AffineTransform { a, b, c: b, d: -a, ..Self::IDENTITY };
+        Self::about(linear, p)
+    }
+
+    /// The transform that applies `self` and then `next`.
+    pub fn then(&self, next: &AffineTransform) -> Option<Self> {
+        let dot = |x: Decimal, y: Decimal, u: Decimal, v: Decimal| x.checked_mul(u)?.checked_add(y.checked_mul(v)?);
+        Some(AffineTransform {
+            a: dot(next.a, next.b, self.a, self.c)?,
+            b: dot(next.a, next.b, self.b, self.d)?,
+            c: dot(next.c, next.d, self.a, self.c)?,
+            d: dot(next.c, next.d, self.b, self.d)?,
+            tx: dot(next.a, next.b, self.tx, self.ty)?.checked_add(next.tx)?,
+            ty: dot(next.c, next.d, self.tx, self.ty)?.checked_add(next.ty)?,
+        })
+    }
+
+    pub fn determinant(&self) -> Option<Decimal> {
+        self.a.checked_mul(self.d)?.checked_sub(self.b.checked_mul(self.c)?)
+    }
+
+    /// The inverse map, or `None` if the transform is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant()?;
+        if det.is_zero() {
+            return None;
+        }
+        let a = self.d.checked_div(det)?;
+        let b = (-self.b).checked_div(det)?;
+        let c = (-self.c).checked_div(det)?;
+        let d = self.a.checked_div(det)?;
+        let linear = AffineTransform { a, b, c, d, ..Self::IDENTITY };
+        let origin = linear.apply(&Point::new(self.tx, self.ty))?;
+        Some(AffineTransform { tx: -origin.x, ty: -origin.y, ..linear })
+    }
+
+    pub fn apply(&self, p: &Point) -> Option<Point> {
+        let x = self.a.checked_mul(p.x)?.checked_add(self.b.checked_mul(p.y)?)?.checked_add(self.tx)?;
+        let y = self.c.checked_mul(p.x)?.checked_add(self.d.checked_mul(p.y)?)?.checked_add(self.ty)?;
+        Some(Point::new(x, y))
+    }
+
+    pub fn apply_triangle(&self, t: &Triangle) -> Option<Triangle> {
+        Some(Triangle::new(self.apply(&t.a)?, self.apply(&t.b)?, self.apply(&t.c)?))
+    }
+
+    /// Conjugates `linear` by a translation so that it fixes `pivot`.
+    fn about(linear: AffineTransform, pivot: Point) -> Option<Self> {
+        AffineTransform::translation(-pivot.x, -pivot.y)
+            .then(&linear)?
+            .then(&AffineTransform::translation(pivot.x, pivot.y))
+    }
+}
+
+/// Sine and cosine to full `Decimal` precision.
+///
+/// `rust_decimal`'s own trigonometry is only good to about ten places, which
+/// is visible after a single rotation, so this sums the Taylor series after
+/// reducing the angle into `[-pi, pi]`.
+fn sin_cos(angle: Decimal) -> Option<(Decimal, Decimal)> {
+    let mut x = angle.checked_rem(Decimal::TWO_PI)?;
+    if x > Decimal::PI {
+        x -= Decimal::TWO_PI;
+    } else if x < -Decimal::PI {
+        x += Decimal::TWO_PI;
+    }
+    let (mut sin, mut cos) = (Decimal::ZERO, Decimal::ZERO);
+    // term = x^n / n!
+    let mut term = Decimal::ONE;
+    for n in 0u32.. {
+        if term.is_zero() {
+            break;
+        }
+        match n % 4 {
+            0 => cos += term,
+            1 => sin += term,
+            2 => cos -= term,
+            _ => sin -= term,
+        }
+        term = term * x / Decimal::from(n + 1);
+    }
+    Some((sin, cos))
+}
+
+impl Default for AffineTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn close(p: &Point, q: &Point) -> bool {
+        (p.x - q.x).abs() < dec!(1e-20) && (p.y - q.y).abs() < dec!(1e-20)
+    }
+
+    #[test]
+    fn test_translation() {
+        let t = AffineTransform::translation(dec!(1), dec!(-2));
+        assert_eq!(t.apply(&Point::new(dec!(3), dec!(4))), Some(Point::new(dec!(4), dec!(2))));
+    }
+
+    #[test]
+    fn test_quarter_turn_about_pivot() {
+        let t = AffineTransform::rotation_about(Decimal::HALF_PI, Point::new(dec!(1), dec!(1))).unwrap();
+        let p = t.apply(&Point::new(dec!(2), dec!(1))).unwrap();
+        assert!(close(&p, &Point::new(dec!(1), dec!(2))));
+    }
+
+    #[test]
+    fn test_scaling_about_pivot_keeps_pivot() {
+        let pivot = Point::new(dec!(2), dec!(3));
+        let t = AffineTransform::scaling_about(dec!(2), dec!(0.5), pivot).unwrap();
+        assert_eq!(t.apply(&pivot), Some(pivot));
+        assert_eq!(t.apply(&Point::new(dec!(3), dec!(5))), Some(Point::new(dec!(4), dec!(4))));
+    }
+
+    #[test]
+    fn test_reflection_across_diagonal() {
+        let t = AffineTransform::reflection(Point::new(dec!(0), dec!(0)), Point::new(dec!(1), dec!(1))).unwrap();
+        assert_eq!(t.apply(&Point::new(dec!(2), dec!(0))), Some(Point::new(dec!(0), dec!(2))));
+        assert!(AffineTransform::reflection(Point::new(dec!(1), dec!(1)), Point::new(dec!(1), dec!(1))).is_none());
+    }
+
+    #[test]
+    fn test_composition_order() {
+        let scale = AffineTransform::uniform_scaling(dec!(2));
+        let shift = AffineTransform::translation(dec!(1), dec!(0));
+        let p = Point::new(dec!(1), dec!(1));
+        assert_eq!(scale.then(&shift).unwrap().apply(&p), Some(Point::new(dec!(3), dec!(2))));
+        assert_eq!(shift.then(&scale).unwrap().apply(&p), Some(Point::new(dec!(4), dec!(2))));
+    }
+
+    #[test]
+    fn test_inverse_round_trip() {
+        let t = AffineTransform::scaling(dec!(2), dec!(4)).then(&AffineTransform::translation(dec!(1), dec!(3))).unwrap();
+        let p = Point::new(dec!(5), dec!(7));
+        let back = t.inverse().unwrap().apply(&t.apply(&p).unwrap()).unwrap();
+        assert_eq!(back, p);
+        assert!(AffineTransform::uniform_scaling(Decimal::ZERO).inverse().is_none());
+    }
+
+    #[test]
+    fn test_sin_cos_precision() {
+        let (sin, cos) = sin_cos(dec!(0.7)).unwrap();
+        assert!((sin - dec!(0.6442176872376910536726143513)).abs() < dec!(1e-26));
+        assert!((cos - dec!(0.7648421872844884262558599901)).abs() < dec!(1e-26));
+        let (sin, _) = sin_cos(Decimal::TWO_PI * dec!(1000) + dec!(0.7)).unwrap();
+        assert!((sin - dec!(0.6442176872376910536726143513)).abs() < dec!(1e-22));
+    }
+
+    #[test]
+    fn test_rotation_preserves_triangle_area() {
+        let tri = Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(2), dec!(0)), Point::new(dec!(1), dec!(2)));
+        let rotated = AffineTransform::rotation(dec!(0.7)).unwrap().apply_triangle(&tri).unwrap();
+        assert!((rotated.area() - tri.area()).abs() < dec!(1e-20));
+    }
+
+    #[test]
+    fn test_overflow_is_none() {
+        let t = AffineTransform::uniform_scaling(Decimal::MAX);
+        assert_eq!(t.apply(&Point::new(dec!(2), dec!(0))), None);
+    }
+}
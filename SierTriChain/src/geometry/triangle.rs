@@ -1,9 +1,51 @@
+use crate::geometry::lattice::LatticeTriangle;
 use crate::geometry::point::Point;
+use num_bigint::{BigInt, Sign};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
 
 pub const GOLDEN_RATIO: Decimal = dec!(1.61803398875);
+/// sqrt(3)/2 to the full 28 decimal places `Decimal` carries.
+pub const SQRT_3_HALF: Decimal = dec!(0.8660254037844386467637231708);
+
+/// Turn direction of three points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Clockwise,
+    Collinear,
+    CounterClockwise,
+}
+
+/// Sign of the cross product `(q - p) x (r - p)`, computed exactly.
+///
+/// Coordinates are lifted to integers at `Decimal`'s largest scale, so the
+/// answer is right even where the `Decimal` products would round or overflow.
+pub fn orientation(p: &Point, q: &Point, r: &Point) -> Orientation {
+    let [px, py, qx, qy, rx, ry] = [p.x, p.y, q.x, q.y, r.x, r.y].map(integer);
+    let cross = (qx - &px) * (ry - &py) - (qy - &py) * (rx - &px);
+    match cross.sign() {
+        Sign::Plus => Orientation::CounterClockwise,
+        Sign::Minus => Orientation::Clockwise,
+        Sign::NoSign => Orientation::Collinear,
+    }
+}
+
+/// `value * 10^28` as an integer; exact because no `Decimal` has a larger scale.
+fn integer(value: Decimal) -> BigInt {
+    BigInt::from(value.mantissa()) * BigInt::from(10u8).pow(Decimal::MAX_SCALE - value.scale())
+}
+
+/// Area of a simple polygon by the shoelace formula.
+pub fn polygon_area(vertices: &[Point]) -> Decimal {
+    let twice: Decimal = (0..vertices.len())
+        .map(|i| {
+            let (p, q) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+            p.x * q.y - q.x * p.y
+        })
+        .sum();
+    (twice / Decimal::TWO).abs()
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Triangle {
@@ -22,6 +64,13 @@ impl Triangle {
         area.abs()
     }
 
+    pub fn centroid(&self) -> Point {
+        Point::new(
+            (self.a.x + self.b.x + self.c.x) / Decimal::new(3, 0),
+            (self.a.y + self.b.y + self.c.y) / Decimal::new(3, 0),
+        )
+    }
+
     pub fn subdivide(&self) -> [Triangle; 4] {
         let mid_ab = self.a.midpoint(&self.b);
         let mid_bc = self.b.midpoint(&self.c);
@@ -34,22 +83,154 @@ impl Triangle {
             Triangle::new(mid_ab, mid_bc, mid_ca),
         ]
     }
+
+    /// All three vertices on one line. The spatial predicates below treat a
+    /// degenerate triangle as covering nothing.
+    pub fn is_degenerate(&self) -> bool {
+        orientation(&self.a, &self.b, &self.c) == Orientation::Collinear
+    }
+
+    /// The vertices reordered, if needed, to run counter-clockwise.
+    pub fn counter_clockwise(&self) -> [Point; 3] {
+        match orientation(&self.a, &self.b, &self.c) {
+            Orientation::Clockwise => [self.a, self.c, self.b],
+            _ => [self.a, self.b, self.c],
+        }
+    }
+
+    fn edges(&self) -> [(Point, Point); 3] {
+        let [a, b, c] = self.counter_clockwise();
+        [(a, b), (b, c), (c, a)]
+    }
+
+    /// True if `p` lies inside the triangle or on its boundary.
+    pub fn contains_point(&self, p: &Point) -> bool {
+        !self.is_degenerate() && self.edges().iter().all(|(a, b)| orientation(a, b, p) != Orientation::Clockwise)
+    }
+
+    /// True if `p` lies inside the triangle and not on its boundary.
+    pub fn strictly_contains_point(&self, p: &Point) -> bool {
+        !self.is_degenerate() && self.edges().iter().all(|(a, b)| orientation(a, b, p) == Orientation::CounterClockwise)
+    }
+
+    /// True if `other` lies entirely within this triangle, boundary included.
+    pub fn contains(&self, other: &Triangle) -> bool {
+        !other.is_degenerate() && [other.a, other.b, other.c].iter().all(|p| self.contains_point(p))
+    }
+
+    /// True if the two triangles share at least one point.
+    pub fn intersects(&self, other: &Triangle) -> bool {
+        !self.is_degenerate() && !other.is_degenerate() && !self.separated_from(other, Orientation::Clockwise)
+    }
+
+    /// True if the two triangles overlap in a region of positive area.
+    pub fn interiors_overlap(&self, other: &Triangle) -> bool {
+        !self.is_degenerate() && !other.is_degenerate() && !self.separated_from(other, Orientation::Collinear)
+    }
+
+    /// True if the triangles meet along a segment of positive length without
+    /// overlapping. The segment may be only part of either edge, as between a
+    /// triangle and a neighbour of a different subdivision depth.
+    pub fn shares_edge(&self, other: &Triangle) -> bool {
+        self.intersects(other)
+            && !self.interiors_overlap(other)
+            && self.edges().iter().any(|(p, q)| other.edges().iter().any(|(r, s)| segments_overlap(p, q, r, s)))
+    }
+
+    /// True if the triangles meet in a single point, which is a vertex of at
+    /// least one of them.
+    pub fn touches_at_vertex(&self, other: &Triangle) -> bool {
+        self.intersects(other) && !self.interiors_overlap(other) && !self.shares_edge(other)
+    }
+
+    /// Vertices of the region common to both triangles, counter-clockwise.
+    ///
+    /// Empty if they are disjoint. Triangles that only touch give a
+    /// degenerate polygon of zero area. Which points belong to the region is
+    /// decided exactly; points where edges cross are rounded to `Decimal`.
+    pub fn intersection(&self, other: &Triangle) -> Vec<Point> {
+        if !self.intersects(other) {
+            return Vec::new();
+        }
+        // Sutherland-Hodgman: clip `other` by each edge of this triangle.
+        let mut polygon = other.counter_clockwise().to_vec();
+        for (a, b) in self.edges() {
+            let input = std::mem::take(&mut polygon);
+            for (i, &end) in input.iter().enumerate() {
+                let start = input[(i + input.len() - 1) % input.len()];
+                let start_inside = orientation(&a, &b, &start) != Orientation::Clockwise;
+                let end_inside = orientation(&a, &b, &end) != Orientation::Clockwise;
+                if start_inside != end_inside {
+                    polygon.push(line_crossing(&a, &b, &start, &end));
+                }
+                if end_inside {
+                    polygon.push(end);
+                }
+            }
+            polygon.dedup();
+            while polygon.len() > 1 && polygon.first() == polygon.last() {
+                polygon.pop();
+            }
+        }
+        polygon
+    }
+
+    /// Area of the region common to both triangles.
+    pub fn intersection_area(&self, other: &Triangle) -> Decimal {
+        polygon_area(&self.intersection(other))
+    }
+
+    /// True if some edge line of either triangle has the other triangle
+    /// entirely on its outer side. `boundary` is the orientation a vertex on
+    /// the line has, so `Collinear` lets the triangles touch and
+    /// `Clockwise` does not.
+    fn separated_from(&self, other: &Triangle, boundary: Orientation) -> bool {
+        let outside = |edges: [(Point, Point); 3], points: [Point; 3]| {
+            edges.iter().any(|(a, b)| {
+                points.iter().all(|p| {
+                    let side = orientation(a, b, p);
+                    side == Orientation::Clockwise || side == boundary
+                })
+            })
+        };
+        outside(self.edges(), other.counter_clockwise()) || outside(other.edges(), self.counter_clockwise())
+    }
+}
+
+/// True if segments `pq` and `rs` lie on one line and overlap in more than a
+/// point.
+fn segments_overlap(p: &Point, q: &Point, r: &Point, s: &Point) -> bool {
+    if orientation(p, q, r) != Orientation::Collinear || orientation(p, q, s) != Orientation::Collinear {
+        return false;
+    }
+    // Compare along whichever axis the line is not perpendicular to.
+    let key = |point: &Point| if p.x != q.x { point.x } else { point.y };
+    let (lo1, hi1) = (key(p).min(key(q)), key(p).max(key(q)));
+    let (lo2, hi2) = (key(r).min(key(s)), key(r).max(key(s)));
+    lo1.max(lo2) < hi1.min(hi2)
+}
+
+/// Where segment `start`-`end` crosses the line through `a` and `b`.
+fn line_crossing(a: &Point, b: &Point, start: &Point, end: &Point) -> Point {
+    let cross = |p: &Point| (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+    let (from, to) = (cross(start), cross(end));
+    // One division per coordinate, so crossings at representable points are exact.
+    let weigh = |s: Decimal, e: Decimal| (from * e - to * s) / (from - to);
+    Point::new(weigh(start.x, end.x), weigh(start.y, end.y))
 }
 
 pub fn is_equilateral(tri: &Triangle, epsilon: Decimal) -> bool {
-    let side1 = tri.a.distance(&tri.b);
-    let side2 = tri.b.distance(&tri.c);
-    let side3 = tri.c.distance(&tri.a);
+    let sides = (tri.a.distance(&tri.b), tri.b.distance(&tri.c), tri.c.distance(&tri.a));
+    let (Some(side1), Some(side2), Some(side3)) = sides else {
+        return false;
+    };
 
     (side1 - side2).abs() < epsilon && (side2 - side3).abs() < epsilon
 }
 
+/// The root of the subdivision, derived from its exact lattice form.
 pub fn genesis_triangle() -> Triangle {
-    Triangle::new(
-        Point::new(dec!(0.0), dec!(0.0)),
-        Point::new(dec!(1.0), dec!(0.0)),
-        Point::new(dec!(0.5), dec!(0.86602540378)), // approx sqrt(3)/2
-    )
+    Triangle::from(LatticeTriangle::genesis())
 }
 
 #[cfg(test)]
@@ -104,4 +285,110 @@ mod tests {
         let t = Triangle::new(a, b, c);
         assert!(is_equilateral(&t, dec!(1e-9)));
     }
+
+    #[test]
+    fn test_genesis_is_equilateral_to_full_precision() {
+        assert!(is_equilateral(&genesis_triangle(), dec!(1e-26)));
+        assert_eq!(genesis_triangle().c, Point::new(dec!(0.5), SQRT_3_HALF));
+    }
+
+    fn tri(points: [(Decimal, Decimal); 3]) -> Triangle {
+        let [a, b, c] = points.map(|(x, y)| Point::new(x, y));
+        Triangle::new(a, b, c)
+    }
+
+    fn unit() -> Triangle {
+        tri([(dec!(0), dec!(0)), (dec!(2), dec!(0)), (dec!(0), dec!(2))])
+    }
+
+    #[test]
+    fn test_orientation_is_exact() {
+        let p = Point::new(dec!(0), dec!(0));
+        let q = Point::new(dec!(1), dec!(1));
+        assert_eq!(orientation(&p, &q, &Point::new(dec!(3), dec!(3))), Orientation::Collinear);
+        // A nudge in the last decimal place still registers.
+        let r = Point::new(dec!(3), dec!(3.0000000000000000000000000001));
+        assert_eq!(orientation(&p, &q, &r), Orientation::CounterClockwise);
+        // Products that overflow `Decimal` are still compared exactly.
+        let far = Point::new(Decimal::MAX, Decimal::MIN);
+        assert_eq!(orientation(&p, &q, &far), Orientation::Clockwise);
+    }
+
+    #[test]
+    fn test_contains_point_includes_boundary() {
+        let t = unit();
+        assert!(t.contains_point(&Point::new(dec!(0.5), dec!(0.5))));
+        assert!(t.contains_point(&Point::new(dec!(1), dec!(1))));
+        assert!(!t.strictly_contains_point(&Point::new(dec!(1), dec!(1))));
+        assert!(!t.contains_point(&Point::new(dec!(1.5), dec!(1))));
+        // Vertex order does not matter.
+        let flipped = Triangle::new(t.a, t.c, t.b);
+        assert!(flipped.contains_point(&Point::new(dec!(0.5), dec!(0.5))));
+    }
+
+    #[test]
+    fn test_containment_of_subdivision() {
+        let t = unit();
+        for child in t.subdivide() {
+            assert!(t.contains(&child));
+            assert!(!child.contains(&t));
+        }
+    }
+
+    #[test]
+    fn test_subdivision_children_share_edges() {
+        let [corner_a, corner_b, corner_c, middle] = unit().subdivide();
+        for corner in [corner_a, corner_b, corner_c] {
+            assert!(middle.shares_edge(&corner));
+            assert!(!middle.interiors_overlap(&corner));
+        }
+        assert!(corner_a.touches_at_vertex(&corner_b));
+        assert!(!corner_a.shares_edge(&corner_b));
+    }
+
+    #[test]
+    fn test_partial_edge_counts_as_shared() {
+        let big = unit();
+        let small = tri([(dec!(0.5), dec!(0)), (dec!(1), dec!(0)), (dec!(0.75), dec!(-1))]);
+        assert!(big.shares_edge(&small));
+        let beyond = tri([(dec!(2), dec!(0)), (dec!(3), dec!(0)), (dec!(2.5), dec!(-1))]);
+        assert!(big.touches_at_vertex(&beyond));
+    }
+
+    #[test]
+    fn test_disjoint_triangles() {
+        let far = tri([(dec!(5), dec!(5)), (dec!(6), dec!(5)), (dec!(5), dec!(6))]);
+        assert!(!unit().intersects(&far));
+        assert!(unit().intersection(&far).is_empty());
+        assert_eq!(unit().intersection_area(&far), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_intersection_polygon_and_area() {
+        let shifted = tri([(dec!(1), dec!(0)), (dec!(3), dec!(0)), (dec!(1), dec!(2))]);
+        assert!(unit().interiors_overlap(&shifted));
+        // The overlap is the triangle (1, 0), (2, 0), (1, 1).
+        assert_eq!(unit().intersection(&shifted).len(), 3);
+        assert_eq!(unit().intersection_area(&shifted), dec!(0.5));
+        // A triangle and its point reflection through the centroid overlap in a hexagon.
+        let c = unit().centroid();
+        let reflected = tri([(dec!(2) * c.x, dec!(2) * c.y), (dec!(2) * c.x - dec!(2), dec!(2) * c.y), (dec!(2) * c.x, dec!(2) * c.y - dec!(2))]);
+        let hexagon = unit().intersection(&reflected);
+        assert_eq!(hexagon.len(), 6);
+        assert!((polygon_area(&hexagon) - dec!(4) / dec!(3)).abs() < dec!(1e-20));
+    }
+
+    #[test]
+    fn test_intersection_with_contained_triangle_is_that_triangle() {
+        let child = unit().subdivide()[3];
+        assert_eq!(unit().intersection_area(&child), child.area());
+    }
+
+    #[test]
+    fn test_degenerate_triangle_covers_nothing() {
+        let flat = tri([(dec!(0), dec!(0)), (dec!(1), dec!(0)), (dec!(2), dec!(0))]);
+        assert!(flat.is_degenerate());
+        assert!(!flat.contains_point(&Point::new(dec!(1), dec!(0))));
+        assert!(!unit().intersects(&flat));
+    }
 }
\ No newline at end of file
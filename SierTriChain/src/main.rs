@@ -1,52 +1,194 @@
+// Modules keep their tests at the top, and several share their parent's name.
+#![allow(clippy::items_after_test_module, clippy::module_inception)]
+
 pub mod block;
 pub mod blockchain;
 pub mod core;
 pub mod crypto;
 pub mod defi;
 pub mod geometry;
+pub mod mempool;
 pub mod network;
 pub mod protocol;
+pub mod state;
+pub mod storage;
+pub mod territory;
+pub mod transaction;
 pub mod vm;
 pub mod wallet;
 
 
 use blockchain::Blockchain;
-use rust_decimal_macros::dec;
+use core::consensus::{Consensus, DefaultConsensus};
+use block::BlockHeader;
+use core::mining::parallel::{MiningProgress, ParallelMiner};
+use core::mining::MiningStrategy;
+use crypto::crypto::{KeyPair, SignatureAlgorithm, SEED_LEN};
+use crypto::wallet::{WalletFile, WalletFileError};
+use mempool::Mempool;
+use state::State;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
+use wallet::geo_wallet::Wallet;
+
+/// Directory the node keeps its block store in, overridable by `SIERTRICHAIN_DATA`.
+const DEFAULT_DATA_DIR: &str = "chaindata";
+/// File in the data directory holding the algorithm and hex seed of the key
+/// blocks are signed with, readable only by its owner.
+const PRODUCER_KEY_FILE: &str = "producer.key";
+/// Algorithm of a newly generated producer key, overridable by
+/// `SIERTRICHAIN_SIGNATURE` (`ed25519` or `ml-dsa-65`).
+const DEFAULT_SIGNATURE_ALGORITHM: SignatureAlgorithm = SignatureAlgorithm::Ed25519;
+/// File in the data directory holding the operator's encrypted wallet, opened
+/// when `SIERTRICHAIN_WALLET_PASSWORD` is set.
+const WALLET_FILE: &str = "wallet.dat";
+/// Encoded transaction bytes a mined block may carry.
+const MAX_BLOCK_TRANSACTION_BYTES: usize = 1024 * 1024;
+/// Mining strategy, overridable by `SIERTRICHAIN_MINER` (`deterministic` or `chaotic`).
+/// `SIERTRICHAIN_THREADS` sets the number of mining threads, one per core by default.
+const DEFAULT_MINING_STRATEGY: MiningStrategy = MiningStrategy::Deterministic;
+
+/// The environment variable `name` parsed as a `T`, or `default` if unset.
+/// Exits if it is set but does not parse.
+fn setting<T: FromStr>(name: &str, default: T) -> T
+where
+    T::Err: Display,
+{
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|e| {
+            eprintln!("Invalid {}: {}", name, e);
+            std::process::exit(1);
+        }),
+        Err(_) => default,
+    }
+}
+
+/// The node's block signing key, generated with `algorithm` on first run.
+/// A key file holding only a seed is an Ed25519 key.
+fn producer_key(data_dir: &str, algorithm: SignatureAlgorithm) -> std::io::Result<KeyPair> {
+    let path = std::path::Path::new(data_dir).join(PRODUCER_KEY_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => {
+            // Key files from before they were created private are narrowed now.
+            #[cfg(unix)]
+            std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+            let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "producer key is not an algorithm and hex seed");
+            let (algorithm, seed) = match text.trim().split_once(' ') {
+                Some((algorithm, seed)) => (algorithm.parse().map_err(|_| invalid())?, seed),
+                None => (SignatureAlgorithm::Ed25519, text.trim()),
+            };
+            let seed: [u8; SEED_LEN] = hex::decode(seed).ok().and_then(|bytes| bytes.try_into().ok()).ok_or_else(invalid)?;
+            Ok(algorithm.scheme().keypair_from_seed(&seed))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let keypair = algorithm.scheme().generate_keypair();
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(&path)?;
+            std::io::Write::write_all(&mut file, format!("{} {}", algorithm, hex::encode(&keypair.secret.bytes)).as_bytes())?;
+            file.sync_all()?;
+            Ok(keypair)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// The operator's wallet, unlocked with `password`. A new wallet is created
+/// and saved on first run.
+fn operator_wallet(data_dir: &str, password: &str) -> Result<Wallet, WalletFileError> {
+    let path = std::path::Path::new(data_dir).join(WALLET_FILE);
+    match WalletFile::load(&path) {
+        Ok(file) => file.unlock(password),
+        Err(WalletFileError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            let wallet = Wallet::new("operator".to_string());
+            WalletFile::lock(&wallet, password)?.save(&path)?;
+            Ok(wallet)
+        }
+        Err(e) => Err(e),
+    }
+}
 
 fn main() {
     println!("SierTriChain: Geometric blockchain engine initialized.");
 
-    let mut blockchain = Blockchain::new();
-    println!("Genesis block created: {:?}", blockchain.blocks[0]);
-
-    let initial_depth = 1;
-    let adjustment_interval = 10;
-
-    for i in 1..4 {
-        let block_height = i as usize;
-        let depth = core::mining::required_fractal_depth(block_height, initial_depth, adjustment_interval);
-        println!("Mining block {} at depth {}", block_height, depth);
-
-        if let Some(mining_result) = core::mining::mine_genesis(depth, dec!(1e-6)) {
-            let previous_block = blockchain.blocks.last().unwrap();
-            let mut new_block = block::Block {
-                index: previous_block.index + 1,
-                timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
-                transactions: vec![],
-                previous_hash: previous_block.hash.clone(),
-                hash: "".to_string(),
-                mining_result,
-            };
-            new_block.hash = new_block.calculate_hash();
-            println!("New block found: {:?}", new_block);
-            blockchain.add_block(new_block);
+    let data_dir = std::env::var("SIERTRICHAIN_DATA").unwrap_or_else(|_| DEFAULT_DATA_DIR.to_string());
+    let strategy = setting("SIERTRICHAIN_MINER", DEFAULT_MINING_STRATEGY);
+    let miner = match setting("SIERTRICHAIN_THREADS", 0) {
+        0 => ParallelMiner::with_available_parallelism(strategy.miner()),
+        threads => ParallelMiner::new(strategy.miner(), threads),
+    };
+    let state = Rc::new(RefCell::new(State::new()));
+    let mut blockchain = match Blockchain::open(&data_dir, Box::new(Rc::clone(&state))) {
+        Ok(blockchain) => blockchain,
+        Err(e) => {
+            eprintln!("Failed to open chain in {}: {}", data_dir, e);
+            std::process::exit(1);
+        }
+    };
+    let producer = producer_key(&data_dir, setting("SIERTRICHAIN_SIGNATURE", DEFAULT_SIGNATURE_ALGORITHM)).unwrap_or_else(|e| {
+        eprintln!("Failed to load the producer key from {}: {}", data_dir, e);
+        std::process::exit(1);
+    });
+    if let Ok(password) = std::env::var("SIERTRICHAIN_WALLET_PASSWORD") {
+        let wallet = operator_wallet(&data_dir, &password).unwrap_or_else(|e| {
+            eprintln!("Failed to open the wallet in {}: {}", data_dir, e);
+            std::process::exit(1);
+        });
+        println!("Unlocked wallet with {} accounts and {} territories", wallet.hd_keys.len(), wallet.owned_territories.len());
+    }
+    let mut mempool = Mempool::new();
+    println!("Loaded {} blocks from {}, genesis: {}", blockchain.best_chain().len(), data_dir, hex::encode(blockchain.best_chain()[0].hash()));
+    println!("Producing blocks as {} ({})", producer.address(), producer.public.algorithm);
+
+    for _ in 0..3 {
+        let block_height = blockchain.best_chain().len() as u64;
+        let difficulty = blockchain.next_difficulty();
+        println!("Mining block {} at depth {} ({}, {} threads)", block_height, difficulty.depth, strategy, miner.threads());
+
+        let previous_block = blockchain.tip();
+        let (transactions, state_root) = {
+            let state = state.borrow();
+            state.assemble(&producer.address(), mempool.block_template(state.accounts(), MAX_BLOCK_TRANSACTION_BYTES))
+        };
+        let template = BlockHeader {
+            version: block::BLOCK_VERSION,
+            height: block_height,
+            previous_hash: previous_block.hash(),
+            merkle_root: block::transactions_root(&transactions),
+            state_root,
+            producer: producer.address(),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().max(blockchain.median_time_past() + 1),
+            difficulty,
+            strategy,
+            address: geometry::hierarchy::FractalAddress::root(),
+            triangle_commitment: [0; 32],
+            nonce: 0,
+        };
+        let outcome = miner.search(&template, &MiningProgress::new());
+        let stats = outcome.stats();
+        println!("{} attempts in {:.2?} ({:.0} H/s)", stats.attempts, stats.elapsed, stats.hashrate());
+        if let Some(&header) = outcome.header() {
+            let mut new_block = block::Block { header, transactions, signature: vec![] };
+            new_block.sign(&producer).expect("the template names our own key");
+            println!("New block found: {}", hex::encode(new_block.hash()));
+            if let Err(e) = blockchain.add_block(new_block) {
+                eprintln!("Block {} rejected: {}", block_height, e);
+                std::process::exit(1);
+            }
+            for event in blockchain.take_events() {
+                mempool.chain_event(&event, state.borrow().accounts());
+            }
         } else {
-            println!("No triangle found at depth {}", depth);
+            println!("No triangle found at depth {}", difficulty.depth);
         }
     }
 
-    println!("\nChain complexity score: {}", core::consensus::chain_complexity_score(&blockchain.blocks));
+    println!("\nChain complexity score: {}", DefaultConsensus.chain_complexity_score(blockchain.best_chain()));
 }
 
 #[cfg(test)]
@@ -55,4 +197,20 @@ mod tests {
     fn test_main_basic() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_producer_key_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_str().unwrap();
+        let path = dir.path().join(super::PRODUCER_KEY_FILE);
+        let mode = || std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+
+        let key = super::producer_key(data_dir, super::SignatureAlgorithm::Ed25519).unwrap();
+        assert_eq!(mode(), 0o600);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(super::producer_key(data_dir, super::SignatureAlgorithm::Ed25519).unwrap().address(), key.address());
+        assert_eq!(mode(), 0o600);
+    }
 }
@@ -0,0 +1,589 @@
+//! Pool of transactions waiting to be mined.
+//!
+//! Transactions are kept per sender in nonce order. A sender's transactions
+//! are ready once their nonces run on without a gap from the sender's next
+//! confirmed nonce; later ones wait in the pool until the gap is filled.
+//! Templates take ready transactions highest fee rate first, always keeping
+//! each sender's transactions in nonce order.
+//!
+//! The pool keeps no ledger of its own. Admission and chain changes read the
+//! sender's nonce and balance from the [`Accounts`] of the best chain, so a
+//! restarted node needs nothing replayed into it.
+
+use crate::block::Block;
+use crate::blockchain::ChainEvent;
+use crate::crypto::crypto::CryptoError;
+use crate::defi::token::Amount;
+use crate::state::Accounts;
+use crate::transaction::Transaction;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
+
+/// Default limit on the encoded size of everything in the pool.
+pub const DEFAULT_MAX_POOL_BYTES: usize = 32 * 1024 * 1024;
+/// Largest transaction the pool accepts.
+pub const MAX_TRANSACTION_BYTES: usize = 100 * 1024;
+/// Lowest fee rate the pool accepts.
+pub const MIN_RELAY_FEE_RATE: FeeRate = FeeRate { fee: 1, size: 1 };
+/// How far past a sender's next confirmed nonce a pooled nonce may be.
+pub const MAX_NONCE_GAP: u64 = 64;
+/// A replacement must pay at least this many percent more per byte, and more
+/// in total, than the transaction it replaces.
+pub const MIN_REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+/// Fee per encoded byte, compared exactly.
+#[derive(Debug, Clone, Copy)]
+pub struct FeeRate {
+    pub fee: u64,
+    pub size: u64,
+}
+
+impl FeeRate {
+    pub fn of(tx: &Transaction) -> Self {
+        FeeRate { fee: tx.fee(), size: tx.encode().len() as u64 }
+    }
+
+    /// This rate scaled by `percent` / 100.
+    fn scaled(self, percent: u64) -> (u128, u128) {
+        (self.fee as u128 * percent as u128, self.size as u128 * 100)
+    }
+}
+
+impl PartialEq for FeeRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FeeRate {}
+
+impl PartialOrd for FeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.fee as u128 * other.size as u128).cmp(&(other.fee as u128 * self.size as u128))
+    }
+}
+
+/// Why the pool turned a transaction away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolError {
+    AlreadyKnown,
+    TooLarge(usize),
+    FeeTooLow,
+    /// The sender has already used this nonce on chain.
+    NonceTooLow { next: u64, found: u64 },
+    /// The nonce is further than [`MAX_NONCE_GAP`] past the sender's next one.
+    NonceTooHigh { next: u64, found: u64 },
+    /// The sender's balance does not cover this transaction's value and fee
+    /// on top of its pooled transactions with lower nonces.
+    InsufficientFunds { needed: u128, available: Amount },
+    /// Another transaction has this sender and nonce and pays too much to replace.
+    ReplacementUnderpriced,
+    /// The pool is full of transactions paying a higher fee rate.
+    PoolFull,
+    /// The transaction is not signed by its sender.
+    BadSignature(CryptoError),
+    /// A transfer's recipient is not a canonical address.
+    BadRecipient(CryptoError),
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MempoolError::AlreadyKnown => write!(f, "transaction is already in the pool"),
+            MempoolError::TooLarge(size) => write!(f, "transaction of {} bytes is too large", size),
+            MempoolError::FeeTooLow => write!(f, "fee rate is below the relay minimum"),
+            MempoolError::NonceTooLow { next, found } => write!(f, "nonce {} already used, next is {}", found, next),
+            MempoolError::NonceTooHigh { next, found } => write!(f, "nonce {} too far ahead of {}", found, next),
+            MempoolError::InsufficientFunds { needed, available } => {
+                write!(f, "needs {} base units but the sender has {}", needed, available)
+            }
+            MempoolError::ReplacementUnderpriced => write!(f, "replacement does not pay enough more"),
+            MempoolError::PoolFull => write!(f, "pool is full of higher fee transactions"),
+            MempoolError::BadSignature(e) => write!(f, "bad signature: {}", e),
+            MempoolError::BadRecipient(e) => write!(f, "bad recipient: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    tx: Transaction,
+    txid: [u8; 32],
+    rate: FeeRate,
+}
+
+pub struct Mempool {
+    /// Pooled transactions by sender, then nonce.
+    senders: HashMap<String, BTreeMap<u64, Entry>>,
+    by_txid: HashMap<[u8; 32], (String, u64)>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Self::with_max_bytes(DEFAULT_MAX_POOL_BYTES)
+    }
+
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        Mempool { senders: HashMap::new(), by_txid: HashMap::new(), bytes: 0, max_bytes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_txid.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_txid.is_empty()
+    }
+
+    /// Encoded size of everything in the pool.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn contains(&self, txid: &[u8; 32]) -> bool {
+        self.by_txid.contains_key(txid)
+    }
+
+    /// Admits `tx` on top of `accounts`, the ledger at the best chain's tip,
+    /// replacing a pooled transaction with the same sender and nonce if `tx`
+    /// pays enough more, and evicting the lowest fee rate transactions if the
+    /// pool grows past its limit. The sender's later transactions that their
+    /// balance no longer covers are dropped.
+    ///
+    /// Returns the transactions replaced, evicted or dropped.
+    pub fn add(&mut self, tx: Transaction, accounts: &Accounts) -> Result<Vec<Transaction>, MempoolError> {
+        let txid = tx.txid();
+        if self.by_txid.contains_key(&txid) {
+            return Err(MempoolError::AlreadyKnown);
+        }
+        let rate = FeeRate::of(&tx);
+        if rate.size as usize > MAX_TRANSACTION_BYTES {
+            return Err(MempoolError::TooLarge(rate.size as usize));
+        }
+        if rate < MIN_RELAY_FEE_RATE {
+            return Err(MempoolError::FeeTooLow);
+        }
+        tx.verify_signature().map_err(MempoolError::BadSignature)?;
+        tx.verify_recipient().map_err(MempoolError::BadRecipient)?;
+        let (sender, nonce) = (tx.sender().to_string(), tx.nonce());
+        let account = accounts.get(&sender);
+        let next = account.nonce;
+        if nonce < next {
+            return Err(MempoolError::NonceTooLow { next, found: nonce });
+        }
+        if nonce - next >= MAX_NONCE_GAP {
+            return Err(MempoolError::NonceTooHigh { next, found: nonce });
+        }
+        // Everything the sender's earlier pooled transactions spend comes first.
+        let earlier: u128 = self.senders.get(&sender).map_or(0, |queue| queue.range(..nonce).map(|(_, entry)| cost(&entry.tx)).sum());
+        let needed = earlier + cost(&tx);
+        if needed > account.balance.base_units() as u128 {
+            return Err(MempoolError::InsufficientFunds { needed, available: account.balance });
+        }
+
+        let mut removed = Vec::new();
+        if let Some(existing) = self.senders.get(&sender).and_then(|queue| queue.get(&nonce)) {
+            let (bumped_fee, bumped_size) = existing.rate.scaled(100 + MIN_REPLACEMENT_BUMP_PERCENT);
+            let pays_more_per_byte = rate.fee as u128 * bumped_size >= bumped_fee * rate.size as u128;
+            if !pays_more_per_byte || rate.fee <= existing.rate.fee {
+                return Err(MempoolError::ReplacementUnderpriced);
+            }
+            removed.extend(self.remove(&sender, nonce));
+        }
+        self.insert(Entry { tx, txid, rate });
+
+        while self.bytes > self.max_bytes {
+            let (victim_sender, victim_nonce) = self.eviction_candidate().expect("an over-full pool is not empty");
+            let victim = self.remove(&victim_sender, victim_nonce).expect("candidate is pooled");
+            if victim.txid() == txid {
+                // It pays least of all, so restore the pool as it was.
+                for tx in removed {
+                    self.insert(Entry { txid: tx.txid(), rate: FeeRate::of(&tx), tx });
+                }
+                return Err(MempoolError::PoolFull);
+            }
+            removed.push(victim);
+        }
+        removed.extend(self.drop_unfunded(&sender, account.balance));
+        Ok(removed)
+    }
+
+    /// Drops `sender`'s pooled transactions from the first one `balance`
+    /// no longer covers, in nonce order, so a replacement that spends more
+    /// cannot leave later ones queued that could never be mined.
+    fn drop_unfunded(&mut self, sender: &str, balance: Amount) -> Vec<Transaction> {
+        let Some(queue) = self.senders.get(sender) else {
+            return vec![];
+        };
+        let mut spent = 0u128;
+        let unfunded: Vec<u64> = queue
+            .iter()
+            .skip_while(|(_, entry)| {
+                spent += cost(&entry.tx);
+                spent <= balance.base_units() as u128
+            })
+            .map(|(&nonce, _)| nonce)
+            .collect();
+        unfunded.into_iter().filter_map(|nonce| self.remove(sender, nonce)).collect()
+    }
+
+    /// Follows a change to the best chain, given the ledger at its new tip.
+    pub fn chain_event(&mut self, event: &ChainEvent, accounts: &Accounts) {
+        match event {
+            ChainEvent::Connected(block) => self.block_connected(block, accounts),
+            ChainEvent::Disconnected(block) => self.block_disconnected(block, accounts),
+        }
+    }
+
+    /// Drops `block`'s transactions, and any others whose nonces it used up.
+    pub fn block_connected(&mut self, block: &Block, accounts: &Accounts) {
+        for tx in &block.transactions {
+            let next = accounts.get(tx.sender()).nonce;
+            let stale: Vec<u64> = match self.senders.get(tx.sender()) {
+                Some(queue) => queue.range(..next).map(|(&nonce, _)| nonce).collect(),
+                None => continue,
+            };
+            for nonce in stale {
+                self.remove(tx.sender(), nonce);
+            }
+        }
+    }
+
+    /// Returns `block`'s transactions to the pool after a reorganization took
+    /// it off the best chain. Any that no longer fit, or that the new chain
+    /// has already used the nonces of, are dropped.
+    pub fn block_disconnected(&mut self, block: &Block, accounts: &Accounts) {
+        for tx in &block.transactions {
+            // A pooled replacement for the same nonce may outbid it; either way
+            // the nonce is covered.
+            let _ = self.add(tx.clone(), accounts);
+        }
+    }
+
+    /// Ready transactions to mine, highest fee rate first within the limits
+    /// of nonce order, up to `max_bytes` of encoded transactions.
+    pub fn block_template(&self, accounts: &Accounts, max_bytes: usize) -> Vec<Transaction> {
+        // Each sender's ready run, lowest nonce first.
+        let mut runs: Vec<Vec<&Entry>> = self
+            .senders
+            .iter()
+            .map(|(sender, queue)| {
+                let mut expected = accounts.get(sender).nonce;
+                queue
+                    .iter()
+                    .take_while(|(&nonce, _)| {
+                        let ready = nonce == expected;
+                        expected += 1;
+                        ready
+                    })
+                    .map(|(_, entry)| entry)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|run: &Vec<&Entry>| !run.is_empty())
+            .collect();
+        for run in &mut runs {
+            run.reverse();
+        }
+
+        let mut heads: BinaryHeap<Head> = runs
+            .iter()
+            .enumerate()
+            .map(|(run, entries)| Head::of(entries.last().expect("runs are not empty"), run))
+            .collect();
+        let (mut template, mut bytes) = (Vec::new(), 0);
+        while let Some(head) = heads.pop() {
+            let entry = runs[head.run].pop().expect("head is in its run");
+            if bytes + entry.rate.size as usize > max_bytes {
+                // Later nonces of this sender cannot go in without this one.
+                continue;
+            }
+            bytes += entry.rate.size as usize;
+            template.push(entry.tx.clone());
+            if let Some(next) = runs[head.run].last() {
+                heads.push(Head::of(next, head.run));
+            }
+        }
+        template
+    }
+
+    fn insert(&mut self, entry: Entry) {
+        let (sender, nonce) = (entry.tx.sender().to_string(), entry.tx.nonce());
+        self.bytes += entry.rate.size as usize;
+        self.by_txid.insert(entry.txid, (sender.clone(), nonce));
+        self.senders.entry(sender).or_default().insert(nonce, entry);
+    }
+
+    fn remove(&mut self, sender: &str, nonce: u64) -> Option<Transaction> {
+        let queue = self.senders.get_mut(sender)?;
+        let entry = queue.remove(&nonce)?;
+        if queue.is_empty() {
+            self.senders.remove(sender);
+        }
+        self.by_txid.remove(&entry.txid);
+        self.bytes -= entry.rate.size as usize;
+        Some(entry.tx)
+    }
+
+    /// The lowest fee rate among each sender's highest nonce, so that
+    /// evicting it never opens a nonce gap.
+    fn eviction_candidate(&self) -> Option<(String, u64)> {
+        self.senders
+            .iter()
+            .filter_map(|(sender, queue)| queue.iter().next_back().map(|(&nonce, entry)| (entry, sender, nonce)))
+            .min_by(|(a, ..), (b, ..)| a.rate.cmp(&b.rate).then_with(|| b.txid.cmp(&a.txid)))
+            .map(|(_, sender, nonce)| (sender.clone(), nonce))
+    }
+}
+
+/// What `tx` takes from its sender's balance, fee included.
+fn cost(tx: &Transaction) -> u128 {
+    tx.fee() as u128 + tx.value() as u128
+}
+
+/// A sender's next transaction in the template heap.
+struct Head {
+    rate: FeeRate,
+    txid: [u8; 32],
+    run: usize,
+}
+
+impl Head {
+    fn of(entry: &Entry, run: usize) -> Self {
+        Head { rate: entry.rate, txid: entry.txid, run }
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    /// Higher fee rate first, then lower txid, so templates do not depend on
+    /// hash map order.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rate.cmp(&other.rate).then_with(|| other.txid.cmp(&self.txid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::crypto::crypto::{Ed25519, KeyPair, SignatureScheme};
+    use crate::state::State;
+    use crate::transaction::TxMeta;
+    use sha2::{Digest, Sha256};
+
+    const NAMES: [&str; 3] = ["alice", "bob", "carol"];
+
+    fn key(name: &str) -> KeyPair {
+        Ed25519.keypair_from_seed(&Sha256::digest(name).into())
+    }
+
+    fn address(name: &str) -> String {
+        key(name).address().to_string()
+    }
+
+    /// A ledger in which every name has a million base units.
+    fn funded() -> State {
+        let addresses = NAMES.map(address);
+        State::with_balances(addresses.iter().map(|a| (a.as_str(), Amount::from_base_units(1_000_000)))).unwrap()
+    }
+
+    fn tx(sender: &str, nonce: u64, fee: u64) -> Transaction {
+        let meta = TxMeta { sender: address(sender), nonce, fee, signature: vec![] };
+        let mut tx = Transaction::Transfer { meta, to: address("sink"), amount: 1 };
+        tx.sign(&key(sender)).unwrap();
+        tx
+    }
+
+    fn block(transactions: Vec<Transaction>) -> Block {
+        let header = BlockHeader { height: 1, merkle_root: crate::block::transactions_root(&transactions), ..BlockHeader::test_template() };
+        Block { header, transactions, signature: vec![] }
+    }
+
+    fn senders_and_nonces(template: &[Transaction]) -> Vec<(&str, u64)> {
+        let name = |tx: &Transaction| NAMES.into_iter().find(|name| address(name) == tx.sender()).unwrap();
+        template.iter().map(|tx| (name(tx), tx.nonce())).collect()
+    }
+
+    #[test]
+    fn test_template_orders_by_fee_rate_within_nonce_order() {
+        let mut pool = Mempool::new();
+        let state = funded();
+        for tx in [tx("alice", 0, 1000), tx("alice", 1, 50000), tx("bob", 0, 10000), tx("carol", 0, 5000)] {
+            pool.add(tx, state.accounts()).unwrap();
+        }
+        let template = pool.block_template(state.accounts(), usize::MAX);
+        // alice's second transaction pays most but has to follow her first.
+        assert_eq!(senders_and_nonces(&template), vec![("bob", 0), ("carol", 0), ("alice", 0), ("alice", 1)]);
+    }
+
+    #[test]
+    fn test_nonce_gap_waits_until_filled() {
+        let mut pool = Mempool::new();
+        let state = funded();
+        pool.add(tx("alice", 1, 1000), state.accounts()).unwrap();
+        assert!(pool.block_template(state.accounts(), usize::MAX).is_empty());
+        pool.add(tx("alice", 0, 1000), state.accounts()).unwrap();
+        assert_eq!(senders_and_nonces(&pool.block_template(state.accounts(), usize::MAX)), vec![("alice", 0), ("alice", 1)]);
+        assert_eq!(
+            pool.add(tx("alice", MAX_NONCE_GAP, 1000), state.accounts()),
+            Err(MempoolError::NonceTooHigh { next: 0, found: MAX_NONCE_GAP })
+        );
+    }
+
+    #[test]
+    fn test_replace_by_fee() {
+        let mut pool = Mempool::new();
+        let state = funded();
+        let original = tx("alice", 0, 1000);
+        pool.add(original.clone(), state.accounts()).unwrap();
+        assert_eq!(pool.add(tx("alice", 0, 1050), state.accounts()), Err(MempoolError::ReplacementUnderpriced));
+        assert_eq!(pool.add(original.clone(), state.accounts()), Err(MempoolError::AlreadyKnown));
+        assert_eq!(pool.add(tx("alice", 0, 1100), state.accounts()), Ok(vec![original.clone()]));
+        assert_eq!(pool.len(), 1);
+        assert!(!pool.contains(&original.txid()));
+        assert_eq!(pool.block_template(state.accounts(), usize::MAX), vec![tx("alice", 0, 1100)]);
+    }
+
+    #[test]
+    fn test_rejects_cheap_and_oversized() {
+        let mut pool = Mempool::new();
+        let state = funded();
+        assert_eq!(pool.add(tx("alice", 0, 1), state.accounts()), Err(MempoolError::FeeTooLow));
+        let meta = TxMeta { sender: address("alice"), nonce: 0, fee: u64::MAX, signature: vec![] };
+        let huge = Transaction::ContractDeploy { meta, code: vec![0; MAX_TRANSACTION_BYTES] };
+        assert!(matches!(pool.add(huge, state.accounts()), Err(MempoolError::TooLarge(_))));
+        let mut unsigned = tx("alice", 0, 1000);
+        unsigned.meta_mut().signature.clear();
+        assert!(matches!(pool.add(unsigned, state.accounts()), Err(MempoolError::BadSignature(_))));
+        let mut forged = tx("alice", 0, 1000);
+        forged.meta_mut().sender = address("bob");
+        assert_eq!(pool.add(forged, state.accounts()), Err(MempoolError::BadSignature(CryptoError::WrongSigner)));
+        let meta = TxMeta { sender: address("alice"), nonce: 0, fee: 1000, signature: vec![] };
+        let mut typo = Transaction::Transfer { meta, to: address("bob").to_uppercase(), amount: 1 };
+        typo.sign(&key("alice")).unwrap();
+        assert_eq!(pool.add(typo, state.accounts()), Err(MempoolError::BadRecipient(CryptoError::InvalidAddress)));
+    }
+
+    #[test]
+    fn test_full_pool_evicts_lowest_fee_rate() {
+        let size = FeeRate::of(&tx("alice", 0, 1000)).size as usize;
+        let mut pool = Mempool::with_max_bytes(2 * size);
+        let state = funded();
+        pool.add(tx("alice", 0, 3000), state.accounts()).unwrap();
+        pool.add(tx("alice", 1, 1000), state.accounts()).unwrap();
+        // bob outbids alice's tail, which goes; her first transaction stays.
+        assert_eq!(pool.add(tx("bob", 0, 2000), state.accounts()), Ok(vec![tx("alice", 1, 1000)]));
+        assert_eq!(pool.add(tx("carol", 0, 1500), state.accounts()), Err(MempoolError::PoolFull));
+        assert_eq!(pool.len(), 2);
+        assert_eq!(pool.bytes(), 2 * size);
+    }
+
+    #[test]
+    fn test_template_respects_size_limit() {
+        let mut pool = Mempool::new();
+        let state = funded();
+        for tx in [tx("alice", 0, 10000), tx("alice", 1, 10000), tx("bob", 0, 5000)] {
+            pool.add(tx, state.accounts()).unwrap();
+        }
+        let size = FeeRate::of(&tx("alice", 0, 10000)).size as usize;
+        assert_eq!(senders_and_nonces(&pool.block_template(state.accounts(), 2 * size)), vec![("alice", 0), ("alice", 1)]);
+        assert_eq!(senders_and_nonces(&pool.block_template(state.accounts(), size)), vec![("alice", 0)]);
+    }
+
+    #[test]
+    fn test_connect_and_disconnect() {
+        let mut pool = Mempool::new();
+        let before = funded();
+        let mined = block(vec![tx("alice", 0, 1000), tx("alice", 1, 1000)]);
+        pool.add(tx("alice", 0, 5000), before.accounts()).unwrap();
+        pool.add(tx("alice", 2, 1000), before.accounts()).unwrap();
+        pool.add(tx("bob", 0, 600), before.accounts()).unwrap();
+
+        let mut after = funded();
+        for tx in &mined.transactions {
+            after.apply_transaction(tx).unwrap();
+        }
+        pool.block_connected(&mined, after.accounts());
+        // The block used alice's nonces 0 and 1, so her other nonce 0 is gone.
+        assert_eq!(senders_and_nonces(&pool.block_template(after.accounts(), usize::MAX)), vec![("alice", 2), ("bob", 0)]);
+        assert_eq!(pool.add(tx("alice", 1, 9000), after.accounts()), Err(MempoolError::NonceTooLow { next: 2, found: 1 }));
+
+        pool.block_disconnected(&mined, before.accounts());
+        assert_eq!(pool.len(), 4);
+        assert_eq!(
+            senders_and_nonces(&pool.block_template(before.accounts(), usize::MAX)),
+            vec![("alice", 0), ("alice", 1), ("alice", 2), ("bob", 0)]
+        );
+    }
+
+    #[test]
+    fn test_nonces_come_from_the_ledger() {
+        // A sender with history, as a freshly restarted node sees it.
+        let mut state = funded();
+        for nonce in 0..MAX_NONCE_GAP + 6 {
+            state.apply_transaction(&tx("alice", nonce, 1000)).unwrap();
+        }
+        let mut pool = Mempool::new();
+        let next = MAX_NONCE_GAP + 6;
+        assert_eq!(pool.add(tx("alice", 0, 1000), state.accounts()), Err(MempoolError::NonceTooLow { next, found: 0 }));
+        pool.add(tx("alice", next, 1000), state.accounts()).unwrap();
+        assert_eq!(senders_and_nonces(&pool.block_template(state.accounts(), usize::MAX)), vec![("alice", next)]);
+    }
+
+    #[test]
+    fn test_balance_must_cover_pooled_spending() {
+        let mut pool = Mempool::new();
+        let state = funded();
+        let spend = |nonce, amount, fee| {
+            let meta = TxMeta { sender: address("alice"), nonce, fee, signature: vec![] };
+            let mut tx = Transaction::Transfer { meta, to: address("sink"), amount };
+            tx.sign(&key("alice")).unwrap();
+            tx
+        };
+        pool.add(spend(0, 600_000, 1000), state.accounts()).unwrap();
+        assert_eq!(
+            pool.add(spend(1, 400_000, 1000), state.accounts()),
+            Err(MempoolError::InsufficientFunds { needed: 1_002_000, available: Amount::from_base_units(1_000_000) })
+        );
+        pool.add(spend(1, 398_000, 1000), state.accounts()).unwrap();
+
+        // Replacing the first with one spending everything leaves nothing
+        // for those after it, so they go.
+        let replaced = pool.add(spend(0, 990_000, 2000), state.accounts()).unwrap();
+        assert_eq!(replaced.iter().map(Transaction::nonce).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(pool.len(), 1);
+        assert_eq!(senders_and_nonces(&pool.block_template(state.accounts(), usize::MAX)), vec![("alice", 0)]);
+        let broke = State::new();
+        assert!(matches!(pool.add(tx("bob", 0, 1000), broke.accounts()), Err(MempoolError::InsufficientFunds { .. })));
+    }
+}
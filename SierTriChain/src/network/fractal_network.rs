@@ -4,12 +4,34 @@ mod tests {
     fn test_network_fractal_network_basic() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn test_triangle_signatures_verify() {
+        use crate::crypto::crypto::{Ed25519, SignatureScheme};
+        let keypair = Ed25519.keypair_from_seed(&[3; 32]);
+        let signature = super::quantum::sign_triangle(b"triangle", &keypair.secret).unwrap();
+        assert!(super::quantum::verify_signature(b"triangle", &signature, &keypair.public));
+        assert!(!super::quantum::verify_signature(b"other", &signature, &keypair.public));
+    }
+
+    #[test]
+    fn test_post_quantum_signatures_and_key_exchange() {
+        use crate::crypto::crypto::{KeyEncapsulation, SignatureScheme};
+        use crate::crypto::pq::{MlDsa65, MlKem768};
+        let keypair = MlDsa65.keypair_from_seed(&[3; 32]);
+        let signature = super::quantum::sign_triangle(b"triangle", &keypair.secret).unwrap();
+        assert!(super::quantum::verify_signature(b"triangle", &signature, &keypair.public));
+
+        let peer = MlKem768.generate_keypair().unwrap();
+        let (ciphertext, shared) = super::quantum::key_exchange(&peer.public).unwrap();
+        assert_eq!(super::quantum::accept_key_exchange(&peer.secret, &ciphertext), Ok(shared));
+    }
 }
 // Fractal tree routing and network topology for geometric territory system
-// Quantum-resistant cryptography stubs included
+// Quantum-resistant signatures and key exchange included
 
 use crate::geometry::subdivision::FractalAddress;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use crate::geometry::point::Point;
 use rust_decimal::Decimal;
 use rust_decimal::prelude::{ToPrimitive, FromPrimitive};
@@ -20,7 +42,13 @@ pub struct NetworkNode {
 }
 
 pub struct FractalNetwork {
-    pub nodes: HashMap<String, NetworkNode>, // Key: base-3 address string
+    pub nodes: HashMap<FractalAddress, NetworkNode>,
+}
+
+impl Default for FractalNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FractalNetwork {
@@ -30,29 +58,24 @@ impl FractalNetwork {
 
     // Add node to network
     pub fn add_node(&mut self, node: NetworkNode) {
-        let key = Self::address_to_string(&node.address);
-        self.nodes.insert(key, node);
+        self.nodes.insert(node.address, node);
     }
 
     // Fractal tree traversal routing: BFS from source to target
     pub fn route(&self, source: &FractalAddress, target: &FractalAddress) -> Option<Vec<FractalAddress>> {
-        let src_key = Self::address_to_string(source);
-        let tgt_key = Self::address_to_string(target);
-        let mut visited = HashMap::new();
+        let mut visited = HashSet::new();
         let mut queue = VecDeque::new();
-        queue.push_back((src_key.clone(), vec![source.clone()]));
+        queue.push_back((*source, vec![*source]));
         while let Some((current, path)) = queue.pop_front() {
-            if current == tgt_key {
+            if current == *target {
                 return Some(path);
             }
             if let Some(node) = self.nodes.get(&current) {
                 for peer in &node.peers {
-                    let peer_key = Self::address_to_string(peer);
-                    if !visited.contains_key(&peer_key) {
+                    if visited.insert(*peer) {
                         let mut new_path = path.clone();
-                        new_path.push(peer.clone());
-                        queue.push_back((peer_key.clone(), new_path));
-                        visited.insert(peer_key, true);
+                        new_path.push(*peer);
+                        queue.push_back((*peer, new_path));
                     }
                 }
             }
@@ -63,64 +86,52 @@ impl FractalNetwork {
     // Optimize topology: connect nodes with minimal fractal distance
     pub fn optimize_topology(&mut self) {
         // Connect each node to its nearest neighbors in fractal space
+        let addresses: Vec<FractalAddress> = self.nodes.keys().copied().collect();
         for node in self.nodes.values_mut() {
-            let mut distances: Vec<(f64, FractalAddress)> = self.nodes
-                .values()
-                .filter(|other| other.address != node.address)
+            let mut distances: Vec<(f64, FractalAddress)> = addresses
+                .iter()
+                .filter(|other| **other != node.address)
                 .map(|other| {
                     let p1 = Point {
-                        x: Decimal::from_f64(node.address.0.iter().map(|d| *d as f64).sum::<f64>()).unwrap(),
-                        y: Decimal::from_f64(node.address.0.len() as f64).unwrap(),
+                        x: Decimal::from_f64(node.address.digits().map(|d| d as f64).sum::<f64>()).unwrap(),
+                        y: Decimal::from_f64(node.address.depth() as f64).unwrap(),
                     };
                     let p2 = Point {
-                        x: Decimal::from_f64(other.address.0.iter().map(|d| *d as f64).sum::<f64>()).unwrap(),
-                        y: Decimal::from_f64(other.address.0.len() as f64).unwrap(),
+                        x: Decimal::from_f64(other.digits().map(|d| d as f64).sum::<f64>()).unwrap(),
+                        y: Decimal::from_f64(other.depth() as f64).unwrap(),
                     };
-                    let dist = p1.distance(&p2).to_f64().unwrap();
-                    (dist, other.address.clone())
+                    let dist = p1.distance(&p2).and_then(|d| d.to_f64()).unwrap_or(f64::MAX);
+                    (dist, *other)
                 })
                 .collect();
             distances.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-            node.peers = distances.iter().take(3).map(|(_, addr)| addr.clone()).collect();
+            node.peers = distances.iter().take(3).map(|(_, addr)| *addr).collect();
         }
     }
 
     // Geometric locality message propagation
     pub fn propagate_message(&self, origin: &FractalAddress, message: &str) {
         // Propagate message to all nodes within 1 fractal digit of origin
-        let origin_key = Self::address_to_string(origin);
-        for (key, node) in &self.nodes {
-            let locality = key.chars().zip(origin_key.chars()).filter(|(a, b)| a == b).count();
-            if locality >= origin_key.len().saturating_sub(1) {
+        for (address, node) in &self.nodes {
+            let locality = address.common_ancestor(origin).depth();
+            if locality >= origin.depth().saturating_sub(1) {
                 // Simulate message delivery
-                println!("Message '{}' delivered to node {:?}", message, node.address);
+                println!("Message '{}' delivered to node {}", message, node.address);
             }
         }
     }
-
-    pub fn address_to_string(addr: &FractalAddress) -> String {
-        addr.0.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("")
-    }
 }
 
-// Quantum-resistant cryptography stubs
+// Quantum-resistant cryptography: ML-DSA signatures and ML-KEM key exchange
 pub mod quantum {
-    // CRYSTALS-Dilithium signature stub
-    pub fn sign_triangle(_triangle_bytes: &[u8], _private_key: &[u8]) -> Vec<u8> {
-    // Placeholder: Use SHA-256 hash as mock signature
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
-    hasher.update(_triangle_bytes);
-    hasher.update(_private_key);
-    hasher.finalize().to_vec()
+    use crate::crypto::crypto::{CryptoError, DecapsulationKey, EncapsulationKey, PublicKey, SecretKey, Signature, SHARED_SECRET_LEN};
+
+    // Signs triangle bytes with whichever scheme the key belongs to; ML-DSA-65 keys are post-quantum
+    pub fn sign_triangle(triangle_bytes: &[u8], private_key: &SecretKey) -> Result<Signature, CryptoError> {
+        private_key.sign(triangle_bytes)
     }
-    pub fn verify_signature(_triangle_bytes: &[u8], _signature: &[u8], _public_key: &[u8]) -> bool {
-    // Placeholder: Recompute hash and compare
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
-    hasher.update(_triangle_bytes);
-    hasher.update(_public_key);
-    hasher.finalize().to_vec() == _signature
+    pub fn verify_signature(triangle_bytes: &[u8], signature: &Signature, public_key: &PublicKey) -> bool {
+        public_key.verify(triangle_bytes, signature).is_ok()
     }
     // Quantum-resistant commitment scheme stub
     pub fn commit_triangle(_triangle_bytes: &[u8], _randomness: &[u8]) -> Vec<u8> {
@@ -131,13 +142,12 @@ pub mod quantum {
     hasher.update(_randomness);
     hasher.finalize().to_vec()
     }
-    // Post-quantum key exchange stub
-    pub fn key_exchange(_public_a: &[u8], _public_b: &[u8]) -> Vec<u8> {
-    // Placeholder: Hash both public keys together
-    use sha2::{Sha256, Digest};
-    let mut hasher = Sha256::new();
-    hasher.update(_public_a);
-    hasher.update(_public_b);
-    hasher.finalize().to_vec()
+    // Post-quantum key exchange: a shared secret for the peer and the ciphertext to send them
+    pub fn key_exchange(peer: &EncapsulationKey) -> Result<(Vec<u8>, [u8; SHARED_SECRET_LEN]), CryptoError> {
+        peer.algorithm.scheme().encapsulate(peer)
+    }
+    // Recovers the secret a peer's key exchange ciphertext carries
+    pub fn accept_key_exchange(secret: &DecapsulationKey, ciphertext: &[u8]) -> Result<[u8; SHARED_SECRET_LEN], CryptoError> {
+        secret.algorithm.scheme().decapsulate(secret, ciphertext)
     }
 }
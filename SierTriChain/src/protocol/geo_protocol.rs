@@ -1,25 +1,58 @@
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::geometry::point::Point;
+    use rust_decimal_macros::dec;
+
     #[test]
     fn test_protocol_geo_protocol_basic() {
         assert_eq!(2 + 2, 4);
     }
-// Removed unexpected closing brace
+
+    #[test]
+    fn test_inflation_is_exact_and_rounds_down() {
+        let supply = Amount::from_base_units(1_000_000);
+        assert_eq!(inflation_rate(supply, 0), Ok(supply));
+        // 1,000,000 * 0.75^3 = 421,875 exactly; 0.75^4 leaves 316,406.25.
+        assert_eq!(inflation_rate(supply, 3), Ok(Amount::from_base_units(421_875)));
+        assert_eq!(inflation_rate(supply, 4), Ok(Amount::from_base_units(316_406)));
+    }
+
+    #[test]
+    fn test_entropy_burn_never_goes_below_zero() {
+        let unit = Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(1), dec!(0)), Point::new(dec!(0), dec!(2)));
+        let supply = Amount::from_tokens(10).unwrap();
+        // ln(1) = 0: nothing burns.
+        assert_eq!(entropy_burn(supply, &unit), supply);
+        let flat = Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(1), dec!(0)), Point::new(dec!(2), dec!(0)));
+        assert_eq!(entropy_burn(supply, &flat), Amount::ZERO);
+    }
+}
 // Protocol and governance module for fractal territory system
-// Includes inflation, burning, voting, treasury, metrics, and infrastructure stubs
+// Includes inflation, burning, voting, treasury, metrics, and infrastructure stubs.
+// Token quantities are fixed-point `Amount`s; weights and scores stay `f64`.
 
+use crate::defi::token::{Amount, AmountError, Rounding, BASE_UNITS_PER_TOKEN};
 use crate::geometry::triangle::Triangle;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, MathematicalOps};
 
-// Inflation rate tied to fractal growth
-pub fn inflation_rate(old_supply: f64, depth: usize) -> f64 {
-    old_supply * (0.75f64).powi(depth as i32)
+// Inflation rate tied to fractal growth: old_supply * 0.75^depth, rounded down
+pub fn inflation_rate(old_supply: Amount, depth: usize) -> Result<Amount, AmountError> {
+    let factor = Decimal::new(75, 2).checked_powu(depth as u64).ok_or(AmountError::Overflow)?;
+    old_supply.checked_mul_decimal(factor, Rounding::Down)
 }
 
-// Entropy-based burning: token destruction by thermodynamic triangle relationships (stub)
-pub fn entropy_burn(supply: f64, triangle: &Triangle) -> f64 {
+// Entropy-based burning: token destruction by thermodynamic triangle relationships (stub).
+// Burns |ln(area)| tokens, rounded down, and everything for a degenerate triangle.
+pub fn entropy_burn(supply: Amount, triangle: &Triangle) -> Amount {
     // TODO: Use triangle entropy/area for burn calculation
-    let entropy = crate::area::triangle_area(triangle).ln().abs();
-    supply - entropy.min(supply)
+    let burn = triangle
+        .area()
+        .checked_ln()
+        .and_then(|entropy| Amount::from_tokens_decimal(entropy.abs(), Rounding::Down).ok())
+        .unwrap_or(supply);
+    supply.saturating_sub(burn)
 }
 
 // Quadratic voting weighted by geometric territory complexity
@@ -30,7 +63,7 @@ pub fn quadratic_vote(weight: f64, complexity: f64) -> f64 {
 // Treasury rebalancing stub: geometric mean reversion and portfolio optimization
 pub fn treasury_rebalance(portfolio: &[Triangle]) -> f64 {
     // TODO: Implement geometric mean reversion
-    portfolio.iter().map(|t| crate::area::triangle_area(t)).sum::<f64>() / (portfolio.len().max(1) as f64)
+    portfolio.iter().map(crate::geometry::area::triangle_area).sum::<f64>() / (portfolio.len().max(1) as f64)
 }
 
 // Governance proposal stub: must satisfy geometric constraints
@@ -69,16 +102,13 @@ pub fn ar_overlay(_triangles: &[Triangle]) {
 }
 
 // Research bounty stub: reward mathematical proofs and algorithm improvements
-pub fn research_bounty(_proof: &str) -> f64 {
+pub fn research_bounty(_proof: &str) -> Amount {
     // TODO: Validate proof and assign bounty
-    100.0
+    Amount::from_base_units(100 * BASE_UNITS_PER_TOKEN)
 }
 
 // Ecosystem grant stub: fund geometric art, visualization, education
-pub fn ecosystem_grant(_project: &str) -> f64 {
+pub fn ecosystem_grant(_project: &str) -> Amount {
     // TODO: Evaluate and fund project
-    500.0
-}
-nd fund project
-    500.0
+    Amount::from_base_units(500 * BASE_UNITS_PER_TOKEN)
 }
@@ -0,0 +1,751 @@
+//! Account and territory state, as the best chain leaves it.
+//!
+//! Every account has a spendable balance, the nonce its next transaction must
+//! carry, and the stake it has locked in territories. A transaction pays its
+//! fee, then takes effect; one that cannot take effect leaves the state as it
+//! was. A block applies all its transactions or none, after which its
+//! producer is credited [`BLOCK_REWARD`] and the fees. This is the only
+//! issuance: the genesis block pays nobody and its state is empty.
+//!
+//! The state root committed in each block header is the Merkle root over a
+//! hash of every non-empty account, by name, then of every territory, by
+//! fractal address. A territory's triangle is always the lattice triangle at
+//! its address, so the address stands for its geometry. Strings are a `u32` length and their bytes, integers
+//! little-endian, and amounts their base units as a `u64`:
+//!
+//! ```text
+//! account:   "account" | name | balance | nonce: u64 | locked
+//! territory: "territory" | address depth: u8 | address digits | owner | stake | yield | commitment id
+//! ```
+//!
+//! A claim or conquest records the claimant's commitment to the territory's
+//! address once its proof shows that it opens to it; the commitment id is
+//! its SHA-256, or zeros for a territory without one. Ownership proofs are
+//! then checked against the recorded commitments, through
+//! [`TerritoryRegistry::verify_ownership`].
+
+use crate::block::Block;
+use crate::blockchain::ChainState;
+use crate::crypto::crypto::Address;
+use crate::crypto::hash::merkle_root;
+use crate::crypto::zk::{AddressProof, TerritoryCommitment, ZkError};
+use crate::defi::token::{Amount, BASE_UNITS_PER_TOKEN};
+use crate::geometry::hierarchy::FractalAddress;
+use crate::geometry::subdivision::triangle_at;
+use crate::territory::{Territory, TerritoryRegistry};
+use crate::transaction::Transaction;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// New tokens credited to the producer of every block after genesis.
+pub const BLOCK_REWARD: Amount = Amount::from_base_units(50 * BASE_UNITS_PER_TOKEN);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Account {
+    pub balance: Amount,
+    /// The nonce of the account's next transaction.
+    pub nonce: u64,
+    /// Stake held in the account's territories, not spendable.
+    pub locked: Amount,
+}
+
+/// Why a transaction or block cannot be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    BadNonce { account: String, expected: u64, found: u64 },
+    InsufficientFunds { account: String, needed: Amount, available: Amount },
+    /// More stake released than the account has locked.
+    InsufficientLocked { account: String, needed: Amount, locked: Amount },
+    Overflow(String),
+    /// The territory registry refused the claim, conquest or stake.
+    Territory(String),
+    /// A claim's or conquest's commitment is malformed or does not open to
+    /// its address.
+    Commitment(ZkError),
+    /// Transaction `index` of a block failed.
+    Transaction { index: usize, error: Box<StateError> },
+    StateRootMismatch { expected: [u8; 32], found: [u8; 32] },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadNonce { account, expected, found } => {
+                write!(f, "{} sent nonce {}, expected {}", account, found, expected)
+            }
+            StateError::InsufficientFunds { account, needed, available } => {
+                write!(f, "{} needs {} but has {}", account, needed, available)
+            }
+            StateError::InsufficientLocked { account, needed, locked } => {
+                write!(f, "{} releases {} but has {} locked", account, needed, locked)
+            }
+            StateError::Overflow(account) => write!(f, "balance of {} overflows", account),
+            StateError::Territory(reason) => write!(f, "{}", reason),
+            StateError::Commitment(e) => write!(f, "territory commitment: {}", e),
+            StateError::Transaction { index, error } => write!(f, "transaction {}: {}", index, error),
+            StateError::StateRootMismatch { expected, found } => {
+                write!(f, "state root {} does not match {}", hex::encode(found), hex::encode(expected))
+            }
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// Every account with anything in it, by name.
+#[derive(Debug, Clone, Default)]
+pub struct Accounts {
+    accounts: BTreeMap<String, Account>,
+    /// While a block is applied, each changed account as it was before.
+    journal: Option<BTreeMap<String, Account>>,
+}
+
+impl PartialEq for Accounts {
+    fn eq(&self, other: &Self) -> bool {
+        self.accounts == other.accounts
+    }
+}
+
+impl Eq for Accounts {}
+
+impl Accounts {
+    /// The account `name`, empty if it has never been used.
+    pub fn get(&self, name: &str) -> Account {
+        self.accounts.get(name).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Account)> {
+        self.accounts.iter()
+    }
+
+    pub fn credit(&mut self, name: &str, amount: Amount) -> Result<(), StateError> {
+        let mut account = self.get(name);
+        account.balance = account.balance.checked_add(amount).map_err(|_| StateError::Overflow(name.to_string()))?;
+        self.set(name, account);
+        Ok(())
+    }
+
+    pub fn debit(&mut self, name: &str, amount: Amount) -> Result<(), StateError> {
+        let mut account = self.get(name);
+        account.balance = account.balance.checked_sub(amount).map_err(|_| StateError::InsufficientFunds {
+            account: name.to_string(),
+            needed: amount,
+            available: account.balance,
+        })?;
+        self.set(name, account);
+        Ok(())
+    }
+
+    /// Moves `amount` from `from` to `to`, or nothing on error.
+    pub fn transfer(&mut self, from: &str, to: &str, amount: Amount) -> Result<(), StateError> {
+        self.debit(from, amount)?;
+        if let Err(e) = self.credit(to, amount) {
+            self.credit(from, amount).expect("refunding a debit fits");
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Moves `amount` of `name`'s balance into its locked stake.
+    pub fn lock(&mut self, name: &str, amount: Amount) -> Result<(), StateError> {
+        let mut account = self.get(name);
+        account.balance = account.balance.checked_sub(amount).map_err(|_| StateError::InsufficientFunds {
+            account: name.to_string(),
+            needed: amount,
+            available: account.balance,
+        })?;
+        account.locked = account.locked.checked_add(amount).map_err(|_| StateError::Overflow(name.to_string()))?;
+        self.set(name, account);
+        Ok(())
+    }
+
+    /// Releases `amount` of `name`'s locked stake back into its balance.
+    pub fn unlock(&mut self, name: &str, amount: Amount) -> Result<(), StateError> {
+        let mut account = self.get(name);
+        account.locked = account.locked.checked_sub(amount).map_err(|_| StateError::InsufficientLocked {
+            account: name.to_string(),
+            needed: amount,
+            locked: account.locked,
+        })?;
+        account.balance = account.balance.checked_add(amount).map_err(|_| StateError::Overflow(name.to_string()))?;
+        self.set(name, account);
+        Ok(())
+    }
+
+    fn increment_nonce(&mut self, name: &str) -> Result<(), StateError> {
+        let mut account = self.get(name);
+        account.nonce = account.nonce.checked_add(1).ok_or_else(|| StateError::Overflow(name.to_string()))?;
+        self.set(name, account);
+        Ok(())
+    }
+
+    /// Stores `account`, dropping it if it is empty so that the root does not
+    /// depend on which empty accounts were ever touched.
+    fn set(&mut self, name: &str, account: Account) {
+        let before = self.get(name);
+        if let Some(journal) = &mut self.journal {
+            journal.entry(name.to_string()).or_insert(before);
+        }
+        if account == Account::default() {
+            self.accounts.remove(name);
+        } else {
+            self.accounts.insert(name.to_string(), account);
+        }
+    }
+}
+
+/// What applying a block changed: every account and territory it touched,
+/// as they were before. A territory that did not exist is `None`.
+#[derive(Default)]
+struct Undo {
+    accounts: BTreeMap<String, Account>,
+    territories: BTreeMap<FractalAddress, Option<Territory>>,
+}
+
+/// The ledger, with undo data for the blocks connected to it.
+#[derive(Default)]
+pub struct State {
+    accounts: Accounts,
+    territories: TerritoryRegistry,
+    /// What each connected block changed, oldest first. Each entry is the
+    /// size of its block's changes, not of the ledger.
+    undo: Vec<Undo>,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A state in which each named account starts with a balance.
+    pub fn with_balances<'a>(balances: impl IntoIterator<Item = (&'a str, Amount)>) -> Result<Self, StateError> {
+        let mut state = Self::new();
+        for (name, balance) in balances {
+            state.accounts.credit(name, balance)?;
+        }
+        Ok(state)
+    }
+
+    pub fn account(&self, name: &str) -> Account {
+        self.accounts.get(name)
+    }
+
+    pub fn accounts(&self) -> &Accounts {
+        &self.accounts
+    }
+
+    pub fn territories(&self) -> &TerritoryRegistry {
+        &self.territories
+    }
+
+    /// Applies one transaction, or leaves the state unchanged and says why
+    /// it cannot.
+    pub fn apply_transaction(&mut self, tx: &Transaction) -> Result<(), StateError> {
+        let sender = tx.sender();
+        let expected = self.accounts.get(sender).nonce;
+        if tx.nonce() != expected {
+            return Err(StateError::BadNonce { account: sender.to_string(), expected, found: tx.nonce() });
+        }
+        let fee = Amount::from_base_units(tx.fee());
+        self.accounts.debit(sender, fee)?;
+        if let Err(e) = self.apply_effect(tx) {
+            self.accounts.credit(sender, fee).expect("refunding a debit fits");
+            return Err(e);
+        }
+        self.accounts.increment_nonce(sender).expect("nonce checked against the account");
+        Ok(())
+    }
+
+    /// Applies every transaction of `block` and checks the state root it
+    /// commits to, or leaves the state unchanged.
+    pub fn apply_block(&mut self, block: &Block) -> Result<(), StateError> {
+        self.apply_block_with_undo(block).map(|_| ())
+    }
+
+    /// [`apply_block`](Self::apply_block), returning what it changed.
+    fn apply_block_with_undo(&mut self, block: &Block) -> Result<Undo, StateError> {
+        self.accounts.journal = Some(BTreeMap::new());
+        let mut territories = BTreeMap::new();
+        let applied = block.transactions.iter().enumerate().try_for_each(|(index, tx)| {
+            if let Some(address) = self.touched_territory(tx) {
+                territories.entry(address).or_insert_with(|| self.territories.territories.get(&address).cloned());
+            }
+            self.apply_transaction(tx).map_err(|error| StateError::Transaction { index, error: Box::new(error) })
+        });
+        let rewarded = applied.and_then(|()| match block.header.height {
+            0 => Ok(()),
+            _ => self.reward(&block.header.producer.to_string(), &block.transactions),
+        });
+        let result = rewarded.and_then(|()| match self.root() {
+            root if root == block.header.state_root => Ok(()),
+            root => Err(StateError::StateRootMismatch { expected: root, found: block.header.state_root }),
+        });
+        let undo = Undo { accounts: self.accounts.journal.take().unwrap_or_default(), territories };
+        match result {
+            Ok(()) => Ok(undo),
+            Err(e) => {
+                self.revert(undo);
+                Err(e)
+            }
+        }
+    }
+
+    fn revert(&mut self, undo: Undo) {
+        for (name, account) in undo.accounts {
+            self.accounts.set(&name, account);
+        }
+        for (address, territory) in undo.territories {
+            match territory {
+                Some(territory) => self.territories.territories.insert(address, territory),
+                None => self.territories.territories.remove(&address),
+            };
+        }
+    }
+
+    /// The key of the one territory `tx` may change, if any.
+    fn touched_territory(&self, tx: &Transaction) -> Option<FractalAddress> {
+        match tx {
+            Transaction::TerritoryClaim { address, .. } | Transaction::TerritoryConquest { address, .. } => Some(*address),
+            Transaction::Stake { address, .. } => self.territory_at(address).ok(),
+            Transaction::Transfer { .. } | Transaction::ContractDeploy { .. } | Transaction::ContractCall { .. } => None,
+        }
+    }
+
+    /// The candidates that apply in order on top of this state, and the
+    /// state root a block carrying them and produced by `producer` commits to.
+    pub fn assemble(&self, producer: &Address, candidates: Vec<Transaction>) -> (Vec<Transaction>, [u8; 32]) {
+        let mut scratch = State { accounts: self.accounts.clone(), territories: self.territories.clone(), undo: vec![] };
+        let transactions: Vec<Transaction> = candidates.into_iter().filter(|tx| scratch.apply_transaction(tx).is_ok()).collect();
+        // A producer whose balance cannot take the reward has no block that
+        // applies, whatever root it commits to.
+        let _ = scratch.reward(&producer.to_string(), &transactions);
+        (transactions, scratch.root())
+    }
+
+    /// Credits `producer` with the block reward and the fees of `transactions`.
+    fn reward(&mut self, producer: &str, transactions: &[Transaction]) -> Result<(), StateError> {
+        let overflow = || StateError::Overflow(producer.to_string());
+        let fees = transactions.iter().try_fold(BLOCK_REWARD, |total, tx| {
+            total.checked_add(Amount::from_base_units(tx.fee())).map_err(|_| overflow())
+        })?;
+        self.accounts.credit(producer, fees)
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        let accounts = self.accounts.iter().map(|(name, account)| {
+            let mut hasher = Sha256::new();
+            put_str(&mut hasher, "account");
+            put_str(&mut hasher, name);
+            hasher.update(account.balance.base_units().to_le_bytes());
+            hasher.update(account.nonce.to_le_bytes());
+            hasher.update(account.locked.base_units().to_le_bytes());
+            hasher.finalize().into()
+        });
+        let territories = self.territories.territories.values().map(|territory| {
+            let mut hasher = Sha256::new();
+            put_str(&mut hasher, "territory");
+            hasher.update([territory.address.depth() as u8]);
+            hasher.update(&territory.address.as_bytes()[..territory.address.depth().div_ceil(4)]);
+            put_str(&mut hasher, &territory.owner);
+            hasher.update(territory.staked_tokens.base_units().to_le_bytes());
+            hasher.update(territory.yield_tokens.base_units().to_le_bytes());
+            hasher.update(territory.commitment.as_ref().map_or([0; 32], TerritoryCommitment::id));
+            hasher.finalize().into()
+        });
+        let leaves: Vec<[u8; 32]> = accounts.chain(territories).collect();
+        merkle_root(&leaves)
+    }
+
+    fn apply_effect(&mut self, tx: &Transaction) -> Result<(), StateError> {
+        let sender = tx.sender();
+        let territory_error = StateError::Territory;
+        match tx {
+            Transaction::Transfer { to, amount, .. } => self.accounts.transfer(sender, to, Amount::from_base_units(*amount)),
+            Transaction::TerritoryClaim { address, stake, commitment, proof, .. } => {
+                let commitment = opened_commitment(sender, address, commitment, proof)?;
+                let triangle = triangle_at(address);
+                self.territories
+                    .claim_territory(&mut self.accounts, triangle, *address, sender.to_string(), Amount::from_base_units(*stake))
+                    .map_err(territory_error)?;
+                self.record_commitment(address, commitment);
+                Ok(())
+            }
+            Transaction::TerritoryConquest { address, stake, commitment, proof, .. } => {
+                let commitment = opened_commitment(sender, address, commitment, proof)?;
+                self.territories
+                    .conquer_territory(&mut self.accounts, address, sender.to_string(), Amount::from_base_units(*stake))
+                    .map_err(territory_error)?;
+                self.record_commitment(address, commitment);
+                Ok(())
+            }
+            Transaction::Stake { address, amount, .. } => {
+                let territory = self.territory_at(address)?;
+                let amount = Amount::from_base_units(*amount);
+                self.territories.defend_territory(&mut self.accounts, &territory, sender, amount).map_err(territory_error)
+            }
+            // Contracts do not run yet; their transactions only pay the fee.
+            Transaction::ContractDeploy { .. } | Transaction::ContractCall { .. } => Ok(()),
+        }
+    }
+
+    fn record_commitment(&mut self, address: &FractalAddress, commitment: TerritoryCommitment) {
+        let territory = self.territories.territories.get_mut(address).expect("the territory was just claimed");
+        territory.commitment = Some(commitment);
+    }
+
+    /// The address of the territory covering `address`.
+    fn territory_at(&self, address: &FractalAddress) -> Result<FractalAddress, StateError> {
+        self.territories
+            .territory_at(address)
+            .map(|territory| territory.address)
+            .ok_or_else(|| StateError::Territory("Territory not found".to_string()))
+    }
+}
+
+impl ChainState for State {
+    fn connect_block(&mut self, block: &Block) -> Result<(), String> {
+        let undo = self.apply_block_with_undo(block).map_err(|e| e.to_string())?;
+        self.undo.push(undo);
+        Ok(())
+    }
+
+    fn disconnect_block(&mut self, _block: &Block) {
+        let undo = self.undo.pop().expect("only connected blocks are disconnected");
+        self.revert(undo);
+    }
+}
+
+/// The commitment a claim of `address` by `sender` carries, once its proof
+/// shows that it opens to the address.
+fn opened_commitment(sender: &str, address: &FractalAddress, commitment: &[u8], proof: &[u8]) -> Result<TerritoryCommitment, StateError> {
+    let commitment = TerritoryCommitment::decode(commitment).map_err(StateError::Commitment)?;
+    let proof = AddressProof::decode(proof).map_err(StateError::Commitment)?;
+    proof.verify(&commitment, address, sender.as_bytes()).map_err(StateError::Commitment)?;
+    Ok(commitment)
+}
+
+fn put_str(hasher: &mut Sha256, value: &str) {
+    hasher.update((value.len() as u32).to_le_bytes());
+    hasher.update(value.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{transactions_root, BlockHeader};
+    use crate::blockchain::{genesis_block, Blockchain, ChainError};
+    use crate::core::mining::deterministic::DeterministicMiner;
+    use crate::core::mining::{Miner, TARGET_BLOCK_INTERVAL};
+    use crate::crypto::crypto::{Ed25519, KeyPair, SignatureScheme};
+    use crate::crypto::zk::{OwnershipProof, Statement, TerritoryOpening};
+    use crate::storage::memory::MemoryBlockStore;
+    use crate::transaction::TxMeta;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn meta(sender: &str, nonce: u64) -> TxMeta {
+        TxMeta { sender: sender.to_string(), nonce, fee: 1, signature: vec![] }
+    }
+
+    fn transfer(sender: &str, nonce: u64, to: &str, amount: u64) -> Transaction {
+        Transaction::Transfer { meta: meta(sender, nonce), to: to.to_string(), amount }
+    }
+
+    fn address(digits: &[u8]) -> FractalAddress {
+        FractalAddress::from_digits(digits).unwrap()
+    }
+
+    /// A commitment to `digits` and a proof that it opens to them, bound to
+    /// `sender`, both encoded, with the opening.
+    fn committed(sender: &str, digits: &[u8]) -> (Vec<u8>, Vec<u8>, TerritoryOpening) {
+        let address = address(digits);
+        let (commitment, opening) = TerritoryCommitment::commit(&triangle_at(&address), &address).unwrap();
+        let proof = AddressProof::prove(&commitment, &opening, sender.as_bytes());
+        (commitment.encode(), proof.encode(), opening)
+    }
+
+    fn claim(sender: &str, nonce: u64, digits: &[u8], stake: u64) -> (Transaction, TerritoryOpening) {
+        let (commitment, proof, opening) = committed(sender, digits);
+        (Transaction::TerritoryClaim { meta: meta(sender, nonce), address: address(digits), stake, commitment, proof }, opening)
+    }
+
+    fn conquest(sender: &str, nonce: u64, digits: &[u8], stake: u64) -> (Transaction, TerritoryOpening) {
+        let (commitment, proof, opening) = committed(sender, digits);
+        (Transaction::TerritoryConquest { meta: meta(sender, nonce), address: address(digits), stake, commitment, proof }, opening)
+    }
+
+    fn units(n: u64) -> Amount {
+        Amount::from_base_units(n)
+    }
+
+    fn account(balance: u64, nonce: u64, locked: u64) -> Account {
+        Account { balance: units(balance), nonce, locked: units(locked) }
+    }
+
+    fn funded() -> State {
+        State::with_balances([("alice", units(100)), ("bob", units(50))]).unwrap()
+    }
+
+    /// A block after genesis carrying `transactions` that commits to the
+    /// state root they and its reward leave `state` with. Only the state
+    /// checks it.
+    fn block_for(state: &State, transactions: Vec<Transaction>) -> Block {
+        let mut header = genesis_block().header;
+        let (transactions, state_root) = state.assemble(&header.producer, transactions);
+        header.height = 1;
+        header.state_root = state_root;
+        Block { header, transactions, signature: vec![] }
+    }
+
+    #[test]
+    fn test_transfer_pays_fee_and_advances_nonce() {
+        let mut state = funded();
+        state.apply_transaction(&transfer("alice", 0, "carol", 30)).unwrap();
+        assert_eq!(state.account("alice"), account(69, 1, 0));
+        assert_eq!(state.account("carol").balance, units(30));
+
+        assert_eq!(
+            state.apply_transaction(&transfer("alice", 0, "carol", 1)),
+            Err(StateError::BadNonce { account: "alice".to_string(), expected: 1, found: 0 })
+        );
+        // Too much to send: the fee is not taken and the nonce stays put.
+        assert!(matches!(state.apply_transaction(&transfer("alice", 1, "carol", 69)), Err(StateError::InsufficientFunds { .. })));
+        assert_eq!(state.account("alice"), account(69, 1, 0));
+    }
+
+    #[test]
+    fn test_territory_stakes_are_funded_from_accounts() {
+        let mut state = funded();
+        let (claim, _) = claim("alice", 0, &[2], 10);
+        state.apply_transaction(&claim).unwrap();
+        let stake = Transaction::Stake { meta: meta("alice", 1), address: address(&[2]), amount: 5 };
+        state.apply_transaction(&stake).unwrap();
+        assert_eq!(state.account("alice"), account(83, 2, 15));
+
+        let (weak, _) = conquest("bob", 0, &[2], 15);
+        assert!(matches!(state.apply_transaction(&weak), Err(StateError::Territory(_))));
+        let (conquest, _) = conquest("bob", 0, &[2], 16);
+        state.apply_transaction(&conquest).unwrap();
+        assert_eq!(state.account("alice"), account(98, 2, 0));
+        assert_eq!(state.account("bob"), account(33, 1, 16));
+        let territory = state.territories().territory_at(&address(&[2])).unwrap();
+        assert_eq!((territory.owner.as_str(), territory.staked_tokens), ("bob", units(16)));
+
+        let nowhere = Transaction::Stake { meta: meta("bob", 1), address: address(&[0]), amount: 1 };
+        assert_eq!(state.apply_transaction(&nowhere), Err(StateError::Territory("Territory not found".to_string())));
+    }
+
+    #[test]
+    fn test_conquest_keeps_territories_whole() {
+        let mut state = funded();
+        state.apply_transaction(&claim("alice", 0, &[2], 10).0).unwrap();
+        let root = state.root();
+
+        // Only the whole territory can be taken, so no sibling is left open.
+        let (child, _) = conquest("bob", 0, &[2, 1], 11);
+        assert_eq!(state.apply_transaction(&child), Err(StateError::Territory("Territory not found".to_string())));
+        let (sibling, _) = claim("bob", 0, &[2, 0], 1);
+        assert!(matches!(state.apply_transaction(&sibling), Err(StateError::Territory(_))));
+        assert_eq!(state.root(), root);
+
+        // Staking anywhere inside reaches the one territory that covers it.
+        for (nonce, digits) in [(1, &[2, 1][..]), (2, &[2, 0])] {
+            let stake = Transaction::Stake { meta: meta("alice", nonce), address: address(digits), amount: 1 };
+            state.apply_transaction(&stake).unwrap();
+        }
+        let territory = state.territories().territory_at(&address(&[2, 0, 3])).unwrap();
+        assert_eq!((territory.address, territory.staked_tokens), (address(&[2]), units(12)));
+
+        state.apply_transaction(&conquest("bob", 0, &[2], 13).0).unwrap();
+        for digits in [&[2, 1][..], &[2, 0]] {
+            let territory = state.territories().territory_at(&address(digits)).unwrap();
+            assert_eq!((territory.owner.as_str(), territory.triangle), ("bob", triangle_at(&address(&[2]))));
+        }
+        assert_eq!(state.territories().territories.len(), 1);
+    }
+
+    #[test]
+    fn test_ownership_is_checked_against_the_claimants_commitment() {
+        let mut state = funded();
+        let (alice_claim, alice_opening) = claim("alice", 0, &[2], 10);
+        state.apply_transaction(&alice_claim).unwrap();
+        let territory = state.territories().territory_at(&address(&[2])).unwrap();
+        let recorded = territory.commitment.clone().unwrap();
+        let statement = Statement::InsideRegion { region: address(&[2]), min_depth: 1 };
+        let alice_proof = OwnershipProof::prove(&recorded, &alice_opening, &statement, b"alice").unwrap();
+        assert_eq!(state.territories().verify_ownership("alice", &statement, &alice_proof), Ok(()));
+        assert!(state.territories().verify_ownership("bob", &statement, &alice_proof).is_err());
+
+        // bob can commit to alice's territory and prove things about his own
+        // commitment, but it is not the one her claim recorded.
+        let address_two = address(&[2]);
+        let (bobs, bob_opening) = TerritoryCommitment::commit(&triangle_at(&address_two), &address_two).unwrap();
+        for context in ["bob", "alice"] {
+            let proof = OwnershipProof::prove(&bobs, &bob_opening, &statement, context.as_bytes()).unwrap();
+            assert_eq!(proof.verify(&bobs, &statement, context.as_bytes()), Ok(()));
+            assert!(state.territories().verify_ownership(context, &statement, &proof).is_err());
+        }
+
+        // A claim's commitment must open to its address, for its sender.
+        let (commitment, proof, _) = committed("alice", &[0]);
+        let lifted = Transaction::TerritoryClaim { meta: meta("bob", 0), address: address(&[0]), stake: 1, commitment, proof };
+        assert_eq!(state.apply_transaction(&lifted), Err(StateError::Commitment(ZkError::InvalidProof)));
+        let (commitment, proof, _) = committed("bob", &[0, 1]);
+        let elsewhere = Transaction::TerritoryClaim { meta: meta("bob", 0), address: address(&[0]), stake: 1, commitment, proof };
+        assert_eq!(state.apply_transaction(&elsewhere), Err(StateError::Commitment(ZkError::InvalidProof)));
+        assert_eq!(state.account("bob"), account(50, 0, 0));
+
+        // Conquest records the conqueror's commitment in place of alice's.
+        let (bob_conquest, bob_opening) = conquest("bob", 0, &[2], 11);
+        state.apply_transaction(&bob_conquest).unwrap();
+        let territory = state.territories().territory_at(&address(&[2])).unwrap();
+        let bob_proof = OwnershipProof::prove(territory.commitment.as_ref().unwrap(), &bob_opening, &statement, b"bob").unwrap();
+        assert_eq!(state.territories().verify_ownership("bob", &statement, &bob_proof), Ok(()));
+        assert!(state.territories().verify_ownership("alice", &statement, &alice_proof).is_err());
+
+        // The recorded commitment is part of the state root.
+        let root = state.root();
+        state.territories.territories.values_mut().for_each(|territory| territory.commitment = None);
+        assert_ne!(state.root(), root);
+    }
+
+    #[test]
+    fn test_block_applies_atomically() {
+        let mut state = funded();
+        let root = state.root();
+        let good = block_for(&state, vec![transfer("alice", 0, "bob", 10)]);
+
+        let mut failing = good.clone();
+        failing.transactions.push(transfer("bob", 0, "alice", 500));
+        assert!(matches!(state.apply_block(&failing), Err(StateError::Transaction { index: 1, .. })));
+        assert_eq!(state.root(), root);
+
+        let mut wrong_root = good.clone();
+        wrong_root.header.state_root = root;
+        assert!(matches!(state.apply_block(&wrong_root), Err(StateError::StateRootMismatch { .. })));
+        assert_eq!(state.root(), root);
+
+        state.apply_block(&good).unwrap();
+        assert_eq!(state.root(), good.header.state_root);
+        assert_eq!(state.account("bob").balance, units(60));
+    }
+
+    #[test]
+    fn test_assemble_skips_what_does_not_apply() {
+        let state = funded();
+        let candidates = vec![transfer("alice", 0, "bob", 10), transfer("carol", 0, "bob", 1), transfer("alice", 1, "bob", 10)];
+        let (transactions, root) = state.assemble(&Address::default(), candidates.clone());
+        assert_eq!(transactions, vec![candidates[0].clone(), candidates[2].clone()]);
+        assert_ne!(root, state.root());
+    }
+
+    #[test]
+    fn test_root_ignores_emptied_accounts() {
+        let mut state = State::with_balances([("alice", units(1))]).unwrap();
+        let mut emptied = state.accounts.clone();
+        emptied.debit("alice", units(1)).unwrap();
+        assert_eq!(emptied, Accounts::default());
+        state.accounts = emptied;
+        assert_eq!(state.root(), State::new().root());
+    }
+
+    #[test]
+    fn test_undo_keeps_only_what_each_block_touched() {
+        let mut state = funded();
+        // Bystanders that no block touches, so they never enter the undo data.
+        for i in 0..50 {
+            state.accounts.credit(&format!("holder{}", i), units(1)).unwrap();
+        }
+        let mut roots = vec![state.root()];
+        let blocks = [
+            vec![transfer("alice", 0, "carol", 5), claim("alice", 1, &[1], 10).0],
+            vec![conquest("bob", 0, &[1], 11).0],
+            vec![Transaction::Stake { meta: meta("bob", 1), address: address(&[1]), amount: 2 }, transfer("carol", 0, "alice", 4)],
+        ];
+        for transactions in blocks {
+            let block = block_for(&state, transactions);
+            state.connect_block(&block).unwrap();
+            roots.push(state.root());
+        }
+        let touched: Vec<(usize, usize)> = state.undo.iter().map(|undo| (undo.accounts.len(), undo.territories.len())).collect();
+        // Each block also pays its producer.
+        assert_eq!(touched, vec![(3, 1), (3, 1), (4, 1)]);
+
+        while let Some(root) = roots.pop() {
+            assert_eq!(state.root(), root);
+            if !roots.is_empty() {
+                state.disconnect_block(&genesis_block());
+            }
+        }
+        assert!(state.territories().territories.is_empty());
+        assert_eq!(state.account("alice"), account(100, 0, 0));
+    }
+
+    /// The next block on `chain`, produced by `producer` and carrying
+    /// whichever of `candidates` apply, committing to `state_root` if given.
+    fn mine_next(chain: &Blockchain, state: &State, producer: &KeyPair, candidates: Vec<Transaction>, state_root: Option<[u8; 32]>) -> Block {
+        let tip = chain.tip();
+        let (transactions, assembled) = state.assemble(&producer.address(), candidates);
+        let template = BlockHeader {
+            height: tip.height() + 1,
+            previous_hash: tip.hash(),
+            merkle_root: transactions_root(&transactions),
+            state_root: state_root.unwrap_or(assembled),
+            timestamp: tip.header.timestamp + TARGET_BLOCK_INTERVAL,
+            difficulty: chain.next_difficulty(),
+            producer: producer.address(),
+            ..tip.header
+        };
+        let mut block = Block { header: DeterministicMiner.mine(&template).unwrap(), transactions, signature: vec![] };
+        block.sign(producer).unwrap();
+        block
+    }
+
+    #[test]
+    fn test_follows_the_chain_and_reverts_on_disconnect() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut chain = Blockchain::with_store(Box::new(MemoryBlockStore::new()), Box::new(Rc::clone(&state))).unwrap();
+        let genesis = chain.tip().clone();
+        let root = state.borrow().root();
+        assert_eq!(genesis.header.state_root, root);
+        let producer = Ed25519.keypair_from_seed(&[1; 32]);
+
+        // Nobody has funds, so a transfer cannot go in.
+        let (transactions, _) = state.borrow().assemble(&producer.address(), vec![transfer("alice", 0, "bob", 1)]);
+        assert!(transactions.is_empty());
+        let wrong = mine_next(&chain, &state.borrow(), &producer, vec![], Some(root));
+        assert!(matches!(chain.add_block(wrong), Err(ChainError::State { .. })));
+        let right = mine_next(&chain, &state.borrow(), &producer, vec![], None);
+        chain.add_block(right.clone()).unwrap();
+        assert_eq!(chain.tip(), &right);
+        assert_eq!(state.borrow().account(&producer.address().to_string()), Account { balance: BLOCK_REWARD, ..Account::default() });
+
+        state.borrow_mut().disconnect_block(&right);
+        assert_eq!(state.borrow().root(), root);
+    }
+
+    #[test]
+    fn test_block_rewards_fund_accounts_through_the_chain() {
+        let state = Rc::new(RefCell::new(State::new()));
+        let mut chain = Blockchain::with_store(Box::new(MemoryBlockStore::new()), Box::new(Rc::clone(&state))).unwrap();
+        let producer = Ed25519.keypair_from_seed(&[1; 32]);
+        let miner = producer.address().to_string();
+        let block = mine_next(&chain, &state.borrow(), &producer, vec![], None);
+        chain.add_block(block).unwrap();
+
+        // The reward pays for a transfer with a fee, which goes back to the producer.
+        let alice = Ed25519.keypair_from_seed(&[2; 32]).address().to_string();
+        let mut pay = Transaction::Transfer { meta: TxMeta { sender: miner.clone(), nonce: 0, fee: 7, signature: vec![] }, to: alice.clone(), amount: 1000 };
+        pay.sign(&producer).unwrap();
+        let block = mine_next(&chain, &state.borrow(), &producer, vec![pay.clone()], None);
+        assert_eq!(block.transactions, vec![pay]);
+        chain.add_block(block).unwrap();
+
+        let state = state.borrow();
+        assert_eq!(state.account(&alice).balance, units(1000));
+        let earned = BLOCK_REWARD.checked_mul(2).unwrap().checked_sub(units(1000)).unwrap();
+        assert_eq!(state.account(&miner), Account { balance: earned, nonce: 1, locked: Amount::ZERO });
+        assert_eq!(chain.tip().header.state_root, state.root());
+    }
+}
@@ -0,0 +1,121 @@
+//! Storage interface for the block chain.
+
+use crate::block::Block;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+/// Errors from reading or writing a [`BlockStore`].
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    /// A stored record failed its checksum or could not be decoded, anywhere
+    /// but the tail of the newest segment.
+    Corrupt { segment: u32, offset: u64 },
+    /// A block's height must be one more than its parent's.
+    NonContiguous { expected: u64, found: u64 },
+    /// Blocks are stored parents first, so every non-genesis block's parent
+    /// must already be present.
+    UnknownParent([u8; 32]),
+    Duplicate([u8; 32]),
+    Encoding(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "block store I/O error: {}", e),
+            StoreError::Corrupt { segment, offset } => write!(f, "corrupt record in segment {} at offset {}", segment, offset),
+            StoreError::NonContiguous { expected, found } => write!(f, "expected block at height {}, got {}", expected, found),
+            StoreError::UnknownParent(hash) => write!(f, "parent block {} is not stored", hex::encode(hash)),
+            StoreError::Duplicate(hash) => write!(f, "block {} is already stored", hex::encode(hash)),
+            StoreError::Encoding(e) => write!(f, "block encoding error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+/// Append-only storage of every block seen, on the best chain or not.
+///
+/// Blocks are kept in the order they were appended, which always puts a
+/// parent before its children, and indexed by hash and by height.
+pub trait BlockStore {
+    /// Stores `block`. The block is durable once this returns `Ok`.
+    fn append(&mut self, block: &Block) -> Result<(), StoreError>;
+    /// The block at `position` in append order.
+    fn block_at(&self, position: u64) -> Result<Option<Block>, StoreError>;
+    fn block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<Block>, StoreError>;
+    /// Every stored block at `height`, one per competing branch.
+    fn blocks_at_height(&self, height: u64) -> Result<Vec<Block>, StoreError>;
+    /// Number of blocks stored.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every stored block in append order.
+    fn load_all(&self) -> Result<Vec<Block>, StoreError> {
+        (0..self.len())
+            .map(|position| self.block_at(position)?.ok_or(StoreError::NonContiguous { expected: position, found: self.len() }))
+            .collect()
+    }
+}
+
+/// Hash and height indexes over blocks numbered by append position.
+#[derive(Default)]
+pub(crate) struct BlockIndex {
+    by_hash: HashMap<[u8; 32], u64>,
+    by_height: HashMap<u64, Vec<u64>>,
+    heights: Vec<u64>,
+}
+
+impl BlockIndex {
+    /// Checks that `block` links to a stored parent at the height below it,
+    /// or is a genesis block.
+    pub(crate) fn check(&self, block: &Block) -> Result<(), StoreError> {
+        let hash = block.hash();
+        if self.by_hash.contains_key(&hash) {
+            return Err(StoreError::Duplicate(hash));
+        }
+        let previous = block.previous_hash();
+        let expected = if *previous == [0; 32] {
+            0
+        } else {
+            let parent = self.by_hash.get(previous).ok_or(StoreError::UnknownParent(*previous))?;
+            self.heights[*parent as usize] + 1
+        };
+        if block.height() != expected {
+            return Err(StoreError::NonContiguous { expected, found: block.height() });
+        }
+        Ok(())
+    }
+
+    /// Records `block` at the next position and returns that position.
+    pub(crate) fn insert(&mut self, block: &Block) -> u64 {
+        let position = self.heights.len() as u64;
+        self.by_hash.insert(block.hash(), position);
+        self.by_height.entry(block.height()).or_default().push(position);
+        self.heights.push(block.height());
+        position
+    }
+
+    pub(crate) fn position(&self, hash: &[u8; 32]) -> Option<u64> {
+        self.by_hash.get(hash).copied()
+    }
+
+    pub(crate) fn at_height(&self, height: u64) -> &[u64] {
+        self.by_height.get(&height).map_or(&[], |positions| positions.as_slice())
+    }
+
+    pub(crate) fn len(&self) -> u64 {
+        self.heights.len() as u64
+    }
+}
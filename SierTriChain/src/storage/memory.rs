@@ -0,0 +1,87 @@
+//! Volatile block store for tests and throwaway nodes.
+
+use crate::block::Block;
+use crate::storage::block_store::{BlockIndex, BlockStore, StoreError};
+
+#[derive(Default)]
+pub struct MemoryBlockStore {
+    blocks: Vec<Block>,
+    index: BlockIndex,
+}
+
+impl MemoryBlockStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl BlockStore for MemoryBlockStore {
+    fn append(&mut self, block: &Block) -> Result<(), StoreError> {
+        self.index.check(block)?;
+        self.index.insert(block);
+        self.blocks.push(block.clone());
+        Ok(())
+    }
+
+    fn block_at(&self, position: u64) -> Result<Option<Block>, StoreError> {
+        Ok(self.blocks.get(position as usize).cloned())
+    }
+
+    fn block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<Block>, StoreError> {
+        match self.index.position(hash) {
+            Some(position) => self.block_at(position),
+            None => Ok(None),
+        }
+    }
+
+    fn blocks_at_height(&self, height: u64) -> Result<Vec<Block>, StoreError> {
+        Ok(self.index.at_height(height).iter().map(|&position| self.blocks[position as usize].clone()).collect())
+    }
+
+    fn len(&self) -> u64 {
+        self.index.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+
+    fn block(parent: Option<&Block>, nonce: u64) -> Block {
+        let header = BlockHeader {
+            height: parent.map_or(0, |p| p.height() + 1),
+            previous_hash: parent.map_or([0; 32], Block::hash),
+            nonce,
+            ..BlockHeader::test_template()
+        };
+        Block { header, transactions: vec![], signature: vec![] }
+    }
+
+    #[test]
+    fn test_indexes_by_height_and_hash() {
+        let mut store = MemoryBlockStore::new();
+        let genesis = block(None, 0);
+        let (left, right) = (block(Some(&genesis), 1), block(Some(&genesis), 2));
+        for b in [&genesis, &left, &right] {
+            store.append(b).unwrap();
+        }
+        assert_eq!(store.block_at(1).unwrap(), Some(left.clone()));
+        assert_eq!(store.block_by_hash(&right.hash()).unwrap(), Some(right.clone()));
+        assert_eq!(store.block_by_hash(&[9; 32]).unwrap(), None);
+        assert_eq!(store.blocks_at_height(1).unwrap(), vec![left, right]);
+    }
+
+    #[test]
+    fn test_rejects_orphans_and_duplicates() {
+        let mut store = MemoryBlockStore::new();
+        let genesis = block(None, 0);
+        store.append(&genesis).unwrap();
+        let orphan = block(Some(&block(None, 9)), 1);
+        assert!(matches!(store.append(&orphan), Err(StoreError::UnknownParent(_))));
+        assert!(matches!(store.append(&genesis), Err(StoreError::Duplicate(_))));
+        let mut skipped = block(Some(&genesis), 1);
+        skipped.header.height = 2;
+        assert!(matches!(store.append(&skipped), Err(StoreError::NonContiguous { expected: 1, found: 2 })));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod block_store;
+pub mod memory;
+pub mod segment;
@@ -0,0 +1,313 @@
+//! Append-only block store in numbered segment files.
+//!
+//! A store is a directory of `blocks-NNNNNN.seg` files, each a run of records
+//!
+//! ```text
+//! length: u32 LE | checksum: first 4 bytes of SHA-256(payload) | payload
+//! ```
+//!
+//! where the payload is the binary encoding of one block. Blocks are written in
+//! the order they arrive, parents first, and a new segment is started once the current one would
+//! grow past the segment size. Every append is synced before it returns, so
+//! the only damage a crash can leave is a partial record at the end of the
+//! newest segment. Opening the store rebuilds the height and hash indexes by
+//! scanning every record, and cuts such a tail off.
+
+use crate::block::Block;
+use crate::storage::block_store::{BlockIndex, BlockStore, StoreError};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Size at which a new segment file is started.
+pub const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+const HEADER_LEN: u64 = 8;
+
+/// Where a block's payload sits on disk.
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    segment: u32,
+    offset: u64,
+    len: u32,
+}
+
+pub struct SegmentStore {
+    dir: PathBuf,
+    segment_size: u64,
+    locations: Vec<Location>,
+    index: BlockIndex,
+    active: File,
+    active_segment: u32,
+    active_len: u64,
+}
+
+impl SegmentStore {
+    /// Opens the store in `dir`, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::open_with_segment_size(dir, DEFAULT_SEGMENT_SIZE)
+    }
+
+    pub fn open_with_segment_size(dir: impl AsRef<Path>, segment_size: u64) -> Result<Self, StoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let segments = Self::segment_ids(&dir)?;
+        let mut locations = Vec::new();
+        let mut index = BlockIndex::default();
+        let mut active_len = 0;
+        for (i, &segment) in segments.iter().enumerate() {
+            let is_last = i + 1 == segments.len();
+            active_len = Self::scan(&dir, segment, is_last, &mut locations, &mut index)?;
+        }
+        let active_segment = segments.last().copied().unwrap_or(0);
+        let active = OpenOptions::new().create(true).append(true).open(segment_path(&dir, active_segment))?;
+        if segments.is_empty() {
+            sync_dir(&dir)?;
+        }
+        Ok(SegmentStore { dir, segment_size, locations, index, active, active_segment, active_len })
+    }
+
+    /// Indexes the records of one segment and returns its valid length.
+    ///
+    /// A bad record in the last segment is a torn write: the file is cut back
+    /// to the last good record. Anywhere else it is corruption.
+    fn scan(
+        dir: &Path,
+        segment: u32,
+        is_last: bool,
+        locations: &mut Vec<Location>,
+        index: &mut BlockIndex,
+    ) -> Result<u64, StoreError> {
+        let path = segment_path(dir, segment);
+        let data = fs::read(&path)?;
+        let mut offset = 0u64;
+        while offset < data.len() as u64 {
+            let Some((block, len)) = decode_record(&data[offset as usize..]) else {
+                if !is_last {
+                    return Err(StoreError::Corrupt { segment, offset });
+                }
+                let file = OpenOptions::new().write(true).open(&path)?;
+                file.set_len(offset)?;
+                file.sync_all()?;
+                break;
+            };
+            index.check(&block).map_err(|_| StoreError::Corrupt { segment, offset })?;
+            index.insert(&block);
+            locations.push(Location { segment, offset: offset + HEADER_LEN, len });
+            offset += HEADER_LEN + len as u64;
+        }
+        Ok(offset)
+    }
+
+    /// Segment numbers present in `dir`, in order.
+    fn segment_ids(dir: &Path) -> Result<Vec<u32>, StoreError> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let id: Option<u32> = name
+                .to_str()
+                .and_then(|name| name.strip_prefix("blocks-"))
+                .and_then(|name| name.strip_suffix(".seg"))
+                .and_then(|id| id.parse().ok());
+            ids.extend(id);
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn roll_segment(&mut self) -> Result<(), StoreError> {
+        let next = self.active_segment + 1;
+        self.active = OpenOptions::new().create(true).append(true).open(segment_path(&self.dir, next))?;
+        sync_dir(&self.dir)?;
+        self.active_segment = next;
+        self.active_len = 0;
+        Ok(())
+    }
+}
+
+impl BlockStore for SegmentStore {
+    fn append(&mut self, block: &Block) -> Result<(), StoreError> {
+        self.index.check(block)?;
+        let record = encode_record(block)?;
+        if self.active_len > 0 && self.active_len + record.len() as u64 > self.segment_size {
+            self.roll_segment()?;
+        }
+        if let Err(e) = self.active.write_all(&record).and_then(|_| self.active.sync_data()) {
+            // Drop whatever part of the record made it out; the next open
+            // would cut it off anyway.
+            let _ = self.active.set_len(self.active_len);
+            return Err(e.into());
+        }
+        self.index.insert(block);
+        self.locations.push(Location {
+            segment: self.active_segment,
+            offset: self.active_len + HEADER_LEN,
+            len: (record.len() as u64 - HEADER_LEN) as u32,
+        });
+        self.active_len += record.len() as u64;
+        Ok(())
+    }
+
+    fn block_at(&self, position: u64) -> Result<Option<Block>, StoreError> {
+        let Some(location) = self.locations.get(position as usize) else {
+            return Ok(None);
+        };
+        let mut file = File::open(segment_path(&self.dir, location.segment))?;
+        file.seek(SeekFrom::Start(location.offset - HEADER_LEN))?;
+        let mut record = vec![0; HEADER_LEN as usize + location.len as usize];
+        file.read_exact(&mut record)?;
+        let corrupt = StoreError::Corrupt { segment: location.segment, offset: location.offset - HEADER_LEN };
+        let (block, _) = decode_record(&record).ok_or(corrupt)?;
+        Ok(Some(block))
+    }
+
+    fn block_by_hash(&self, hash: &[u8; 32]) -> Result<Option<Block>, StoreError> {
+        match self.index.position(hash) {
+            Some(position) => self.block_at(position),
+            None => Ok(None),
+        }
+    }
+
+    fn blocks_at_height(&self, height: u64) -> Result<Vec<Block>, StoreError> {
+        self.index.at_height(height).iter().filter_map(|&position| self.block_at(position).transpose()).collect()
+    }
+
+    fn len(&self) -> u64 {
+        self.locations.len() as u64
+    }
+}
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("blocks-{:06}.seg", segment))
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let digest = Sha256::digest(payload);
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+fn encode_record(block: &Block) -> Result<Vec<u8>, StoreError> {
+    let payload = block.encode();
+    let len = u32::try_from(payload.len()).map_err(|_| StoreError::Encoding("block too large".to_string()))?;
+    let mut record = Vec::with_capacity(HEADER_LEN as usize + payload.len());
+    record.extend_from_slice(&len.to_le_bytes());
+    record.extend_from_slice(&checksum(&payload));
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+/// The block at the start of `data` and its payload length, or `None` if the
+/// record is incomplete or damaged.
+fn decode_record(data: &[u8]) -> Option<(Block, u32)> {
+    let header = data.get(..HEADER_LEN as usize)?;
+    let len = u32::from_le_bytes(header[..4].try_into().ok()?);
+    let payload = data.get(HEADER_LEN as usize..HEADER_LEN as usize + len as usize)?;
+    if checksum(payload) != header[4..] {
+        return None;
+    }
+    let block = Block::decode(payload).ok()?;
+    Some((block, len))
+}
+
+/// Makes a new directory entry durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<(), StoreError> {
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<(), StoreError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+
+    /// A chain of `count` linked blocks; the proofs are not real.
+    fn chain(count: u64) -> Vec<Block> {
+        let mut blocks: Vec<Block> = Vec::new();
+        for index in 0..count {
+            let header = BlockHeader {
+                height: index,
+                previous_hash: blocks.last().map_or([0; 32], Block::hash),
+                timestamp: index,
+                nonce: index,
+                ..BlockHeader::test_template()
+            };
+            blocks.push(Block { header, transactions: vec![], signature: vec![] });
+        }
+        blocks
+    }
+
+    fn filled(dir: &Path, count: u64, segment_size: u64) -> SegmentStore {
+        let mut store = SegmentStore::open_with_segment_size(dir, segment_size).unwrap();
+        for block in chain(count) {
+            store.append(&block).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn test_blocks_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(filled(dir.path(), 3, DEFAULT_SEGMENT_SIZE));
+        let store = SegmentStore::open(dir.path()).unwrap();
+        let blocks = chain(3);
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.block_at(1).unwrap(), Some(blocks[1].clone()));
+        assert_eq!(store.block_by_hash(&blocks[2].hash()).unwrap(), Some(blocks[2].clone()));
+        assert_eq!(store.blocks_at_height(2).unwrap(), vec![blocks[2].clone()]);
+        assert_eq!(store.block_at(3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rolls_over_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(filled(dir.path(), 5, 1));
+        assert_eq!(SegmentStore::segment_ids(dir.path()).unwrap(), vec![0, 1, 2, 3, 4]);
+        let mut store = SegmentStore::open_with_segment_size(dir.path(), 1).unwrap();
+        let blocks = chain(6);
+        assert_eq!(store.load_all().unwrap(), blocks[..5]);
+        store.append(&blocks[5]).unwrap();
+        assert_eq!(store.block_at(5).unwrap(), Some(blocks[5].clone()));
+    }
+
+    #[test]
+    fn test_truncated_tail_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(filled(dir.path(), 3, DEFAULT_SEGMENT_SIZE));
+        let path = segment_path(dir.path(), 0);
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 5).unwrap();
+
+        let mut store = SegmentStore::open(dir.path()).unwrap();
+        assert_eq!(store.len(), 2);
+        // The torn record is gone from disk, so appending picks up cleanly.
+        store.append(&chain(3)[2]).unwrap();
+        drop(store);
+        assert_eq!(SegmentStore::open(dir.path()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_corruption_before_tail_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(filled(dir.path(), 3, 1));
+        let path = segment_path(dir.path(), 1);
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(&path, data).unwrap();
+        assert!(matches!(SegmentStore::open(dir.path()), Err(StoreError::Corrupt { segment: 1, offset: 0 })));
+    }
+
+    #[test]
+    fn test_rejects_orphan_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = filled(dir.path(), 1, DEFAULT_SEGMENT_SIZE);
+        assert!(matches!(store.append(&chain(3)[2]), Err(StoreError::UnknownParent(_))));
+    }
+}
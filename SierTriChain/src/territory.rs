@@ -1,150 +1,354 @@
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_territory_basic() {
         assert_eq!(2 + 2, 4);
     }
+
+    fn plot(digits: &[u8]) -> (Triangle, FractalAddress) {
+        let address = FractalAddress::from_digits(digits).unwrap();
+        (triangle_at(&address), address)
+    }
+
+    fn units(n: u64) -> Amount {
+        Amount::from_base_units(n)
+    }
+
+    fn funded() -> Accounts {
+        let mut accounts = Accounts::default();
+        for name in ["alice", "bob", "carol"] {
+            accounts.credit(name, units(100)).unwrap();
+        }
+        accounts
+    }
+
+    fn claim(registry: &mut TerritoryRegistry, accounts: &mut Accounts, digits: &[u8], owner: &str) -> Result<FractalAddress, String> {
+        let (triangle, address) = plot(digits);
+        registry.claim_territory(accounts, triangle, address, owner.to_string(), units(1))?;
+        Ok(address)
+    }
+
+    #[test]
+    fn test_overlapping_claim_rejected() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        claim(&mut registry, &mut accounts, &[3], "alice").unwrap();
+        assert!(claim(&mut registry, &mut accounts, &[3, 0], "bob").is_err());
+        assert!(claim(&mut registry, &mut accounts, &[0], "bob").is_ok());
+        claim(&mut registry, &mut accounts, &[1, 2, 1], "carol").unwrap();
+        assert!(claim(&mut registry, &mut accounts, &[1], "bob").is_err());
+        assert!(claim(&mut registry, &mut accounts, &[1, 2], "bob").is_err());
+        assert!(claim(&mut registry, &mut accounts, &[1, 2, 2], "bob").is_ok());
+    }
+
+    #[test]
+    fn test_deep_siblings_are_distinct_territories() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        // Far below any rounded coordinate hash's precision.
+        let parent = FractalAddress::from_digits(&[1; 41]).unwrap();
+        for child in parent.children().unwrap() {
+            let (triangle, _) = plot(&child.to_digits());
+            registry.claim_territory(&mut accounts, triangle, child, "alice".to_string(), units(1)).unwrap();
+            assert_eq!(registry.territory_at(&child.child(2).unwrap()).unwrap().address, child);
+        }
+        assert_eq!(registry.territories.len(), 4);
+        assert!(registry.territory_at(&parent).is_none());
+    }
+
+    #[test]
+    fn test_yield_counts_only_edge_adjacent_territories() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        let middle = claim(&mut registry, &mut accounts, &[3], "alice").unwrap();
+        claim(&mut registry, &mut accounts, &[0], "alice").unwrap();
+        claim(&mut registry, &mut accounts, &[1], "bob").unwrap();
+        let corner = claim(&mut registry, &mut accounts, &[2], "alice").unwrap();
+        // The middle triangle borders both of alice's corners; the corners only touch.
+        let one = Amount::from_tokens(1).unwrap();
+        // 3^1.2 and 2^1.2, rounded down to base units.
+        assert_eq!(registry.yield_farming(&middle, one).unwrap(), units(373_719_281));
+        assert_eq!(registry.yield_farming(&corner, one).unwrap(), units(229_739_670));
+        assert_eq!(registry.get_territory(&middle).unwrap().yield_tokens, units(373_719_281));
+    }
+
+    #[test]
+    fn test_cryptographic_claim_requires_real_adjacency() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        let corner = claim(&mut registry, &mut accounts, &[0], "alice").unwrap();
+        let (far, far_address) = plot(&[1]);
+        assert!(registry.cryptographic_claim(&mut accounts, far, far_address, "alice".to_string(), units(1), vec![]).is_err());
+        let (middle, middle_address) = plot(&[3]);
+        assert!(registry.cryptographic_claim(&mut accounts, middle, middle_address, "alice".to_string(), units(1), vec![corner]).is_ok());
+    }
+
+    #[test]
+    fn test_cryptographic_claim_rejects_forged_neighbours() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        claim(&mut registry, &mut accounts, &[3, 3], "alice").unwrap();
+        let far = claim(&mut registry, &mut accounts, &[1, 1], "carol").unwrap();
+        // 3.0 borders 3.3, but 1.1 is listed as a neighbour too.
+        let (triangle, address) = plot(&[3, 0, 2]);
+        assert!(registry.cryptographic_claim(&mut accounts, triangle, address, "bob".to_string(), units(1), vec![far]).is_err());
+        assert!(registry.cryptographic_claim(&mut accounts, triangle, address, "bob".to_string(), units(1), vec![]).is_ok());
+    }
+
+    #[test]
+    fn test_cryptographic_claim_rejects_mismatched_address() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        claim(&mut registry, &mut accounts, &[0], "alice").unwrap();
+        let (middle, _) = plot(&[3]);
+        let (_, wrong) = plot(&[1]);
+        assert!(registry.cryptographic_claim(&mut accounts, middle, wrong, "bob".to_string(), units(1), vec![]).is_err());
+    }
+
+    #[test]
+    fn test_conquest_takes_the_whole_territory() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        let address = claim(&mut registry, &mut accounts, &[0], "alice").unwrap();
+        let (_, inside) = plot(&[0, 3]);
+        assert!(registry.conquer_territory(&mut accounts, &inside, "bob".to_string(), units(5)).is_err());
+        registry.conquer_territory(&mut accounts, &address, "bob".to_string(), units(5)).unwrap();
+        let territory = registry.get_territory(&address).unwrap();
+        assert_eq!((territory.owner.as_str(), territory.triangle), ("bob", triangle_at(&address)));
+    }
+
+    #[test]
+    fn test_stakes_are_locked_in_accounts() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = funded();
+        let address = claim(&mut registry, &mut accounts, &[0], "alice").unwrap();
+        assert!(registry.defend_territory(&mut accounts, &address, "bob", units(10)).is_err());
+        registry.defend_territory(&mut accounts, &address, "alice", units(10)).unwrap();
+        assert_eq!((accounts.get("alice").balance, accounts.get("alice").locked), (units(89), units(11)));
+        assert!(registry.defend_territory(&mut accounts, &address, "alice", units(90)).is_err());
+
+        // bob cannot put up more than he has, and a failed attempt costs nothing.
+        assert!(registry.conquer_territory(&mut accounts, &address, "bob".to_string(), units(101)).is_err());
+        assert!(registry.conquer_territory(&mut accounts, &address, "bob".to_string(), units(11)).is_err());
+        assert_eq!(accounts.get("bob").balance, units(100));
+
+        // Winning locks bob's stake and releases alice's.
+        registry.conquer_territory(&mut accounts, &address, "bob".to_string(), units(12)).unwrap();
+        assert_eq!((accounts.get("alice").balance, accounts.get("alice").locked), (units(100), Amount::ZERO));
+        assert_eq!((accounts.get("bob").balance, accounts.get("bob").locked), (units(88), units(12)));
+        assert_eq!(registry.get_territory(&address).unwrap().staked_tokens, units(12));
+    }
+
+    #[test]
+    fn test_unfunded_claim_changes_nothing() {
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = Accounts::default();
+        assert!(claim(&mut registry, &mut accounts, &[0], "dave").is_err());
+        assert!(registry.territories.is_empty());
+        assert_eq!(accounts, Accounts::default());
+    }
 }
 // Territorial ownership system for Triangular Territory Cryptocurrency
 // Each triangle is owned by an address and may have staked tokens for defense
 
+use crate::crypto::zk::{OwnershipProof, Statement, TerritoryCommitment, ZkError};
+use crate::core::validation::verify_geometric_proof;
 use crate::geometry::triangle::{Triangle};
-use crate::geometry::subdivision::FractalAddress;
-use std::collections::HashMap;
+use crate::geometry::subdivision::{triangle_at, FractalAddress};
+use crate::defi::token::{Amount, Rounding};
+use crate::state::Accounts;
+use rust_decimal::{Decimal, MathematicalOps};
+use std::collections::BTreeMap;
 
 #[derive(Clone)]
 pub struct Territory {
+    // Always the lattice triangle at `address`
     pub triangle: Triangle,
     pub address: FractalAddress,
     pub owner: String, // Could be a wallet address
-    pub staked_tokens: f64,
-    pub yield_tokens: f64,
+    // Locked in the owner's account for as long as they hold the territory
+    pub staked_tokens: Amount,
+    pub yield_tokens: Amount,
+    // Recorded by the owner's claim or conquest; ownership proofs are checked against it
+    pub commitment: Option<TerritoryCommitment>,
 }
 
+#[derive(Clone, Default)]
 pub struct TerritoryRegistry {
-    pub territories: HashMap<String, Territory>, // Key: geometric hash
+    // Keyed by fractal address, which is exact at every depth, unlike a rounded hash
+    pub territories: BTreeMap<FractalAddress, Territory>,
 }
 
 impl TerritoryRegistry {
     pub fn new() -> Self {
-        Self { territories: HashMap::new() }
+        Self { territories: BTreeMap::new() }
     }
 
-    // Claim a territory if geometric proof and adjacency are valid
-    pub fn claim_territory(&mut self, triangle: Triangle, address: FractalAddress, owner: String, staked_tokens: f64) -> Result<(), String> {
-        let hash = crate::hash::geometric_hash(&triangle, 8);
-        if self.territories.contains_key(&hash) {
+    // Claim a territory if it is a valid triangle that overlaps no existing territory,
+    // locking the stake in the owner's account
+    pub fn claim_territory(&mut self, accounts: &mut Accounts, triangle: Triangle, address: FractalAddress, owner: String, staked_tokens: Amount) -> Result<(), String> {
+        if self.territories.contains_key(&address) {
             return Err("Territory already claimed".to_string());
         }
-        // TODO: Verify geometric proof and adjacency
+        if !verify_geometric_proof(&triangle, 1e-8) {
+            return Err("Invalid geometric proof".to_string());
+        }
+        if triangle_at(&address) != triangle {
+            return Err("Triangle does not match its fractal address".to_string());
+        }
+        self.check_unoccupied(&address)?;
+        accounts.lock(&owner, staked_tokens).map_err(|e| e.to_string())?;
         let territory = Territory {
             triangle,
             address,
             owner,
             staked_tokens,
-            yield_tokens: 0.0,
+            yield_tokens: Amount::ZERO,
+            commitment: None,
         };
-        self.territories.insert(hash, territory);
+        self.territories.insert(address, territory);
         Ok(())
     }
 
-        // Cryptographic territory claiming: requires geometric proof and adjacency
-        pub fn cryptographic_claim(&mut self, triangle: Triangle, address: FractalAddress, owner: String, staked_tokens: f64, adjacent_hashes: Vec<String>) -> Result<(), String> {
-            let hash = crate::hash::geometric_hash(&triangle, 8);
-            if self.territories.contains_key(&hash) {
+        // Cryptographic territory claiming: requires geometric proof and adjacency.
+        // `adjacent` are the caller's claimed neighbours; each is checked.
+        pub fn cryptographic_claim(&mut self, accounts: &mut Accounts, triangle: Triangle, address: FractalAddress, owner: String, staked_tokens: Amount, adjacent: Vec<FractalAddress>) -> Result<(), String> {
+            if self.territories.contains_key(&address) {
                 return Err("Territory already claimed".to_string());
             }
             // Geometric validity proof
-            if !crate::validation::verify_geometric_proof(&triangle, 1e-8) {
+            if !verify_geometric_proof(&triangle, 1e-8) {
                 return Err("Invalid geometric proof".to_string());
             }
-            // Adjacency check: must be adjacent to at least one owned territory
-            let mut adjacency_valid = false;
-            for adj_hash in adjacent_hashes {
-                if let Some(adj_territory) = self.territories.get(&adj_hash) {
-                    adjacency_valid = true;
-                    break;
+            // The triangle must be the one its fractal address names
+            if triangle_at(&address) != triangle {
+                return Err("Triangle does not match its fractal address".to_string());
+            }
+            self.check_unoccupied(&address)?;
+            // Every listed neighbour must be a real one, worked out from the addresses
+            for adj_address in &adjacent {
+                match self.territories.get(adj_address) {
+                    Some(adj_territory) if adj_territory.address.is_adjacent_to(&address) => {}
+                    _ => return Err(format!("Forged adjacency claim {}", adj_address)),
                 }
             }
+            // Adjacency check: must border at least one existing territory
+            let adjacency_valid = self.territories.values().any(|territory| territory.address.is_adjacent_to(&address));
             if !adjacency_valid {
                 return Err("No valid adjacency to existing territory".to_string());
             }
+            accounts.lock(&owner, staked_tokens).map_err(|e| e.to_string())?;
             let territory = Territory {
                 triangle,
                 address,
                 owner,
                 staked_tokens,
-                yield_tokens: 0.0,
+                yield_tokens: Amount::ZERO,
+                commitment: None,
             };
-            self.territories.insert(hash, territory);
+            self.territories.insert(address, territory);
             Ok(())
         }
 
-        // Exponential value scaling: triangle value = base_value / (area^2)
-        pub fn triangle_value(&self, triangle: &Triangle, base_value: f64) -> f64 {
-            let area = crate::area::triangle_area(triangle);
-            if area <= 0.0 {
-                return 0.0;
+        // Exponential value scaling: triangle value = base_value / (area^2), rounded down
+        pub fn triangle_value(&self, triangle: &Triangle, base_value: Amount) -> Result<Amount, String> {
+            if triangle.is_degenerate() {
+                return Ok(Amount::ZERO);
             }
-            base_value / (area * area)
+            let area = triangle.area();
+            let area_squared = area.checked_mul(area).ok_or_else(|| "Triangle area overflows".to_string())?;
+            base_value.checked_div_decimal(area_squared, Rounding::Down).map_err(|e| e.to_string())
         }
 
-        // Staking defense: locked tokens protect territory from attacks
-        pub fn defend_territory(&mut self, hash: &str, additional_stake: f64) -> Result<(), String> {
-            if let Some(territory) = self.territories.get_mut(hash) {
-                territory.staked_tokens += additional_stake;
-                Ok(())
-            } else {
-                Err("Territory not found".to_string())
+        // Staking defense: the owner locks more tokens to protect their territory from attacks
+        pub fn defend_territory(&mut self, accounts: &mut Accounts, address: &FractalAddress, staker: &str, additional_stake: Amount) -> Result<(), String> {
+            let territory = self.territories.get_mut(address).ok_or_else(|| "Territory not found".to_string())?;
+            if territory.owner != staker {
+                return Err("Only the owner can stake on a territory".to_string());
             }
+            let staked = territory.staked_tokens.checked_add(additional_stake).map_err(|e| e.to_string())?;
+            accounts.lock(staker, additional_stake).map_err(|e| e.to_string())?;
+            territory.staked_tokens = staked;
+            Ok(())
         }
 
-        // Conquest mechanics: capture triangle through superior geometric proof
-        pub fn conquer_territory(&mut self, hash: &str, challenger: String, challenger_triangle: Triangle, challenger_stake: f64) -> Result<(), String> {
-            if let Some(territory) = self.territories.get(hash) {
-                // Challenger must provide a valid geometric proof and higher stake
-                if !crate::validation::verify_geometric_proof(&challenger_triangle, 1e-8) {
-                    return Err("Challenger geometric proof invalid".to_string());
-                }
-                if challenger_stake > territory.staked_tokens {
-                    let new_territory = Territory {
-                        triangle: challenger_triangle,
-                        address: territory.address.clone(),
-                        owner: challenger,
-                        staked_tokens: challenger_stake,
-                        yield_tokens: territory.yield_tokens,
-                    };
-                    self.territories.insert(hash.to_string(), new_territory);
-                    Ok(())
-                } else {
-                    Err("Challenger stake not sufficient".to_string())
-                }
-            } else {
-                Err("Territory not found".to_string())
+        // Conquest mechanics: take a whole territory by outstaking its owner. Only the
+        // territory's own address can be conquered, so its geometry never changes.
+        // The challenger's stake is locked and the defender's is released.
+        pub fn conquer_territory(&mut self, accounts: &mut Accounts, address: &FractalAddress, challenger: String, challenger_stake: Amount) -> Result<(), String> {
+            let territory = self.territories.get_mut(address).ok_or_else(|| "Territory not found".to_string())?;
+            if challenger_stake <= territory.staked_tokens {
+                return Err("Challenger stake not sufficient".to_string());
+            }
+            if accounts.get(&challenger).balance < challenger_stake {
+                return Err("Challenger cannot fund the stake".to_string());
             }
+            accounts.unlock(&territory.owner, territory.staked_tokens).map_err(|e| e.to_string())?;
+            accounts.lock(&challenger, challenger_stake).expect("balance checked above");
+            territory.owner = challenger;
+            territory.staked_tokens = challenger_stake;
+            // The defender's commitment does not speak for the challenger
+            territory.commitment = None;
+            Ok(())
         }
 
-        // Territorial yield farming: adjacent triangle ownership generates compound rewards
-        pub fn yield_farming(&mut self, hash: &str, reward_rate: f64) -> Result<f64, String> {
-            if let Some(territory) = self.territories.get_mut(hash) {
-                // Count adjacent territories owned by same owner
-                let mut adjacent_owned = 0;
-                for (adj_hash, adj_territory) in &self.territories {
-                    if adj_hash != hash && adj_territory.owner == territory.owner {
-                        // TODO: Check actual geometric adjacency
-                        adjacent_owned += 1;
-                    }
-                }
-                let compound_reward = reward_rate * (1.0 + adjacent_owned as f64).powf(1.2);
-                territory.yield_tokens += compound_reward;
-                Ok(compound_reward)
-            } else {
-                Err("Territory not found".to_string())
+        // Territorial yield farming: adjacent triangle ownership generates compound rewards,
+        // rounded down to base units
+        pub fn yield_farming(&mut self, address: &FractalAddress, reward_rate: Amount) -> Result<Amount, String> {
+            let territory = self.territories.get(address).ok_or_else(|| "Territory not found".to_string())?;
+            // Count edge-adjacent territories owned by same owner
+            let adjacent_owned = self
+                .territories
+                .values()
+                .filter(|adj_territory| {
+                    adj_territory.address != *address
+                        && adj_territory.owner == territory.owner
+                        && adj_territory.triangle.shares_edge(&territory.triangle)
+                })
+                .count();
+            let factor = Decimal::from(1 + adjacent_owned as u64)
+                .checked_powd(Decimal::new(12, 1))
+                .ok_or_else(|| "Yield factor overflows".to_string())?;
+            let compound_reward = reward_rate.checked_mul_decimal(factor, Rounding::Down).map_err(|e| e.to_string())?;
+            if let Some(territory) = self.territories.get_mut(address) {
+                territory.yield_tokens = territory.yield_tokens.checked_add(compound_reward).map_err(|e| e.to_string())?;
+            }
+            Ok(compound_reward)
+        }
+
+        // Territories may touch along edges or at vertices but never overlap: no claimed
+        // address may contain the new one or lie inside it
+        fn check_unoccupied(&self, address: &FractalAddress) -> Result<(), String> {
+            let inside = self.territories.range(address..).next().is_some_and(|(claimed, _)| address.is_ancestor_of(claimed));
+            if self.territory_at(address).is_some() || inside {
+                return Err("Territory overlaps an existing claim".to_string());
             }
+            Ok(())
         }
 
-    // Get territory by geometric hash
-    pub fn get_territory(&self, hash: &str) -> Option<&Territory> {
-        self.territories.get(hash)
+    // The territory covering a fractal address: the address itself or its nearest
+    // claimed ancestor. Claims never overlap, so there is at most one.
+    pub fn territory_at(&self, address: &FractalAddress) -> Option<&Territory> {
+        std::iter::successors(Some(*address), FractalAddress::parent).find_map(|ancestor| self.territories.get(&ancestor))
+    }
+
+    // Check a zero-knowledge proof that `owner` holds a territory satisfying `statement`,
+    // against the commitments recorded for `owner`'s claims. The proof is bound to `owner`
+    // and does not say which of their territories it is about.
+    pub fn verify_ownership(&self, owner: &str, statement: &Statement, proof: &OwnershipProof) -> Result<(), ZkError> {
+        let holds = self
+            .territories
+            .values()
+            .filter(|territory| territory.owner == owner)
+            .filter_map(|territory| territory.commitment.as_ref())
+            .any(|commitment| proof.verify(commitment, statement, owner.as_bytes()).is_ok());
+        if holds { Ok(()) } else { Err(ZkError::InvalidProof) }
+    }
+
+    // Get territory by fractal address
+    pub fn get_territory(&self, address: &FractalAddress) -> Option<&Territory> {
+        self.territories.get(address)
     }
 }
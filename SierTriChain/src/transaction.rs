@@ -0,0 +1,600 @@
+//! Transactions carried in blocks.
+//!
+//! Every transaction has a canonical binary encoding, which is what gets
+//! signed and hashed:
+//!
+//! ```text
+//! tag: u8 | sender: bytes | nonce: u64 | fee: u64 | <variant fields> | signature: bytes
+//! ```
+//!
+//! Integers are little-endian, `bytes` and strings are a `u32` length
+//! followed by the data, and a fractal address is its depth as a `u8`
+//! followed by its packed digits, `ceil(depth / 4)` bytes. The signature
+//! covers everything before it, and the txid is the SHA-256 of the whole
+//! encoding. Amounts, stakes and fees are in token base units; see
+//! [`Amount`](crate::defi::token::Amount).
+//!
+//! The sender is an [`Address`] and the signature field holds a
+//! [`Witness`] by the key it hashes, so anyone can check who authorised a
+//! transaction. A multisig sender's signature field holds a
+//! [`MultisigWitness`] instead, which co-owners build up one signature at a
+//! time in a [`PartiallySignedTransaction`].
+
+use crate::crypto::crypto::{Address, CryptoError, KeyPair, Signature, Witness};
+use crate::crypto::multisig::{self, MultisigPolicy, MultisigWitness};
+use crate::geometry::hierarchy::{FractalAddress, MAX_DEPTH};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Fields every transaction carries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxMeta {
+    /// Address of the account paying the fee and authorising the transaction.
+    pub sender: String,
+    /// Position in the sender's sequence of transactions, starting at zero.
+    pub nonce: u64,
+    pub fee: u64,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Transaction {
+    Transfer { meta: TxMeta, to: String, amount: u64 },
+    /// Claim an unowned territory, locking `stake` to defend it. The
+    /// claim records an encoded
+    /// [`TerritoryCommitment`](crate::crypto::zk::TerritoryCommitment) to
+    /// the address, and `proof` is an encoded
+    /// [`AddressProof`](crate::crypto::zk::AddressProof) that it opens to
+    /// it, bound to the sender.
+    TerritoryClaim { meta: TxMeta, address: FractalAddress, stake: u64, commitment: Vec<u8>, proof: Vec<u8> },
+    /// Take a territory by outstaking its owner, recording a commitment as
+    /// a claim does.
+    TerritoryConquest { meta: TxMeta, address: FractalAddress, stake: u64, commitment: Vec<u8>, proof: Vec<u8> },
+    /// Add to the stake defending one's own territory.
+    Stake { meta: TxMeta, address: FractalAddress, amount: u64 },
+    ContractDeploy { meta: TxMeta, code: Vec<u8> },
+    /// Call the contract deployed by the transaction with txid `contract`.
+    ContractCall { meta: TxMeta, contract: [u8; 32], input: Vec<u8> },
+}
+
+/// Why bytes are not the canonical encoding of a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    UnknownTag(u8),
+    InvalidUtf8,
+    InvalidAddress,
+    /// A partially signed transaction's policy or signatures are malformed.
+    InvalidMultisig,
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "transaction encoding ends early"),
+            DecodeError::UnknownTag(tag) => write!(f, "unknown transaction tag {}", tag),
+            DecodeError::InvalidUtf8 => write!(f, "string field is not UTF-8"),
+            DecodeError::InvalidAddress => write!(f, "fractal address is not canonical"),
+            DecodeError::InvalidMultisig => write!(f, "malformed multisig policy or signature"),
+            DecodeError::TrailingBytes => write!(f, "bytes left over after transaction"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Transaction {
+    pub fn meta(&self) -> &TxMeta {
+        match self {
+            Transaction::Transfer { meta, .. }
+            | Transaction::TerritoryClaim { meta, .. }
+            | Transaction::TerritoryConquest { meta, .. }
+            | Transaction::Stake { meta, .. }
+            | Transaction::ContractDeploy { meta, .. }
+            | Transaction::ContractCall { meta, .. } => meta,
+        }
+    }
+
+    pub fn meta_mut(&mut self) -> &mut TxMeta {
+        match self {
+            Transaction::Transfer { meta, .. }
+            | Transaction::TerritoryClaim { meta, .. }
+            | Transaction::TerritoryConquest { meta, .. }
+            | Transaction::Stake { meta, .. }
+            | Transaction::ContractDeploy { meta, .. }
+            | Transaction::ContractCall { meta, .. } => meta,
+        }
+    }
+
+    pub fn sender(&self) -> &str {
+        &self.meta().sender
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.meta().nonce
+    }
+
+    pub fn fee(&self) -> u64 {
+        self.meta().fee
+    }
+
+    /// Base units taken from the sender's balance besides the fee: the
+    /// amount sent or the stake locked.
+    pub fn value(&self) -> u64 {
+        match self {
+            Transaction::Transfer { amount, .. } | Transaction::Stake { amount, .. } => *amount,
+            Transaction::TerritoryClaim { stake, .. } | Transaction::TerritoryConquest { stake, .. } => *stake,
+            Transaction::ContractDeploy { .. } | Transaction::ContractCall { .. } => 0,
+        }
+    }
+
+    /// The encoding without the signature, which is what the sender signs.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let meta = self.meta();
+        out.push(self.tag());
+        put_bytes(&mut out, meta.sender.as_bytes());
+        out.extend_from_slice(&meta.nonce.to_le_bytes());
+        out.extend_from_slice(&meta.fee.to_le_bytes());
+        match self {
+            Transaction::Transfer { to, amount, .. } => {
+                put_bytes(&mut out, to.as_bytes());
+                out.extend_from_slice(&amount.to_le_bytes());
+            }
+            Transaction::TerritoryClaim { address, stake, commitment, proof, .. }
+            | Transaction::TerritoryConquest { address, stake, commitment, proof, .. } => {
+                put_address(&mut out, address);
+                out.extend_from_slice(&stake.to_le_bytes());
+                put_bytes(&mut out, commitment);
+                put_bytes(&mut out, proof);
+            }
+            Transaction::Stake { address, amount, .. } => {
+                put_address(&mut out, address);
+                out.extend_from_slice(&amount.to_le_bytes());
+            }
+            Transaction::ContractDeploy { code, .. } => put_bytes(&mut out, code),
+            Transaction::ContractCall { contract, input, .. } => {
+                out.extend_from_slice(contract);
+                put_bytes(&mut out, input);
+            }
+        }
+        out
+    }
+
+    /// Signs as `keypair`, whose address must be the sender.
+    pub fn sign(&mut self, keypair: &KeyPair) -> Result<(), CryptoError> {
+        if keypair.address().to_string() != self.sender() {
+            return Err(CryptoError::WrongSigner);
+        }
+        let witness = Witness::sign(keypair, &self.signing_bytes())?;
+        self.meta_mut().signature = witness.encode();
+        Ok(())
+    }
+
+    /// Checks that a transfer's recipient is a canonical address, so funds
+    /// only go to accounts some key can spend from.
+    pub fn verify_recipient(&self) -> Result<(), CryptoError> {
+        match self {
+            Transaction::Transfer { to, .. } => to.parse::<Address>().map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that the sender's key, or enough of a multisig sender's keys,
+    /// signed everything but the signature.
+    pub fn verify_signature(&self) -> Result<(), CryptoError> {
+        let sender: Address = self.sender().parse()?;
+        multisig::verify_witness(&sender, &self.meta().signature, &self.signing_bytes())
+    }
+
+    /// The canonical encoding, signature included.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = self.signing_bytes();
+        put_bytes(&mut out, &self.meta().signature);
+        out
+    }
+
+    /// Parses a canonical encoding; anything else is rejected, so a
+    /// transaction has exactly one encoding and one txid.
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader { bytes };
+        let tag = reader.u8()?;
+        if tag > 5 {
+            return Err(DecodeError::UnknownTag(tag));
+        }
+        let sender = reader.string()?;
+        let nonce = reader.u64()?;
+        let fee = reader.u64()?;
+        let meta = |signature| TxMeta { sender, nonce, fee, signature };
+        let transaction = match tag {
+            0 => {
+                let to = reader.string()?;
+                let amount = reader.u64()?;
+                Transaction::Transfer { meta: meta(reader.bytes()?), to, amount }
+            }
+            1 | 2 => {
+                let address = reader.address()?;
+                let stake = reader.u64()?;
+                let commitment = reader.bytes()?;
+                let proof = reader.bytes()?;
+                let meta = meta(reader.bytes()?);
+                match tag {
+                    1 => Transaction::TerritoryClaim { meta, address, stake, commitment, proof },
+                    _ => Transaction::TerritoryConquest { meta, address, stake, commitment, proof },
+                }
+            }
+            3 => {
+                let address = reader.address()?;
+                let amount = reader.u64()?;
+                Transaction::Stake { meta: meta(reader.bytes()?), address, amount }
+            }
+            4 => {
+                let code = reader.bytes()?;
+                Transaction::ContractDeploy { meta: meta(reader.bytes()?), code }
+            }
+            _ => {
+                let contract = reader.take(32)?.try_into().map_err(|_| DecodeError::UnexpectedEnd)?;
+                let input = reader.bytes()?;
+                Transaction::ContractCall { meta: meta(reader.bytes()?), contract, input }
+            }
+        };
+        if !reader.bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(transaction)
+    }
+
+    /// SHA-256 of the canonical encoding.
+    pub fn txid(&self) -> [u8; 32] {
+        Sha256::digest(self.encode()).into()
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            Transaction::Transfer { .. } => 0,
+            Transaction::TerritoryClaim { .. } => 1,
+            Transaction::TerritoryConquest { .. } => 2,
+            Transaction::Stake { .. } => 3,
+            Transaction::ContractDeploy { .. } => 4,
+            Transaction::ContractCall { .. } => 5,
+        }
+    }
+}
+
+/// A transaction from a multisig account, gathering its co-owners'
+/// signatures. Each co-owner can sign their own copy offline and the copies
+/// be combined, or pass one copy along; once the threshold is met it
+/// finalizes into a signed transaction.
+///
+/// Encoded as the unsigned transaction and the policy, each as `bytes`,
+/// then a `u8` count of signatures, each a `u8` key index and `bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartiallySignedTransaction {
+    transaction: Transaction,
+    policy: MultisigPolicy,
+    signatures: BTreeMap<usize, Signature>,
+}
+
+impl PartiallySignedTransaction {
+    /// Starts collecting signatures for `transaction`, whose sender must be
+    /// the policy's address.
+    pub fn new(mut transaction: Transaction, policy: MultisigPolicy) -> Result<Self, CryptoError> {
+        if transaction.sender() != policy.address().to_string() {
+            return Err(CryptoError::WrongSigner);
+        }
+        transaction.meta_mut().signature.clear();
+        Ok(PartiallySignedTransaction { transaction, policy, signatures: BTreeMap::new() })
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn policy(&self) -> &MultisigPolicy {
+        &self.policy
+    }
+
+    pub fn signatures(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() >= self.policy.threshold()
+    }
+
+    /// Adds a signature by `keypair`, which must be one of the policy's keys.
+    pub fn sign(&mut self, keypair: &KeyPair) -> Result<(), CryptoError> {
+        let index = self.policy.index_of(&keypair.public).ok_or(CryptoError::NotACosigner)?;
+        let signature = keypair.secret.sign(&self.transaction.signing_bytes())?;
+        self.signatures.insert(index, signature);
+        Ok(())
+    }
+
+    /// Adds the signatures of another copy of the same transaction. Nothing
+    /// is added unless all of them verify.
+    pub fn combine(&mut self, other: &PartiallySignedTransaction) -> Result<(), CryptoError> {
+        if other.policy != self.policy {
+            return Err(CryptoError::WrongSigner);
+        }
+        let message = self.transaction.signing_bytes();
+        for (index, signature) in &other.signatures {
+            self.policy.keys()[*index].verify(&message, signature)?;
+        }
+        self.signatures.extend(other.signatures.iter().map(|(index, signature)| (*index, signature.clone())));
+        Ok(())
+    }
+
+    /// The transaction with every signature gathered, once they meet the
+    /// threshold and all verify.
+    pub fn finalize(&self) -> Result<Transaction, CryptoError> {
+        let witness = MultisigWitness {
+            policy: self.policy.clone(),
+            signatures: self.signatures.iter().map(|(index, signature)| (*index, signature.clone())).collect(),
+        };
+        let mut transaction = self.transaction.clone();
+        witness.verify(&self.policy.address(), &transaction.signing_bytes())?;
+        transaction.meta_mut().signature = witness.encode();
+        Ok(transaction)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put_bytes(&mut out, &self.transaction.encode());
+        put_bytes(&mut out, &self.policy.encode());
+        out.push(self.signatures.len() as u8);
+        for (index, signature) in &self.signatures {
+            out.push(*index as u8);
+            put_bytes(&mut out, &signature.bytes);
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader { bytes };
+        let transaction = Transaction::decode(&reader.bytes()?)?;
+        let policy = reader.bytes()?;
+        let (policy, rest) = MultisigPolicy::decode(&policy).map_err(|_| DecodeError::InvalidMultisig)?;
+        if !rest.is_empty() {
+            return Err(DecodeError::InvalidMultisig);
+        }
+        let mut psbt = Self::new(transaction, policy).map_err(|_| DecodeError::InvalidMultisig)?;
+        for _ in 0..reader.u8()? {
+            let index = reader.u8()? as usize;
+            let algorithm = psbt.policy.keys().get(index).ok_or(DecodeError::InvalidMultisig)?.algorithm;
+            let bytes = reader.bytes()?;
+            if bytes.len() != algorithm.signature_len() || psbt.signatures.insert(index, Signature { algorithm, bytes }).is_some() {
+                return Err(DecodeError::InvalidMultisig);
+            }
+        }
+        if !reader.bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(psbt)
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn put_address(out: &mut Vec<u8>, address: &FractalAddress) {
+    out.push(address.depth() as u8);
+    out.extend_from_slice(&address.as_bytes()[..address.depth().div_ceil(4)]);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("took 8 bytes")))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, DecodeError> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().expect("took 4 bytes"));
+        Ok(self.take(len as usize)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        String::from_utf8(self.bytes()?).map_err(|_| DecodeError::InvalidUtf8)
+    }
+
+    fn address(&mut self) -> Result<FractalAddress, DecodeError> {
+        let depth = self.u8()? as usize;
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::InvalidAddress);
+        }
+        let digits = self.take(depth.div_ceil(4))?;
+        let mut packed = [0; MAX_DEPTH / 4];
+        packed[..digits.len()].copy_from_slice(digits);
+        let address = FractalAddress::from_bytes(&packed, depth).map_err(|_| DecodeError::InvalidAddress)?;
+        // Bits past the last digit must be zero.
+        if address.as_bytes()[..digits.len()] != *digits {
+            return Err(DecodeError::InvalidAddress);
+        }
+        Ok(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::crypto::{Ed25519, SignatureScheme};
+
+    fn meta(nonce: u64) -> TxMeta {
+        TxMeta { sender: "alice".to_string(), nonce, fee: 10, signature: vec![0xaa; 4] }
+    }
+
+    fn samples() -> Vec<Transaction> {
+        let address = FractalAddress::from_digits(&[3, 1, 2, 0, 1]).unwrap();
+        vec![
+            Transaction::Transfer { meta: meta(0), to: "bob".to_string(), amount: 500 },
+            Transaction::TerritoryClaim { meta: meta(1), address, stake: 50, commitment: vec![1; 3], proof: vec![2; 2] },
+            Transaction::TerritoryConquest { meta: meta(2), address, stake: 80, commitment: vec![3], proof: vec![] },
+            Transaction::Stake { meta: meta(3), address, amount: 5 },
+            Transaction::ContractDeploy { meta: meta(4), code: vec![1, 2, 3] },
+            Transaction::ContractCall { meta: meta(5), contract: [7; 32], input: vec![9] },
+        ]
+    }
+
+    #[test]
+    fn test_encoding_round_trips() {
+        for tx in samples() {
+            assert_eq!(Transaction::decode(&tx.encode()), Ok(tx));
+        }
+    }
+
+    #[test]
+    fn test_transfer_layout() {
+        let tx = Transaction::Transfer { meta: meta(1), to: "bob".to_string(), amount: 2 };
+        let expected = [
+            &[0][..],
+            &[5, 0, 0, 0],
+            b"alice",
+            &[1, 0, 0, 0, 0, 0, 0, 0],
+            &[10, 0, 0, 0, 0, 0, 0, 0],
+            &[3, 0, 0, 0],
+            b"bob",
+            &[2, 0, 0, 0, 0, 0, 0, 0],
+            &[4, 0, 0, 0],
+            &[0xaa; 4],
+        ]
+        .concat();
+        assert_eq!(tx.encode(), expected);
+        assert_eq!(tx.signing_bytes(), expected[..expected.len() - 8]);
+    }
+
+    #[test]
+    fn test_txid_covers_every_field() {
+        let txids: Vec<_> = samples().iter().map(Transaction::txid).collect();
+        for (i, a) in txids.iter().enumerate() {
+            assert!(txids[i + 1..].iter().all(|b| a != b));
+        }
+        let mut resigned = samples()[0].clone();
+        resigned.meta_mut().signature = vec![0xbb; 4];
+        assert_ne!(resigned.txid(), txids[0]);
+    }
+
+    #[test]
+    fn test_signatures() {
+        let keypair = Ed25519.keypair_from_seed(&[5; 32]);
+        let sender = keypair.address().to_string();
+        let meta = TxMeta { sender, nonce: 0, fee: 10, signature: vec![] };
+        let mut tx = Transaction::Transfer { meta, to: "bob".to_string(), amount: 500 };
+        assert_eq!(tx.verify_signature(), Err(CryptoError::InvalidSignature));
+        tx.sign(&keypair).unwrap();
+        assert_eq!(tx.verify_signature(), Ok(()));
+
+        let mut tampered = tx.clone();
+        if let Transaction::Transfer { amount, .. } = &mut tampered {
+            *amount += 1;
+        }
+        assert_eq!(tampered.verify_signature(), Err(CryptoError::VerificationFailed));
+
+        // The sender is spelled only one way, so one key owns one account.
+        let mut shouted = tx.clone();
+        shouted.meta_mut().sender = tx.sender().to_uppercase();
+        assert_eq!(shouted.verify_signature(), Err(CryptoError::InvalidAddress));
+
+        let mallory = Ed25519.keypair_from_seed(&[6; 32]);
+        assert_eq!(tx.clone().sign(&mallory), Err(CryptoError::WrongSigner));
+        let mut stolen = tx.clone();
+        stolen.meta_mut().signature = Witness::sign(&mallory, &tx.signing_bytes()).unwrap().encode();
+        assert_eq!(stolen.verify_signature(), Err(CryptoError::WrongSigner));
+        assert_eq!(samples()[0].verify_signature(), Err(CryptoError::InvalidAddress));
+
+        // Nobody holds a key for "bob", nor for a respelled address.
+        assert_eq!(tx.verify_recipient(), Err(CryptoError::InvalidAddress));
+        let mut paid = tx.clone();
+        if let Transaction::Transfer { to, .. } = &mut paid {
+            *to = mallory.address().to_string();
+        }
+        assert_eq!(paid.verify_recipient(), Ok(()));
+        if let Transaction::Transfer { to, .. } = &mut paid {
+            *to = to.to_uppercase();
+        }
+        assert_eq!(paid.verify_recipient(), Err(CryptoError::InvalidAddress));
+    }
+
+    #[test]
+    fn test_post_quantum_sender() {
+        let keypair = crate::crypto::pq::MlDsa65.keypair_from_seed(&[5; 32]);
+        let meta = TxMeta { sender: keypair.address().to_string(), nonce: 0, fee: 10, signature: vec![] };
+        let mut tx = Transaction::Stake { meta, address: FractalAddress::from_digits(&[1]).unwrap(), amount: 5 };
+        tx.sign(&keypair).unwrap();
+        assert_eq!(Transaction::decode(&tx.encode()).unwrap().verify_signature(), Ok(()));
+    }
+
+    #[test]
+    fn test_multisig_sender() {
+        use crate::crypto::crypto::Secp256k1;
+        let owners = [Ed25519.keypair_from_seed(&[1; 32]), Ed25519.keypair_from_seed(&[2; 32]), Secp256k1.keypair_from_seed(&[3; 32])];
+        let policy = MultisigPolicy::new(2, owners.iter().map(|k| k.public.clone()).collect()).unwrap();
+        let meta = TxMeta { sender: policy.address().to_string(), nonce: 0, fee: 10, signature: vec![] };
+        let tx = Transaction::Transfer { meta, to: "bob".to_string(), amount: 500 };
+        let unsigned = PartiallySignedTransaction::new(tx.clone(), policy.clone()).unwrap();
+
+        // Two co-owners sign their own copies offline; the copies travel as bytes.
+        let mut first = unsigned.clone();
+        first.sign(&owners[0]).unwrap();
+        assert_eq!(first.finalize(), Err(CryptoError::ThresholdNotMet { signatures: 1, threshold: 2 }));
+        let mut third = PartiallySignedTransaction::decode(&unsigned.encode()).unwrap();
+        third.sign(&owners[2]).unwrap();
+        first.combine(&PartiallySignedTransaction::decode(&third.encode()).unwrap()).unwrap();
+        assert!(first.is_complete());
+        let signed = first.finalize().unwrap();
+        assert_eq!(Transaction::decode(&signed.encode()).unwrap().verify_signature(), Ok(()));
+
+        let mallory = Ed25519.keypair_from_seed(&[9; 32]);
+        assert_eq!(unsigned.clone().sign(&mallory), Err(CryptoError::NotACosigner));
+        let mut tampered = signed.clone();
+        if let Transaction::Transfer { amount, .. } = &mut tampered {
+            *amount += 1;
+        }
+        assert_eq!(tampered.verify_signature(), Err(CryptoError::VerificationFailed));
+        let mut other = PartiallySignedTransaction::new(tampered, policy.clone()).unwrap();
+        other.sign(&owners[1]).unwrap();
+        assert_eq!(first.combine(&other), Err(CryptoError::VerificationFailed));
+        assert_eq!(first.signatures(), 2);
+
+        // A single owner's witness does not speak for the account.
+        let mut alone = tx.clone();
+        alone.meta_mut().signature = Witness::sign(&owners[0], &tx.signing_bytes()).unwrap().encode();
+        assert_eq!(alone.verify_signature(), Err(CryptoError::InvalidSignature));
+        assert_eq!(PartiallySignedTransaction::new(samples()[0].clone(), policy).err(), Some(CryptoError::WrongSigner));
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_bytes() {
+        let mut bytes = samples()[1].encode();
+        bytes.push(0);
+        assert_eq!(Transaction::decode(&bytes), Err(DecodeError::TrailingBytes));
+        assert_eq!(Transaction::decode(&[9]), Err(DecodeError::UnknownTag(9)));
+        assert_eq!(Transaction::decode(&bytes[..10]), Err(DecodeError::UnexpectedEnd));
+
+        // Depth 5 uses two bytes; set a bit past the fifth digit.
+        let claim = samples()[1].encode();
+        let address_at = 1 + 4 + 5 + 16;
+        let mut padded = claim.clone();
+        padded[address_at + 2] |= 0b0001_0000;
+        assert_eq!(Transaction::decode(&padded), Err(DecodeError::InvalidAddress));
+    }
+}
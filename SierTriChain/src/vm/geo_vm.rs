@@ -1,22 +1,89 @@
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::geometry::point::Point;
+    use rust_decimal_macros::dec;
+
     #[test]
     fn test_vm_geo_vm_basic() {
         assert_eq!(2 + 2, 4);
     }
+
+    fn sample() -> Triangle {
+        Triangle::new(Point::new(dec!(0), dec!(0)), Point::new(dec!(3), dec!(0)), Point::new(dec!(0), dec!(3)))
+    }
+
+    #[test]
+    fn test_scale_about_centroid() {
+        let mut vm = VM::new(0);
+        vm.stack.push(sample());
+        vm.execute(Opcode::Scale(dec!(2)));
+        let scaled = vm.stack.pop().unwrap();
+        assert_eq!(scaled.centroid(), sample().centroid());
+        assert_eq!(scaled.area(), sample().area() * dec!(4));
+    }
+
+    #[test]
+    fn test_rotate_moves_vertices_and_keeps_area() {
+        let mut vm = VM::new(0);
+        vm.stack.push(sample());
+        vm.execute(Opcode::Rotate(Decimal::PI));
+        let rotated = vm.stack.pop().unwrap();
+        assert_ne!(rotated, sample());
+        assert!((rotated.area() - sample().area()).abs() < dec!(1e-20));
+    }
+
+    #[test]
+    fn test_subdivide_territory_matches_lattice() {
+        use crate::geometry::subdivision::{triangle_at, FractalAddress};
+        let territory = FractalAddress::from_digits(&[3; 60]).unwrap();
+        let mut vm = VM::new(0);
+        vm.stack.push(triangle_at(&territory));
+        vm.execute(Opcode::Subdivide);
+        let children: Vec<_> = (0..4).map(|d| triangle_at(&territory.child(d).unwrap())).collect();
+        assert_eq!(vm.stack, children);
+
+        vm.stack.clear();
+        vm.stack.push(sample());
+        vm.execute(Opcode::Subdivide);
+        assert_eq!(vm.stack, sample().subdivide());
+    }
+
+    #[test]
+    fn test_intersect_leaves_overlap() {
+        let mut vm = VM::new(0);
+        vm.stack.push(sample());
+        let shifted = Triangle::new(Point::new(dec!(1), dec!(0)), Point::new(dec!(4), dec!(0)), Point::new(dec!(1), dec!(3)));
+        vm.execute(Opcode::Intersect(shifted));
+        let area: Decimal = vm.stack.iter().map(|t| t.area()).sum();
+        assert_eq!(area, dec!(2));
+        assert!(vm.stack.iter().all(|t| sample().contains(t) && shifted.contains(t)));
+    }
+
+    #[test]
+    fn test_intersect_disjoint_empties_slot() {
+        let mut vm = VM::new(0);
+        vm.stack.push(sample());
+        let far = Triangle::new(Point::new(dec!(10), dec!(10)), Point::new(dec!(11), dec!(10)), Point::new(dec!(10), dec!(11)));
+        vm.execute(Opcode::Intersect(far));
+        assert!(vm.stack.is_empty());
+    }
 }
 // Geometric VM for fractal territory smart contracts
 // Includes triangle opcodes, contract storage, DSL stubs, gas pricing, verification, and cross-contract calls
 
+use crate::geometry::hierarchy::MAX_DEPTH;
+use crate::geometry::subdivision::{address_of, Subdivisions};
+use crate::geometry::transform::AffineTransform;
 use crate::geometry::triangle::Triangle;
-use crate::geometry::subdivision::{fractal_subdivide as subdivide, FractalAddress};
+use rust_decimal::Decimal;
 
 #[derive(Clone)]
 pub enum Opcode {
     Subdivide,
-    Rotate(f64), // angle in radians
-    Scale(f64),  // scale factor
-    Intersect(Triangle),
+    Rotate(Decimal), // angle in radians, about the triangle's centroid
+    Scale(Decimal),  // scale factor, about the triangle's centroid
+    Intersect(Triangle), // replaces the top triangle with its overlap, as a fan of triangles
     Store,
     Load,
     Call(String), // cross-contract call by address
@@ -43,29 +110,43 @@ impl VM {
         match op {
             Opcode::Subdivide => {
                 if let Some(tri) = self.stack.pop() {
-                    let subs = subdivide(&tri);
-                    self.stack.extend(subs);
+                    // Territories split exactly as consensus does; anything else by midpoints
+                    match address_of(&tri) {
+                        Some(address) if address.depth() < MAX_DEPTH => {
+                            self.stack.extend(Subdivisions::at_depth(address, address.depth() + 1).map(|(t, _)| Triangle::from(t)))
+                        }
+                        _ => self.stack.extend(tri.subdivide()),
+                    }
                     self.gas_used += self.gas_cost("subdivide");
                 }
             }
             Opcode::Rotate(angle) => {
-                if let Some(mut tri) = self.stack.pop() {
-                    // TODO: Implement rotation
-                    self.stack.push(tri);
+                if let Some(tri) = self.stack.pop() {
+                    // Overflowing transforms leave the triangle unchanged
+                    let rotated = AffineTransform::rotation_about(angle, tri.centroid())
+                        .and_then(|t| t.apply_triangle(&tri));
+                    self.stack.push(rotated.unwrap_or(tri));
                     self.gas_used += self.gas_cost("rotate");
                 }
             }
             Opcode::Scale(factor) => {
-                if let Some(mut tri) = self.stack.pop() {
-                    // TODO: Implement scaling
-                    self.stack.push(tri);
+                if let Some(tri) = self.stack.pop() {
+                    let scaled = AffineTransform::scaling_about(factor, factor, tri.centroid())
+                        .and_then(|t| t.apply_triangle(&tri));
+                    self.stack.push(scaled.unwrap_or(tri));
                     self.gas_used += self.gas_cost("scale");
                 }
             }
             Opcode::Intersect(other) => {
                 if let Some(tri) = self.stack.pop() {
-                    // TODO: Implement intersection
-                    self.stack.push(tri);
+                    let overlap = tri.intersection(&other);
+                    // Fan out from the first vertex; touching triangles leave nothing.
+                    for pair in overlap.windows(2).skip(1) {
+                        let piece = Triangle::new(overlap[0], pair[0], pair[1]);
+                        if !piece.is_degenerate() {
+                            self.stack.push(piece);
+                        }
+                    }
                     self.gas_used += self.gas_cost("intersect");
                 }
             }
@@ -4,64 +4,365 @@ mod tests {
     fn test_wallet_geo_wallet_basic() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn test_mix_transaction_is_deterministic_rigid_motion() {
+        use crate::geometry::triangle::genesis_triangle;
+        use rust_decimal_macros::dec;
+        let wallet = super::Wallet::new("alice".to_string());
+        let t = genesis_triangle();
+        let mixed = wallet.mix_transaction(&t);
+        assert_ne!(mixed, t);
+        assert_eq!(mixed, wallet.mix_transaction(&t));
+        assert!((mixed.area() - t.area()).abs() < dec!(1e-20));
+        assert_ne!(mixed, super::Wallet::new("bob".to_string()).mix_transaction(&t));
+    }
+
+    #[test]
+    fn test_territory_keys_derive_from_the_seed() {
+        use crate::geometry::subdivision::FractalAddress;
+        let seed = [7u8; 32];
+        let mut wallet = super::Wallet::from_seed("alice".to_string(), &seed).unwrap();
+        let region = FractalAddress::from_digits(&[2, 0]).unwrap();
+        let territory = FractalAddress::from_digits(&[2, 0, 3]).unwrap();
+        let key = wallet.derive_hd_key(&territory).unwrap();
+        assert_eq!(wallet.hd_keys[&territory], key.public);
+
+        // The same seed restores the same key, and another seed does not.
+        let mut restored = super::Wallet::from_seed("alice".to_string(), &seed).unwrap();
+        assert_eq!(restored.derive_hd_key(&territory).unwrap().address(), key.address());
+        let mut other = super::Wallet::from_seed("alice".to_string(), &[8u8; 32]).unwrap();
+        assert_ne!(other.derive_hd_key(&territory).unwrap().address(), key.address());
+
+        // A region's extended public key watches its territories without the seed.
+        let watch_only = wallet.territory_xpub(&region).unwrap();
+        assert_eq!(watch_only.derive_child(3).unwrap().address(), key.address());
+        assert_ne!(wallet.derive_hd_key(&region).unwrap().address(), key.address());
+    }
+
+    #[test]
+    fn test_zk_ownership_proofs_only_for_owned_territories() {
+        use crate::crypto::zk::Statement;
+        use crate::defi::token::Amount;
+        use crate::geometry::subdivision::FractalAddress;
+        use crate::state::State;
+        use crate::transaction::TxMeta;
+        let mut state = State::with_balances([("alice", Amount::from_base_units(100))]).unwrap();
+        let mut wallet = super::Wallet::from_seed("alice".to_string(), &[4; 32]).unwrap();
+        let territory = FractalAddress::from_digits(&[0, 2, 2]).unwrap();
+        let meta = TxMeta { sender: "alice".to_string(), nonce: 0, fee: 1, signature: vec![] };
+        let (claim, opening) = wallet.zk_claim(meta, &territory, 10).unwrap();
+        let statement = Statement::InsideRegion { region: FractalAddress::from_digits(&[0]).unwrap(), min_depth: 2 };
+
+        // Once the claim is in, the chain checks proofs against its commitment.
+        state.apply_transaction(&claim).unwrap();
+        let claimed = state.territories().territory_at(&territory).unwrap();
+        let commitment = claimed.commitment.clone().unwrap();
+        assert!(matches!(wallet.zk_prove_ownership(&commitment, &opening, &statement, "alice"), Err(super::WalletError::NotOwned(_))));
+        wallet.owned_territories.insert(territory, claimed.triangle);
+        let proof = wallet.zk_prove_ownership(&commitment, &opening, &statement, "alice").unwrap();
+        assert_eq!(state.territories().verify_ownership("alice", &statement, &proof), Ok(()));
+        // A proof for someone else does not check as theirs, nor as alice's.
+        let relabelled = wallet.zk_prove_ownership(&commitment, &opening, &statement, "bob").unwrap();
+        assert!(state.territories().verify_ownership("bob", &statement, &relabelled).is_err());
+        assert!(state.territories().verify_ownership("alice", &statement, &relabelled).is_err());
+
+        let other = super::Wallet::from_seed("bob".to_string(), &[5; 32]).unwrap();
+        assert!(matches!(other.zk_prove_ownership(&commitment, &opening, &statement, "bob"), Err(super::WalletError::NotOwned(_))));
+    }
+
+    #[test]
+    fn test_co_owned_territory_needs_two_of_three() {
+        use crate::geometry::subdivision::FractalAddress;
+        use crate::transaction::{PartiallySignedTransaction, Transaction, TxMeta};
+        let territory = FractalAddress::from_digits(&[1, 1]).unwrap();
+        let mut owners: Vec<_> = (0..3u8).map(|i| super::Wallet::from_seed(format!("owner {}", i), &[i + 1; 32]).unwrap()).collect();
+        let keys: Vec<_> = owners.iter_mut().map(|w| w.derive_hd_key(&territory).unwrap().public).collect();
+        let policy = owners[0].multisig_policy(&territory, &keys[1..], 2).unwrap();
+        assert_eq!(owners[2].multisig_policy(&territory, &keys[..2], 2).unwrap().address(), policy.address());
+
+        let meta = TxMeta { sender: policy.address().to_string(), nonce: 0, fee: 10, signature: vec![] };
+        let stake = Transaction::Stake { meta, address: territory, amount: 5 };
+        let mut psbt = PartiallySignedTransaction::new(stake, policy).unwrap();
+        owners[0].cosign(&mut psbt, &territory).unwrap();
+        assert!(psbt.finalize().is_err());
+        owners[2].cosign(&mut psbt, &territory).unwrap();
+        assert_eq!(psbt.finalize().unwrap().verify_signature(), Ok(()));
+
+        let mut outsider = super::Wallet::from_seed("mallory".to_string(), &[9; 32]).unwrap();
+        assert_eq!(outsider.cosign(&mut psbt, &territory), Err(super::WalletError::Crypto(crate::crypto::crypto::CryptoError::NotACosigner)));
+    }
+
+    #[test]
+    fn test_mnemonic_recovery_restores_keys_and_holdings() {
+        use crate::crypto::mnemonic::Mnemonic;
+        use crate::geometry::lattice::LatticeTriangle;
+        use crate::geometry::subdivision::FractalAddress;
+        use crate::geometry::triangle::Triangle;
+        use crate::state::Accounts;
+        use crate::territory::TerritoryRegistry;
+        use crate::defi::token::Amount;
+
+        let mnemonic = Mnemonic::generate(12).unwrap();
+        let mut wallet = super::Wallet::from_mnemonic("alice".to_string(), &mnemonic, "").unwrap();
+        let mut registry = TerritoryRegistry::new();
+        let mut accounts = Accounts::default();
+        let mut claim = |digits: &[u8], owner: String| {
+            let address = FractalAddress::from_digits(digits).unwrap();
+            accounts.credit(&owner, Amount::from_base_units(1)).unwrap();
+            registry.claim_territory(&mut accounts, Triangle::from(LatticeTriangle::at(&address)), address, owner, Amount::from_base_units(1)).unwrap();
+        };
+        let first = FractalAddress::from_digits(&[1, 2]).unwrap();
+        let second = FractalAddress::from_digits(&[3]).unwrap();
+        claim(&[1, 2], wallet.derive_hd_key(&first).unwrap().address().to_string());
+        claim(&[3], wallet.derive_hd_key(&second).unwrap().address().to_string());
+        claim(&[0], "bob".to_string());
+
+        let restored = super::Wallet::mnemonic_recovery("alice".to_string(), &mnemonic.phrase(), "", &registry).unwrap();
+        assert_eq!(restored.hd_keys, wallet.hd_keys);
+        assert_eq!(restored.owned_territories.keys().collect::<Vec<_>>(), vec![&first, &second]);
+        assert_eq!(restored.owned_territories[&first], Triangle::from(LatticeTriangle::at(&first)));
+
+        // The wrong passphrase opens an empty wallet; a mistyped phrase is refused.
+        let other = super::Wallet::mnemonic_recovery("alice".to_string(), &mnemonic.phrase(), "secret", &registry).unwrap();
+        assert!(other.owned_territories.is_empty());
+        let mistyped = mnemonic.phrase().replacen(' ', "x ", 1);
+        assert!(matches!(
+            super::Wallet::mnemonic_recovery("alice".to_string(), &mistyped, "", &registry),
+            Err(super::WalletError::Mnemonic(_))
+        ));
+    }
 }
 // Geometric wallet module for fractal territory system
-// Includes HD key derivation, multisig, zk-SNARK stubs, and mnemonic recovery
+// Includes BIP32 territory keys, multisig co-ownership, zero-knowledge ownership proofs, and BIP39 mnemonic recovery
 
-use crate::geometry::subdivision::FractalAddress;
+use crate::crypto::crypto::{CryptoError, KeyPair, PublicKey};
+use crate::crypto::hd::{fractal_path, ExtendedPrivateKey, ExtendedPublicKey, HdError, HARDENED_OFFSET};
+use crate::crypto::mnemonic::{Mnemonic, MnemonicError};
+use crate::crypto::multisig::MultisigPolicy;
+use crate::crypto::zk::{AddressProof, OwnershipProof, Statement, TerritoryCommitment, TerritoryOpening, ZkError};
+use crate::geometry::subdivision::{triangle_at, FractalAddress};
+use crate::geometry::transform::AffineTransform;
 use crate::geometry::triangle::Triangle;
-use std::collections::HashMap;
+use crate::territory::TerritoryRegistry;
+use crate::transaction::{PartiallySignedTransaction, Transaction, TxMeta};
+use rust_decimal::Decimal;
+use rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+// Why a wallet could not be opened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletError {
+    Mnemonic(MnemonicError),
+    Hd(HdError),
+    Crypto(CryptoError),
+    Zk(ZkError),
+    // The wallet holds no territory at this address
+    NotOwned(FractalAddress),
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletError::Mnemonic(e) => write!(f, "mnemonic: {}", e),
+            WalletError::Hd(e) => write!(f, "key derivation: {}", e),
+            WalletError::Crypto(e) => write!(f, "signing: {}", e),
+            WalletError::Zk(e) => write!(f, "ownership proof: {}", e),
+            WalletError::NotOwned(address) => write!(f, "wallet does not own territory {}", address),
+        }
+    }
+}
+
+impl std::error::Error for WalletError {}
+
+impl From<MnemonicError> for WalletError {
+    fn from(e: MnemonicError) -> Self {
+        WalletError::Mnemonic(e)
+    }
+}
+
+impl From<HdError> for WalletError {
+    fn from(e: HdError) -> Self {
+        WalletError::Hd(e)
+    }
+}
+
+impl From<CryptoError> for WalletError {
+    fn from(e: CryptoError) -> Self {
+        WalletError::Crypto(e)
+    }
+}
+
+impl From<ZkError> for WalletError {
+    fn from(e: ZkError) -> Self {
+        WalletError::Zk(e)
+    }
+}
 
 pub struct Wallet {
     pub owner: String,
-    pub hd_keys: HashMap<String, Vec<u8>>, // Key: geometric derivation path
-    pub owned_triangles: Vec<Triangle>,
+    pub hd_keys: HashMap<FractalAddress, PublicKey>, // Public keys derived so far, by territory
+    pub owned_territories: BTreeMap<FractalAddress, Triangle>,
+    pub labels: BTreeMap<FractalAddress, String>, // Names the owner gave territories
+    seed: Vec<u8>,
+    territories: ExtendedPrivateKey, // m/0', the root of the territory tree
 }
 
+// Hardened account under the master key that territory keys descend from
+const TERRITORY_ACCOUNT: u32 = HARDENED_OFFSET;
+
 impl Wallet {
+    // New wallet with a random seed
     pub fn new(owner: String) -> Self {
-        Self {
+        let mut seed = [0u8; 32];
+        OsRng.fill_bytes(&mut seed);
+        let wallet = Self::from_seed(owner, &seed).expect("32-byte seeds are valid");
+        seed.fill(0);
+        wallet
+    }
+
+    // Wallet whose territory keys all derive from `seed`
+    pub fn from_seed(owner: String, seed: &[u8]) -> Result<Self, HdError> {
+        let territories = ExtendedPrivateKey::master(seed)?.derive_child(TERRITORY_ACCOUNT)?;
+        Ok(Self {
             owner,
             hd_keys: HashMap::new(),
-            owned_triangles: Vec::new(),
-        }
+            owned_territories: BTreeMap::new(),
+            labels: BTreeMap::new(),
+            seed: seed.to_vec(),
+            territories,
+        })
+    }
+
+    // Wallet whose seed is stretched from a BIP39 phrase and passphrase
+    pub fn from_mnemonic(owner: String, mnemonic: &Mnemonic, passphrase: &str) -> Result<Self, HdError> {
+        let mut seed = mnemonic.to_seed(passphrase);
+        let wallet = Self::from_seed(owner, &seed);
+        seed.fill(0);
+        wallet
+    }
+
+    // Signing key for a territory: m/0'/d1/d2/... along its fractal address digits.
+    // Children are normal so a region's xpub can watch the territories inside it.
+    pub fn derive_hd_key(&mut self, address: &FractalAddress) -> Result<KeyPair, HdError> {
+        let keypair = self.territory_key(address)?.keypair();
+        self.hd_keys.insert(*address, keypair.public.clone());
+        Ok(keypair)
+    }
+
+    // Extended public key for a territory, for watch-only wallets over everything inside it
+    pub fn territory_xpub(&self, address: &FractalAddress) -> Result<ExtendedPublicKey, HdError> {
+        Ok(self.territory_key(address)?.public())
+    }
+
+    // Seed every key of this wallet derives from
+    pub fn seed(&self) -> &[u8] {
+        &self.seed
+    }
+
+    pub fn set_label(&mut self, address: &FractalAddress, label: &str) {
+        self.labels.insert(*address, label.to_string());
     }
 
-    // Generate HD key using geometric derivation path (base-3 fractal address)
-    pub fn derive_hd_key(&mut self, address: &FractalAddress) -> Vec<u8> {
-        let path = address.0.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("/");
-        // TODO: Use real HD key derivation (e.g., BIP32 + geometric path)
-        let key = path.as_bytes().to_vec();
-        self.hd_keys.insert(path.clone(), key.clone());
-        key
+    fn territory_key(&self, address: &FractalAddress) -> Result<ExtendedPrivateKey, HdError> {
+        let path = fractal_path(&FractalAddress::root(), address, false).expect("the root contains every address");
+        self.territories.derive_path(&path)
     }
 
-    // M-of-N multisig: require geometric proofs from M triangle vertices
-    pub fn multisig_verify(&self, triangle: &Triangle, proofs: Vec<bool>, m: usize) -> bool {
-        proofs.iter().filter(|&&p| p).count() >= m
+    // M-of-N account over this wallet's key for `territory` and the cosigners' keys.
+    // Transactions from its address are signed through a PartiallySignedTransaction.
+    pub fn multisig_policy(&mut self, territory: &FractalAddress, cosigners: &[PublicKey], threshold: usize) -> Result<MultisigPolicy, WalletError> {
+        let mut keys = cosigners.to_vec();
+        keys.push(self.derive_hd_key(territory)?.public);
+        Ok(MultisigPolicy::new(threshold, keys)?)
     }
 
-    // zk-SNARK stub: prove triangle ownership without revealing coordinates
-    pub fn zk_prove_ownership(&self, _triangle: &Triangle) -> Vec<u8> {
-        // TODO: Integrate real zk-SNARK proof system
-        vec![]
+    // Add this wallet's signature, by its key for `territory`, to a co-owned transaction
+    pub fn cosign(&mut self, psbt: &mut PartiallySignedTransaction, territory: &FractalAddress) -> Result<(), WalletError> {
+        let keypair = self.derive_hd_key(territory)?;
+        Ok(psbt.sign(&keypair)?)
     }
 
-    // Geometric transaction mixing: transform triangle coordinates for privacy
+    // Claim a territory as `meta`'s sender, committing to it. The claim carries the
+    // commitment and a proof that it opens to the address; the opening stays with the
+    // wallet's owner and proves statements about the territory once the claim is in.
+    pub fn zk_claim(&self, meta: TxMeta, address: &FractalAddress, stake: u64) -> Result<(Transaction, TerritoryOpening), WalletError> {
+        let (commitment, opening) = TerritoryCommitment::commit(&triangle_at(address), address)?;
+        let proof = AddressProof::prove(&commitment, &opening, meta.sender.as_bytes());
+        let claim = Transaction::TerritoryClaim { meta, address: *address, stake, commitment: commitment.encode(), proof: proof.encode() };
+        Ok((claim, opening))
+    }
+
+    // Zero-knowledge proof that a territory of this wallet satisfies `statement`, for the
+    // account `owner` whose claim recorded `commitment`. Verifiers check it against that
+    // recorded commitment, through `TerritoryRegistry::verify_ownership`.
+    pub fn zk_prove_ownership(
+        &self,
+        commitment: &TerritoryCommitment,
+        opening: &TerritoryOpening,
+        statement: &Statement,
+        owner: &str,
+    ) -> Result<OwnershipProof, WalletError> {
+        if !self.owned_territories.contains_key(opening.address()) {
+            return Err(WalletError::NotOwned(*opening.address()));
+        }
+        Ok(OwnershipProof::prove(commitment, opening, statement, owner.as_bytes())?)
+    }
+
+    // Geometric transaction mixing: transform triangle coordinates for privacy.
+    // The rigid motion is derived from the owner and the triangle, so it is
+    // reproducible by this wallet and preserves the triangle's area.
     pub fn mix_transaction(&self, triangle: &Triangle) -> Triangle {
-        // TODO: Implement geometric mixing (e.g., random rotation/translation)
-        triangle.clone()
+        let mut hasher = Sha256::new();
+        hasher.update(self.owner.as_bytes());
+        for p in [triangle.a, triangle.b, triangle.c] {
+            hasher.update(p.x.serialize());
+            hasher.update(p.y.serialize());
+        }
+        let seed = hasher.finalize();
+        let fraction = |bytes: &[u8]| Decimal::from(u32::from_be_bytes(bytes.try_into().unwrap())) / Decimal::from(u32::MAX);
+        let angle = fraction(&seed[0..4]) * Decimal::TWO_PI;
+        let dx = fraction(&seed[4..8]) * Decimal::TWO - Decimal::ONE;
+        let dy = fraction(&seed[8..12]) * Decimal::TWO - Decimal::ONE;
+        AffineTransform::rotation_about(angle, triangle.centroid())
+            .and_then(|t| t.then(&AffineTransform::translation(dx, dy)))
+            .and_then(|t| t.apply_triangle(triangle))
+            .unwrap_or(*triangle)
+    }
+
+    // Restore a wallet from its BIP39 phrase and passphrase, then find its
+    // territories in the registry
+    pub fn mnemonic_recovery(owner: String, phrase: &str, passphrase: &str, registry: &TerritoryRegistry) -> Result<Self, WalletError> {
+        let mnemonic: Mnemonic = phrase.parse()?;
+        let mut wallet = Self::from_mnemonic(owner, &mnemonic, passphrase)?;
+        wallet.restore_holdings(registry)?;
+        Ok(wallet)
     }
 
-    // Wallet recovery using geometric mnemonic phrases
-    pub fn mnemonic_recovery(&self, mnemonic: &str) -> Option<Vec<u8>> {
-        // TODO: Parse mnemonic based on triangle relationships
-        Some(mnemonic.as_bytes().to_vec())
+    // Claim every territory in the registry owned by this wallet's key for its address.
+    // Keys follow from addresses, so no derivation gap limit is needed.
+    pub fn restore_holdings(&mut self, registry: &TerritoryRegistry) -> Result<(), HdError> {
+        for territory in registry.territories.values() {
+            let key = self.territory_key(&territory.address)?;
+            if key.public().address().to_string() == territory.owner {
+                self.hd_keys.insert(territory.address, key.public().public_key());
+                self.owned_territories.insert(territory.address, territory.triangle);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Wallet {
+    fn drop(&mut self) {
+        self.seed.fill(0);
     }
 }
 
 // 3D wallet visualization stub
 pub fn visualize_wallet(wallet: &Wallet) {
     // TODO: Integrate with 3D visualization library (e.g., WebGL, OpenGL, or Rust egui/three)
-    println!("Visualizing wallet for {} with {} triangles", wallet.owner, wallet.owned_triangles.len());
+    println!("Visualizing wallet for {} with {} triangles", wallet.owner, wallet.owned_territories.len());
 }